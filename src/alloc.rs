@@ -0,0 +1,160 @@
+//! Allocation counting behind the `alloc-stats` feature.
+//!
+//! The feature installs a `#[global_allocator]` that forwards to
+//! [`System`] and counts, per thread, what is allocated. It affects every
+//! binary that links testlog, so enable it only on the dev-dependency.
+
+use crate::record::Metadata;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ops::Sub;
+
+/// Allocation totals of the calling thread; see [`alloc_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Number of allocations, reallocations included.
+    pub allocations: u64,
+    /// Number of frees.
+    pub deallocations: u64,
+    /// Bytes requested by those allocations.
+    pub bytes: u64,
+}
+
+impl Sub for AllocStats {
+    type Output = AllocStats;
+
+    fn sub(self, earlier: AllocStats) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations - earlier.allocations,
+            deallocations: self.deallocations - earlier.deallocations,
+            bytes: self.bytes - earlier.bytes,
+        }
+    }
+}
+
+thread_local! {
+    static STATS: Cell<AllocStats> = const {
+        Cell::new(AllocStats { allocations: 0, deallocations: 0, bytes: 0 })
+    };
+}
+
+fn count(f: impl FnOnce(&mut AllocStats)) {
+    // Allocations made while the thread is being torn down go uncounted.
+    let _ = STATS.try_with(|cell| {
+        let mut stats = cell.get();
+        f(&mut stats);
+        cell.set(stats);
+    });
+}
+
+struct Counting;
+
+// SAFETY: every call is forwarded unchanged to the system allocator.
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(|s| {
+            s.allocations += 1;
+            s.bytes += layout.size() as u64;
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(|s| {
+            s.allocations += 1;
+            s.bytes += layout.size() as u64;
+        });
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count(|s| s.deallocations += 1);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(|s| {
+            s.allocations += 1;
+            s.bytes += new_size as u64;
+        });
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Allocation totals of the calling thread so far.
+///
+/// Subtract two snapshots to get what happened in between, for instance to
+/// assert that a path does not allocate:
+///
+/// ```rust
+/// let before = testlog::alloc_stats();
+/// let sum: u64 = [1, 2, 3].iter().sum();
+/// assert_eq!((testlog::alloc_stats() - before).allocations, 0);
+/// # assert_eq!(sum, 6);
+/// ```
+pub fn alloc_stats() -> AllocStats {
+    STATS.with(Cell::get)
+}
+
+/// Guard created by `test_alloc_stats!`; logs what the block allocated.
+#[doc(hidden)]
+pub struct AllocScope {
+    meta: &'static Metadata,
+    label: String,
+    start: AllocStats,
+}
+
+impl AllocScope {
+    pub fn begin(meta: &'static Metadata, label: &dyn std::fmt::Display) -> Self {
+        let label = label.to_string();
+        AllocScope {
+            meta,
+            label,
+            start: alloc_stats(),
+        }
+    }
+}
+
+impl Drop for AllocScope {
+    fn drop(&mut self) {
+        let stats = alloc_stats() - self.start;
+        crate::emit::log(
+            self.meta,
+            format_args!(
+                "{}: {} allocations ({}), {} frees",
+                self.label,
+                stats.allocations,
+                crate::units::bytes(stats.bytes),
+                stats.deallocations
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_allocations_of_this_thread() {
+        let before = alloc_stats();
+        let v: Vec<u64> = Vec::with_capacity(16);
+        drop(v);
+        let stats = alloc_stats() - before;
+        assert_eq!(stats.allocations, 1);
+        assert_eq!(stats.deallocations, 1);
+        assert_eq!(stats.bytes, 128);
+    }
+
+    #[test]
+    fn macro_logs_the_block_and_returns_its_value() {
+        let _capture = crate::capture();
+        let v = crate::test_alloc_stats!("boxing", { Box::new(5u32) });
+        assert_eq!(*v, 5);
+        let lines = crate::captured_lines();
+        assert!(lines[0].ends_with("boxing: 1 allocations (4 B), 0 frees"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Per-test directories for debug artifacts.
+
+use crate::level::Level;
+use crate::record::Metadata;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+static METADATA: Metadata = Metadata::new(Level::Debug, module_path!(), file!(), line!());
+
+/// The directory handed out to the current thread, removed on thread exit
+/// unless the thread panicked.
+struct ArtifactDir {
+    path: PathBuf,
+}
+
+impl Drop for ArtifactDir {
+    fn drop(&mut self) {
+        if !crate::panic::thread_panicked() && !keep_artifacts() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<ArtifactDir>> = const { RefCell::new(None) };
+}
+
+fn keep_artifacts() -> bool {
+    crate::__private::env_set("TESTLOG_KEEP_ARTIFACTS")
+}
+
+/// Turns a test path such as `parser::tests::empty` into a single path
+/// component.
+pub(crate) fn sanitize(name: &str) -> String {
+    name.replace("::", "__")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "_-.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Name of the directory for the calling thread. libtest names each test
+/// thread after its test; other threads fall back to their id.
+fn dir_name() -> String {
+    let thread = std::thread::current();
+    match thread.name() {
+        Some(name) => sanitize(name),
+        None => sanitize(&format!("{:?}", thread.id())),
+    }
+}
+
+/// The cargo target directory, found by walking up from the test binary to
+/// the first directory holding cargo's `CACHEDIR.TAG`.
+fn target_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(dir);
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.ancestors()
+                .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+                .map(Path::to_path_buf)
+        })
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Returns a directory for the current test's debug artifacts, creating it
+/// on first use.
+///
+/// The directory is `<target>/testlog/<test name>/`. It is emptied the
+/// first time a test asks for it, so it only ever holds output of the
+/// latest run, and it is deleted when the test thread finishes without
+/// panicking. Set `TESTLOG_KEEP_ARTIFACTS=1` to keep it regardless. Tests
+/// marked `#[should_panic]` always keep theirs.
+///
+/// # Panics
+///
+/// Panics if the directory cannot be created.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let dir = testlog::artifact_dir();
+/// std::fs::write(dir.join("render.png"), b"...").unwrap();
+/// ```
+pub fn artifact_dir() -> PathBuf {
+    crate::panic::install();
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        if let Some(dir) = current.as_ref() {
+            return dir.path.clone();
+        }
+        let path = target_dir().join("testlog").join(dir_name());
+        let _ = fs::remove_dir_all(&path);
+        if let Err(e) = fs::create_dir_all(&path) {
+            panic!(
+                "testlog: cannot create artifact directory {}: {e}",
+                path.display()
+            );
+        }
+        crate::emit::log(
+            &METADATA,
+            format_args!("artifact directory: {}", path.display()),
+        );
+        *current = Some(ArtifactDir { path: path.clone() });
+        path
+    })
+}
+
+/// Backend of `test_artifact!`: writes `contents` to `name` inside the
+/// artifact directory and logs where it went.
+#[doc(hidden)]
+pub fn write_artifact(meta: &Metadata, name: &str, contents: &[u8]) {
+    let escapes = Path::new(name).components().any(|c| {
+        matches!(
+            c,
+            Component::RootDir | Component::Prefix(_) | Component::ParentDir
+        )
+    });
+    if escapes {
+        crate::emit::log(
+            &Metadata::new(Level::Error, meta.target, meta.file, meta.line),
+            format_args!(
+                "cannot write artifact `{name}`: it must be a relative path inside the artifact directory"
+            ),
+        );
+        return;
+    }
+    let path = artifact_dir().join(name);
+    let written = match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|()| fs::write(&path, contents));
+    match written {
+        Ok(()) => crate::emit::log(
+            meta,
+            format_args!("artifact {} ({} bytes)", path.display(), contents.len()),
+        ),
+        Err(e) => crate::emit::log(
+            &Metadata::new(Level::Error, meta.target, meta.file, meta.line),
+            format_args!("cannot write artifact {}: {e}", path.display()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_test_paths() {
+        assert_eq!(sanitize("a::b::c"), "a__b__c");
+        assert_eq!(sanitize("case (1/2)"), "case__1_2_");
+    }
+
+    #[test]
+    fn same_directory_within_a_test() {
+        let dir = artifact_dir();
+        assert!(dir.is_dir());
+        assert!(dir.ends_with("artifact__tests__same_directory_within_a_test"));
+        assert_eq!(artifact_dir(), dir);
+    }
+
+    #[test]
+    fn artifact_macro_writes_into_the_directory() {
+        crate::test_artifact!("state.json", r#"{"ok":true}"#);
+        crate::test_artifact!("frames/0.bin", [0u8, 1, 2]);
+        let dir = artifact_dir();
+        assert_eq!(fs::read(dir.join("frames/0.bin")).unwrap(), [0, 1, 2]);
+        assert_eq!(
+            fs::read_to_string(dir.join("state.json")).unwrap(),
+            r#"{"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn names_cannot_leave_the_directory() {
+        let _capture = crate::capture();
+        let outside = std::env::temp_dir().join("testlog-artifact-escape");
+        crate::test_artifact!(outside.to_str().unwrap(), "no");
+        crate::test_artifact!("../../testlog-artifact-escape", "no");
+        assert!(!outside.exists());
+        assert!(!target_dir().join("testlog-artifact-escape").exists());
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 2, "{lines:#?}");
+        assert!(lines
+            .iter()
+            .all(|l| l.starts_with("ERROR cannot write artifact `")));
+        assert!(lines[1].contains("`../../testlog-artifact-escape`: it must be a relative path"));
+    }
+
+    #[test]
+    fn removed_after_passing_thread_kept_after_panic() {
+        let passed = std::thread::Builder::new()
+            .name("artifact-pass".into())
+            .spawn(artifact_dir)
+            .unwrap()
+            .join()
+            .unwrap();
+        if !keep_artifacts() {
+            assert!(!passed.exists());
+        }
+
+        let failed = std::thread::Builder::new()
+            .name("artifact-fail".into())
+            .spawn(|| {
+                let dir = artifact_dir();
+                fs::write(dir.join("state.txt"), "boom").unwrap();
+                panic!("intentional failure");
+            })
+            .unwrap()
+            .join();
+        assert!(failed.is_err());
+        let kept = target_dir().join("testlog").join("artifact-fail");
+        assert!(kept.join("state.txt").is_file());
+        fs::remove_dir_all(kept).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+//! Failure path of [`test_assert!`](crate::test_assert).
+
+use crate::record::Metadata;
+use std::fmt;
+
+/// Logs the failed condition with the values it mentions, then panics like
+/// `assert!` at the caller's location.
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_failed(
+    meta: &Metadata,
+    cond: &str,
+    values: &[(&str, String)],
+    message: Option<fmt::Arguments>,
+) -> ! {
+    crate::emit::log(meta, format_args!("{}", render(cond, values)));
+    match message {
+        Some(message) => panic!("{message}"),
+        None => panic!("assertion failed: {cond}"),
+    }
+}
+
+fn render(cond: &str, values: &[(&str, String)]) -> String {
+    let mut out = format!("assertion failed: `{cond}`");
+    for (name, value) in values {
+        let value = value.replace('\n', "\n  ");
+        out.push_str(&format!("\n  {name} = {value}"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_values_under_the_condition() {
+        assert_eq!(
+            render("a < b", &[("a", "5".into()), ("b", "3".into())]),
+            "assertion failed: `a < b`\n  a = 5\n  b = 3"
+        );
+    }
+
+    #[test]
+    fn logs_mentioned_variables_before_panicking() {
+        let _capture = crate::capture();
+        let limit = 3;
+        let items = vec![1, 5, 2];
+        let result = std::panic::catch_unwind(|| {
+            crate::test_assert!(
+                items.iter().all(|x| *x < limit) && !items.is_empty(),
+                "limit {} exceeded",
+                limit
+            );
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "limit 3 exceeded"
+        );
+        let lines = crate::captured_lines();
+        let logged: Vec<_> = lines[0].lines().map(str::trim).collect();
+        assert_eq!(logged[1..], ["items = [1, 5, 2]", "limit = 3"]);
+        crate::test_assert!(limit as u64 == 3u64);
+    }
+}
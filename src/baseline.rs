@@ -0,0 +1,467 @@
+//! Recorded log streams that later runs are compared against.
+//!
+//! While a [`BaselineGuard`] is alive, every record shown on its thread is
+//! also kept as `LEVEL message`, [normalized](Normalize) so that values
+//! which change from run to run do not count as differences. When the
+//! guard is dropped the stream is written to the test's baseline file
+//! (`TESTLOG_BASELINE=record`), or compared with it and the difference
+//! logged and turned into a test failure.
+//!
+//! Baselines live in `tests/baselines/<test binary>/<test name>.log` under
+//! the package, or under `TESTLOG_BASELINE_DIR`, and are meant to be
+//! committed.
+
+use crate::level::Level;
+use crate::record::Metadata;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+static METADATA: Metadata = Metadata::new(Level::Warn, module_path!(), file!(), line!());
+
+/// Unchanged lines shown around each difference.
+const CONTEXT: usize = 3;
+
+/// What [`#[testlog::test]`](macro@crate::test) does with baselines,
+/// selected with `TESTLOG_BASELINE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BaselineMode {
+    /// Only tests that ask for a baseline use one; they compare against it.
+    #[default]
+    Off,
+    /// Every attributed test compares its log with its baseline, recording
+    /// one if there is none yet.
+    Compare,
+    /// Every test that uses a baseline overwrites it with this run's log.
+    Record,
+}
+
+impl FromStr for BaselineMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "" => Ok(BaselineMode::Off),
+            "compare" => Ok(BaselineMode::Compare),
+            "record" => Ok(BaselineMode::Record),
+            other => Err(format!("unknown baseline mode `{other}`")),
+        }
+    }
+}
+
+/// Run-dependent values replaced by placeholders before log streams are
+/// recorded or compared.
+///
+/// Parsed from `TESTLOG_NORMALIZE`: `all` (the default), `none`, or a
+/// comma-separated list of `timestamps`, `addresses` and `durations`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalize {
+    /// Dates and times such as `2024-02-29T12:34:56Z` or `12:34:56.789`
+    /// become `<timestamp>`.
+    pub timestamps: bool,
+    /// Hex numbers of six digits or more, such as pointers printed with
+    /// `{:p}`, become `0x<addr>`.
+    pub addresses: bool,
+    /// Durations such as `1.2ms` or `3s` become `<duration>`.
+    pub durations: bool,
+}
+
+impl Normalize {
+    /// Every normalization.
+    pub const ALL: Normalize = Normalize {
+        timestamps: true,
+        addresses: true,
+        durations: true,
+    };
+
+    /// Streams are compared verbatim.
+    pub const NONE: Normalize = Normalize {
+        timestamps: false,
+        addresses: false,
+        durations: false,
+    };
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Normalize::ALL
+    }
+}
+
+impl FromStr for Normalize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut normalize = Normalize::NONE;
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.to_ascii_lowercase().as_str() {
+                "all" => normalize = Normalize::ALL,
+                "none" => normalize = Normalize::NONE,
+                "timestamps" => normalize.timestamps = true,
+                "addresses" => normalize.addresses = true,
+                "durations" => normalize.durations = true,
+                other => return Err(format!("unknown normalization `{other}`")),
+            }
+        }
+        Ok(normalize)
+    }
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Guard returned by [`baseline`]; records or compares the thread's log
+/// when dropped.
+#[must_use = "the log is only recorded during the guard's lifetime"]
+#[derive(Debug)]
+pub struct BaselineGuard {
+    path: PathBuf,
+    record: bool,
+    previous: Option<Option<Vec<String>>>,
+    // The recording belongs to the thread that started it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl BaselineGuard {
+    /// Ends the recording, returning the failure message if the log
+    /// differs from the baseline.
+    pub(crate) fn finish(mut self) -> Option<String> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Option<String> {
+        let previous = self.previous.take()?;
+        let lines = RECORDING.try_with(|r| r.replace(previous)).ok().flatten()?;
+        if std::thread::panicking() {
+            return None;
+        }
+        let path = &self.path;
+        let mut text = lines.join("\n");
+        text.push('\n');
+        let old = match std::fs::read_to_string(path) {
+            Ok(old) if !self.record => old,
+            Err(err) if !self.record && err.kind() != std::io::ErrorKind::NotFound => {
+                return Some(format!("cannot read baseline {}: {err}", path.display()));
+            }
+            _ => return write(path, &text),
+        };
+        if old == text {
+            return None;
+        }
+        let old: Vec<&str> = old.lines().collect();
+        let new: Vec<&str> = text.lines().collect();
+        let diff = crate::diff::render(&crate::diff::diff(&old, &new), CONTEXT);
+        crate::emit::log(
+            &METADATA,
+            format_args!("log differs from baseline {}:\n{diff}", path.display()),
+        );
+        Some(format!(
+            "log differs from baseline {}; rerun with TESTLOG_BASELINE=record to accept it",
+            path.display()
+        ))
+    }
+}
+
+impl Drop for BaselineGuard {
+    fn drop(&mut self) {
+        if let Some(msg) = self.end() {
+            if !std::thread::panicking() {
+                panic!("{msg}");
+            }
+        }
+    }
+}
+
+/// Writes a baseline, returning the failure message if that fails.
+fn write(path: &std::path::Path, text: &str) -> Option<String> {
+    let written = match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|()| std::fs::write(path, text));
+    match written {
+        Ok(()) => {
+            let meta = Metadata {
+                level: Level::Info,
+                ..METADATA
+            };
+            crate::emit::log(&meta, format_args!("recorded baseline {}", path.display()));
+            None
+        }
+        Err(err) => Some(format!("cannot write baseline {}: {err}", path.display())),
+    }
+}
+
+/// Compares everything the calling test logs until the returned guard is
+/// dropped with the log recorded by an earlier run, failing the test if
+/// they differ.
+///
+/// The first run, and every run with `TESTLOG_BASELINE=record`, records
+/// the baseline instead. For plain `#[test]` functions;
+/// [`#[testlog::test(baseline)]`](macro@crate::test) does the same for the
+/// whole test.
+///
+/// ```rust,no_run
+/// let _baseline = testlog::baseline();
+/// testlog::test_info!("connected in {:?}", std::time::Duration::from_millis(3));
+/// // Recorded as `INFO  connected in <duration>`.
+/// ```
+pub fn baseline() -> BaselineGuard {
+    let name = crate::test::current()
+        .map(str::to_string)
+        .or_else(|| std::thread::current().name().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", std::thread::current().id()));
+    start(path_for(&name))
+}
+
+/// Starts recording the thread's log for the baseline of the test `name`.
+pub(crate) fn for_test(name: &str) -> BaselineGuard {
+    start(path_for(name))
+}
+
+fn start(path: PathBuf) -> BaselineGuard {
+    let record = crate::config::current().baseline == BaselineMode::Record;
+    let previous = RECORDING.with(|r| r.replace(Some(Vec::new())));
+    BaselineGuard {
+        path,
+        record,
+        previous: Some(previous),
+        _not_send: PhantomData,
+    }
+}
+
+/// Adds a shown record to the thread's recording, if there is one.
+pub(crate) fn record(config: &crate::Config, meta: &Metadata, msg: &str) {
+    let _ = RECORDING.try_with(|r| {
+        if let Ok(mut recording) = r.try_borrow_mut() {
+            if let Some(lines) = recording.as_mut() {
+                let msg = normalize(msg, config.normalize).replace('\n', "\n      ");
+                lines.push(format!("{:<5} {msg}", meta.level.as_str()));
+            }
+        }
+    });
+}
+
+/// The file holding the baseline named `name`.
+fn path_for(name: &str) -> PathBuf {
+    let config = crate::config::current();
+    let dir = match &config.baseline_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default()
+            .join("tests")
+            .join("baselines"),
+    };
+    dir.join(binary_stem(&crate::spans::binary_name()))
+        .join(format!("{}.log", crate::artifact::sanitize(name)))
+}
+
+/// Test binary name without the hash cargo appends, e.g. `parser` for
+/// `parser-1a2b3c4d5e6f7a8b`.
+fn binary_stem(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((stem, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            stem
+        }
+        _ => name,
+    }
+}
+
+/// Replaces the values selected by `normalize` with placeholders.
+pub(crate) fn normalize(text: &str, normalize: Normalize) -> String {
+    if normalize == Normalize::NONE {
+        return text.to_string();
+    }
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    let mut in_word = false;
+    while i < bytes.len() {
+        if !in_word {
+            let rest = &bytes[i..];
+            let matched = [
+                (normalize.timestamps, timestamp(rest), "<timestamp>"),
+                (normalize.addresses, address(rest), "0x<addr>"),
+                (normalize.durations, duration(rest), "<duration>"),
+            ]
+            .into_iter()
+            .find(|&(enabled, len, _)| enabled && len > 0);
+            if let Some((_, len, placeholder)) = matched {
+                out.push_str(placeholder);
+                i += len;
+                continue;
+            }
+        }
+        let ch = text[i..]
+            .chars()
+            .next()
+            .expect("index is on a char boundary");
+        in_word = ch.is_alphanumeric() || ch == '_';
+        out.push(ch);
+        i += ch.len_utf8();
+    }
+    out
+}
+
+fn digits(b: &[u8]) -> usize {
+    b.iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+/// `len` if `b` ends a word there, otherwise 0.
+fn word_end(b: &[u8], len: usize) -> usize {
+    match b.get(len) {
+        Some(&c) if c.is_ascii_alphanumeric() || c == b'_' => 0,
+        _ => len,
+    }
+}
+
+/// `HH:MM:SS`, with optional fraction and offset.
+fn time(b: &[u8]) -> usize {
+    let exact = |at: usize, n: usize| b.len() >= at + n && digits(&b[at..]) >= n;
+    if !(exact(0, 2)
+        && b.get(2) == Some(&b':')
+        && exact(3, 2)
+        && b.get(5) == Some(&b':')
+        && exact(6, 2))
+    {
+        return 0;
+    }
+    let mut len = 8;
+    if b.get(len) == Some(&b'.') && digits(&b[len + 1..]) > 0 {
+        len += 1 + digits(&b[len + 1..]);
+    }
+    match b.get(len) {
+        Some(b'Z') => len += 1,
+        Some(b'+' | b'-') if time_offset(&b[len + 1..]) => len += 6,
+        _ => {}
+    }
+    len
+}
+
+fn time_offset(b: &[u8]) -> bool {
+    b.len() >= 5 && digits(b) == 2 && b[2] == b':' && digits(&b[3..]) >= 2
+}
+
+/// `YYYY-MM-DD`, optionally followed by `T` or a space and a time, or a
+/// time on its own.
+fn timestamp(b: &[u8]) -> usize {
+    let date = b.len() >= 10
+        && digits(b) == 4
+        && b[4] == b'-'
+        && digits(&b[5..]) == 2
+        && b[7] == b'-'
+        && digits(&b[8..]) >= 2;
+    let len = if date {
+        match b.get(10) {
+            Some(b'T' | b' ') if time(&b[11..]) > 0 => 11 + time(&b[11..]),
+            _ => 10,
+        }
+    } else {
+        time(b)
+    };
+    if len == 0 {
+        0
+    } else {
+        word_end(b, len)
+    }
+}
+
+/// `0x` and at least six hex digits.
+fn address(b: &[u8]) -> usize {
+    if !b.starts_with(b"0x") {
+        return 0;
+    }
+    let hex = b[2..].iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    if hex < 6 {
+        0
+    } else {
+        word_end(b, 2 + hex)
+    }
+}
+
+/// A number followed by one of the units [`HumanDuration`](crate::HumanDuration)
+/// and `Debug` for `Duration` print.
+fn duration(b: &[u8]) -> usize {
+    let mut len = digits(b);
+    if len == 0 {
+        return 0;
+    }
+    if b.get(len) == Some(&b'.') && digits(&b[len + 1..]) > 0 {
+        len += 1 + digits(&b[len + 1..]);
+    }
+    let rest = &b[len..];
+    ["ns", "µs", "us", "ms", "s", "m", "h"]
+        .iter()
+        .find(|unit| rest.starts_with(unit.as_bytes()))
+        .map_or(0, |unit| word_end(b, len + unit.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_run_dependent_values() {
+        assert_eq!(
+            normalize(
+                "at 2024-02-29T12:34:56.123Z took 1.2ms (4µs idle) state=0x7ffd5a3c10 v2s",
+                Normalize::ALL
+            ),
+            "at <timestamp> took <duration> (<duration> idle) state=0x<addr> v2s"
+        );
+        let only = "durations".parse().unwrap();
+        assert_eq!(
+            normalize("12:00:01 5s 0x1234567", only),
+            "12:00:01 <duration> 0x1234567"
+        );
+        assert_eq!(normalize("3 items", Normalize::ALL), "3 items");
+    }
+
+    #[test]
+    fn strips_the_cargo_hash() {
+        assert_eq!(binary_stem("parser-1a2b3c4d5e6f7a8b"), "parser");
+        assert_eq!(binary_stem("my-tool"), "my-tool");
+    }
+
+    #[test]
+    fn records_then_reports_differences() {
+        let dir = std::env::temp_dir().join(format!("testlog-baseline-{}", std::process::id()));
+        let path = dir.join("run.log");
+        let _capture = crate::capture();
+        let run = |messages: &[&str]| {
+            let mut guard = start(path.clone());
+            guard.record = false;
+            for msg in messages {
+                crate::test_info!("{msg}");
+            }
+            guard.finish()
+        };
+        assert_eq!(run(&["connecting", "connected in 15ms", "done"]), None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "INFO  connecting\nINFO  connected in <duration>\nINFO  done\n"
+        );
+        assert_eq!(run(&["connecting", "connected in 9ms", "done"]), None);
+        let failure = run(&["connecting", "retrying", "connected in 9ms", "done"]).unwrap();
+        assert!(
+            failure.starts_with("log differs from baseline "),
+            "{failure}"
+        );
+        let lines = crate::captured_lines();
+        let logged: Vec<_> = lines.last().unwrap().lines().map(str::trim).collect();
+        assert_eq!(
+            logged[1..],
+            [
+                "INFO  connecting",
+                "+ INFO  retrying",
+                "INFO  connected in <duration>",
+                "INFO  done"
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
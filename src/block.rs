@@ -0,0 +1,91 @@
+//! Verbatim blocks printed by [`test_log_block!`](crate::test_log_block).
+
+use crate::color;
+use crate::record::Metadata;
+use std::fmt;
+
+/// Renders `content` between begin and end markers `width` columns wide.
+///
+/// With `border` every line gets a `│ ` gutter; without it lines are
+/// printed as they are, so the block can be copied out of the log. A single
+/// trailing newline, as files usually have, is dropped.
+fn render(title: &str, content: &str, border: bool, width: usize) -> (String, String, String) {
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    let rule = |corner: &str, title: &str| {
+        let head = crate::section::banner(title, width.saturating_sub(corner.chars().count()));
+        format!("{corner}{head}")
+    };
+    if border {
+        let body = content
+            .split('\n')
+            .map(|line| format!("│ {line}").trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        (rule("┌", title), body, rule("└", ""))
+    } else {
+        let end = format!("end of {title}");
+        (rule("", title), content.to_string(), rule("", &end))
+    }
+}
+
+/// Backend of `test_log_block!`.
+#[doc(hidden)]
+pub fn log_block(
+    meta: &Metadata,
+    title: &dyn fmt::Display,
+    content: &dyn fmt::Display,
+    border: bool,
+) {
+    crate::strict::record(meta.level, format_args!("{title}"));
+    if !crate::emit::shown(meta) {
+        return;
+    }
+    crate::emit::flush_pending();
+    let config = crate::config::current();
+    let width = config
+        .width
+        .saturating_sub(crate::group::depth() * crate::group::INDENT.len());
+    let (begin, body, end) = render(&title.to_string(), &content.to_string(), border, width);
+    let heading = config.theme.heading();
+    let enabled = config.color_enabled();
+    let text = format!(
+        "{}\n{body}\n{}",
+        color::paint(&begin, &heading, enabled),
+        color::paint(&end, &heading, enabled)
+    );
+    crate::emit::write(
+        meta.stream.unwrap_or(config.stream),
+        Some(meta.level),
+        &text,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bordered_and_plain_blocks() {
+        let (begin, body, end) = render("a.toml", "[x]\n\ny = 1\n", true, 16);
+        assert_eq!(begin, "┌──── a.toml ────");
+        assert_eq!(body, "│ [x]\n│\n│ y = 1");
+        assert_eq!(end, "└───────────────");
+        let (begin, body, end) = render("gen", "  fn f() {}", false, 20);
+        assert_eq!(begin, "──── gen ───────────");
+        assert_eq!(body, "  fn f() {}");
+        assert_eq!(end, "──── end of gen ────");
+    }
+
+    #[test]
+    fn blocks_go_through_capture_verbatim() {
+        let _capture = crate::capture();
+        crate::test_log_block!("cfg", "  indented\n\ttabbed", border: false);
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].contains("\n  indented\n\ttabbed\n"),
+            "{}",
+            lines[0]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Hex diffs for [`test_log_bytes_diff!`](crate::test_log_bytes_diff).
+//!
+//! Both inputs are shown as 16-byte hex dump rows. Rows that differ appear
+//! twice, expected (`-`) above actual (`+`), with the differing bytes
+//! marked underneath; one unchanged row of context is kept on each side.
+
+use crate::record::Metadata;
+
+const ROW: usize = 16;
+
+/// Unchanged rows shown around each differing one.
+const CONTEXT_ROWS: usize = 1;
+
+/// Runs of differing rows shown before the rest are only counted.
+const MAX_HUNKS: usize = 8;
+
+/// One dump row of `bytes`, which may be shorter than a row or empty.
+fn row(mark: char, offset: usize, bytes: &[u8]) -> String {
+    let mut out = format!("{mark} {offset:08x} ");
+    for i in 0..ROW {
+        if i == ROW / 2 {
+            out.push(' ');
+        }
+        match bytes.get(i) {
+            Some(b) => out.push_str(&format!(" {b:02x}")),
+            None => out.push_str("   "),
+        }
+    }
+    out.push_str("  |");
+    out.extend(bytes.iter().map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+            b as char
+        } else {
+            '.'
+        }
+    }));
+    out.push('|');
+    out
+}
+
+/// Row `r` of `bytes`, empty past its end.
+fn slice(bytes: &[u8], r: usize) -> &[u8] {
+    let start = (r * ROW).min(bytes.len());
+    &bytes[start..((r + 1) * ROW).min(bytes.len())]
+}
+
+/// `^^` under every byte of row `r` that differs.
+fn markers(r: usize, differs: impl Fn(usize) -> bool) -> String {
+    let mut out = " ".repeat(11);
+    for i in 0..ROW {
+        if i == ROW / 2 {
+            out.push(' ');
+        }
+        out.push_str(if differs(r * ROW + i) { " ^^" } else { "   " });
+    }
+    out.trim_end().to_string()
+}
+
+fn render(names: (&str, &str), expected: &[u8], actual: &[u8]) -> String {
+    let len = expected.len().max(actual.len());
+    let differs = |i: usize| i < len && expected.get(i) != actual.get(i);
+    let count = (0..len).filter(|&i| differs(i)).count();
+    let (a, b) = names;
+    if count == 0 {
+        return format!("`{a}` and `{b}` are equal ({} bytes)", expected.len());
+    }
+    let mut out = format!(
+        "`{a}` and `{b}` differ in {count} byte{} (lengths {} and {})",
+        if count == 1 { "" } else { "s" },
+        expected.len(),
+        actual.len()
+    );
+
+    let rows = len.div_ceil(ROW);
+    let differing: Vec<usize> = (0..rows)
+        .filter(|&r| (r * ROW..(r + 1) * ROW).any(differs))
+        .collect();
+    // Runs of rows to show, as inclusive ranges, merged where context meets.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &r in &differing {
+        let (start, end) = (
+            r.saturating_sub(CONTEXT_ROWS),
+            (r + CONTEXT_ROWS).min(rows - 1),
+        );
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    for &(start, end) in hunks.iter().take(MAX_HUNKS) {
+        if start > 0 {
+            out.push_str("\n…");
+        }
+        for r in start..=end {
+            let (old, new) = (slice(expected, r), slice(actual, r));
+            if differing.binary_search(&r).is_ok() {
+                out.push('\n');
+                out.push_str(&row('-', r * ROW, old));
+                out.push('\n');
+                out.push_str(&row('+', r * ROW, new));
+                out.push('\n');
+                out.push_str(&markers(r, differs));
+            } else {
+                out.push('\n');
+                out.push_str(&row(' ', r * ROW, old));
+            }
+        }
+    }
+    if hunks.len() > MAX_HUNKS {
+        let shown_to = (hunks[MAX_HUNKS - 1].1 + 1) * ROW;
+        let rest = (shown_to..len).filter(|&i| differs(i)).count();
+        out.push_str(&format!(
+            "\n… {rest} more differing bytes after offset {shown_to:#x}"
+        ));
+    } else if hunks.last().is_some_and(|&(_, end)| end + 1 < rows) {
+        out.push_str("\n…");
+    }
+    out
+}
+
+/// Backend of `test_log_bytes_diff!`.
+#[doc(hidden)]
+pub fn log_bytes_diff(meta: &Metadata, names: (&str, &str), expected: &[u8], actual: &[u8]) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    crate::emit::log(meta, format_args!("{}", render(names, expected, actual)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_differing_bytes_with_context() {
+        let expected: Vec<u8> = (0..64).collect();
+        let mut actual = expected.clone();
+        actual[0x21] = 0xff;
+        actual.push(b'!');
+        let text = render(("expected", "actual"), &expected, &actual);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "`expected` and `actual` differ in 2 bytes (lengths 64 and 65)",
+                "…",
+                "  00000010  10 11 12 13 14 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |................|",
+                "- 00000020  20 21 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | !\"#$%&'()*+,-./|",
+                "+ 00000020  20 ff 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | .\"#$%&'()*+,-./|",
+                "               ^^",
+                "  00000030  30 31 32 33 34 35 36 37  38 39 3a 3b 3c 3d 3e 3f  |0123456789:;<=>?|",
+                "- 00000040                                                    ||",
+                "+ 00000040  21                                                |!|",
+                "            ^^",
+            ]
+        );
+    }
+
+    #[test]
+    fn equal_inputs_are_one_line() {
+        assert_eq!(
+            render(("a", "b"), b"abc", b"abc"),
+            "`a` and `b` are equal (3 bytes)"
+        );
+    }
+
+    #[test]
+    fn counts_hunks_past_the_limit() {
+        let expected = vec![0u8; ROW * 40];
+        let actual: Vec<u8> = (0..ROW * 40)
+            .map(|i| u8::from(i % (ROW * 4) == 0))
+            .collect();
+        let text = render(("a", "b"), &expected, &actual);
+        assert!(text.starts_with("`a` and `b` differ in 10 bytes"), "{text}");
+        assert!(
+            text.ends_with("… 2 more differing bytes after offset 0x1e0"),
+            "{text}"
+        );
+    }
+}
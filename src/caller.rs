@@ -0,0 +1,107 @@
+//! Logging attributed to the caller of a `#[track_caller]` function.
+
+use crate::config;
+use crate::emit;
+use crate::level::Level;
+use crate::record::Metadata;
+use std::fmt;
+use std::panic::Location;
+
+/// Metadata pointing at the caller location.
+///
+/// The module path of a caller is not known at runtime, so its file path
+/// stands in as the target.
+#[track_caller]
+pub(crate) fn caller_metadata(level: Level) -> Metadata {
+    let location = Location::caller();
+    Metadata::new(level, location.file(), location.file(), location.line())
+}
+
+/// Logs `args` at [`Level::Info`], attributed to the caller's location.
+///
+/// Mark shared assertion helpers `#[track_caller]` and log through this
+/// function: the reported `file:line` is then the test that called the
+/// helper, not the helper itself. Unless the format already shows
+/// locations, the message is prefixed with `file:line:`.
+///
+/// Unlike the macros this is an ordinary function and is not compiled out,
+/// so call it only from test code.
+///
+/// # Examples
+///
+/// ```rust
+/// #[track_caller]
+/// fn assert_sorted(v: &[i32]) {
+///     if !v.is_sorted() {
+///         testlog::log_at_caller(format_args!("not sorted: {:?}", v));
+///     }
+///     assert!(v.is_sorted());
+/// }
+///
+/// assert_sorted(&[1, 2, 3]);
+/// ```
+#[track_caller]
+pub fn log_at_caller(args: fmt::Arguments) {
+    let meta = caller_metadata(Level::Info);
+    let config = config::current();
+    if !config.allows(&meta) {
+        return;
+    }
+    if config.format.location {
+        emit::log(&meta, args);
+    } else {
+        emit::log(&meta, format_args!("{}:{}: {}", meta.file, meta.line, args));
+    }
+}
+
+/// Backend of `test_log_caller!`.
+///
+/// Being `#[track_caller]` itself, this sees through to the caller of the
+/// function that expanded the macro, provided that function is
+/// `#[track_caller]` too.
+#[doc(hidden)]
+#[track_caller]
+pub fn log_caller(meta: &Metadata, args: Option<fmt::Arguments>) {
+    let caller = Location::caller();
+    match args {
+        Some(args) => emit::log(meta, format_args!("{args} (called from {caller})")),
+        None => emit::log(meta, format_args!("called from {caller}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn helper() -> Metadata {
+        caller_metadata(Level::Info)
+    }
+
+    #[test]
+    fn location_is_the_helpers_caller() {
+        let line = line!() + 1;
+        let meta = helper();
+        assert_eq!(meta.line, line);
+        assert_eq!(meta.file, file!());
+    }
+
+    #[test]
+    fn caller_macro_accepts_optional_message() {
+        #[track_caller]
+        fn shared_fixture() {
+            crate::test_log_caller!();
+            crate::test_log_caller!("building fixture {}", 1);
+        }
+        shared_fixture();
+    }
+
+    #[test]
+    fn logs_through_helpers() {
+        #[track_caller]
+        fn check(value: i32) {
+            log_at_caller(format_args!("checking {value}"));
+        }
+        check(3);
+    }
+}
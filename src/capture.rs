@@ -0,0 +1,435 @@
+//! Deferred, bounded capture of a thread's output.
+//!
+//! While a [`CaptureGuard`] is alive, everything testlog would print on the
+//! thread is kept in a ring buffer instead. If the thread panics before the
+//! guard is dropped the retained tail is printed; otherwise it is discarded.
+//! The buffer keeps only the most recent output within a [`CaptureLimit`],
+//! so soak tests cannot exhaust memory.
+
+use crate::output::{self, Stream};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+/// How much output a capture retains; the oldest lines are evicted first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureLimit {
+    lines: Option<usize>,
+    bytes: Option<usize>,
+}
+
+impl CaptureLimit {
+    /// No limit at all.
+    pub const UNLIMITED: CaptureLimit = CaptureLimit {
+        lines: None,
+        bytes: None,
+    };
+
+    /// Retains at most `lines` lines.
+    pub fn lines(lines: usize) -> Self {
+        CaptureLimit {
+            lines: Some(lines),
+            bytes: None,
+        }
+    }
+
+    /// Retains at most `bytes` bytes of text.
+    pub fn bytes(bytes: usize) -> Self {
+        CaptureLimit {
+            lines: None,
+            bytes: Some(bytes),
+        }
+    }
+
+    /// Adds a line limit to this one.
+    pub fn and_lines(self, lines: usize) -> Self {
+        CaptureLimit {
+            lines: Some(lines),
+            ..self
+        }
+    }
+
+    /// Adds a byte limit to this one.
+    pub fn and_bytes(self, bytes: usize) -> Self {
+        CaptureLimit {
+            bytes: Some(bytes),
+            ..self
+        }
+    }
+}
+
+impl Default for CaptureLimit {
+    /// One mebibyte of text.
+    fn default() -> Self {
+        CaptureLimit::bytes(1024 * 1024)
+    }
+}
+
+impl FromStr for CaptureLimit {
+    type Err = String;
+
+    /// Parses a comma-separated list of limits: a bare number is a line
+    /// count, and a number with a `k`/`kb` or `m`/`mb` suffix is a size in
+    /// KiB or MiB, e.g. `500`, `64k` or `1000,256kb`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limit = CaptureLimit::UNLIMITED;
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let lower = part.to_ascii_lowercase();
+            let (digits, scale) = match lower.trim_end_matches(['b', 'i']) {
+                d if d.ends_with('k') => (&d[..d.len() - 1], Some(1024)),
+                d if d.ends_with('m') => (&d[..d.len() - 1], Some(1024 * 1024)),
+                d => (d, None),
+            };
+            let n: usize = digits
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid capture limit `{part}`"))?;
+            limit = match scale {
+                Some(scale) => limit.and_bytes(n * scale),
+                None => limit.and_lines(n),
+            };
+        }
+        Ok(limit)
+    }
+}
+
+/// Captured output, oldest first.
+#[derive(Debug)]
+pub(crate) struct RingBuffer {
+    entries: VecDeque<(Stream, String)>,
+    lines: usize,
+    bytes: usize,
+    dropped: usize,
+    limit: CaptureLimit,
+}
+
+impl RingBuffer {
+    pub(crate) fn new(limit: CaptureLimit) -> Self {
+        RingBuffer {
+            entries: VecDeque::new(),
+            lines: 0,
+            bytes: 0,
+            dropped: 0,
+            limit,
+        }
+    }
+
+    fn over_limit(&self) -> bool {
+        self.limit.lines.is_some_and(|max| self.lines > max)
+            || self.limit.bytes.is_some_and(|max| self.bytes > max)
+    }
+
+    pub(crate) fn push(&mut self, stream: Stream, text: &str) {
+        self.lines += text.lines().count().max(1);
+        self.bytes += text.len();
+        self.entries.push_back((stream, text.to_string()));
+        while self.over_limit() {
+            let Some((_, evicted)) = self.entries.pop_front() else {
+                break;
+            };
+            let lines = evicted.lines().count().max(1);
+            self.lines -= lines;
+            self.bytes -= evicted.len();
+            self.dropped += lines;
+        }
+    }
+
+    /// Retained text, oldest first.
+    pub(crate) fn lines(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(_, text)| text.as_str())
+    }
+
+    /// Number of lines evicted so far.
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Prints the retained output, preceded by a note about evicted lines.
+    fn replay(&self) {
+        if self.dropped > 0 {
+            let stream = self.entries.front().map_or(Stream::Stderr, |(s, _)| *s);
+            output::write_line(
+                stream,
+                &format!(
+                    "… {} earlier lines dropped by the capture limit …",
+                    self.dropped
+                ),
+            );
+        }
+        for (stream, text) in &self.entries {
+            output::write_line(*stream, text);
+        }
+    }
+}
+
+struct Capture {
+    /// Live guards plus guardless [`start_capture`] calls.
+    depth: usize,
+    /// Shared with the [watchdog](crate::watchdog), which prints it from
+    /// its own thread when the test stalls.
+    buffer: Arc<Mutex<RingBuffer>>,
+    /// Set once the buffer has been printed because the thread panicked;
+    /// later output is written straight through.
+    replayed: bool,
+    /// Name under which records are also added to the process-wide
+    /// journal, if [`ReplayDump`](crate::ReplayDump) is enabled.
+    journal: Option<String>,
+}
+
+impl Capture {
+    fn buffer(&self) -> MutexGuard<'_, RingBuffer> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// The buffer of the calling thread's capture, if one is active, for
+/// reading from another thread.
+pub(crate) fn shared() -> Option<Weak<Mutex<RingBuffer>>> {
+    CAPTURE
+        .try_with(|capture| {
+            let capture = capture.try_borrow().ok()?;
+            capture.as_ref().map(|c| Arc::downgrade(&c.buffer))
+        })
+        .ok()
+        .flatten()
+}
+
+/// Keeps `text` in the thread's capture buffer if a capture is active.
+/// Returns whether it was captured.
+pub(crate) fn push(stream: Stream, text: &str) -> bool {
+    CAPTURE
+        .try_with(|capture| match capture.try_borrow_mut() {
+            Ok(mut capture) => match capture.as_mut() {
+                Some(capture) if !capture.replayed => {
+                    capture.buffer().push(stream, text);
+                    if let Some(thread) = &capture.journal {
+                        crate::journal::record(thread, stream, text);
+                    }
+                    true
+                }
+                _ => false,
+            },
+            Err(_) => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Called from the panic hook: prints the calling thread's capture, if any,
+/// while keeping it available to [`captured_lines`].
+pub(crate) fn on_panic() {
+    let _ = CAPTURE.try_with(|capture| {
+        if let Ok(mut capture) = capture.try_borrow_mut() {
+            if let Some(capture) = capture.as_mut().filter(|c| !c.replayed) {
+                capture.replayed = true;
+                capture.buffer().replay();
+            }
+        }
+    });
+}
+
+/// Guard returned by [`capture`], which describes how capturing works.
+#[must_use = "output is only captured while the guard is alive"]
+#[derive(Debug)]
+pub struct CaptureGuard {
+    // The capture belongs to the thread that started it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        end_capture();
+    }
+}
+
+/// Starts a capture level on this thread, creating the buffer if needed.
+fn begin_capture() {
+    crate::panic::install();
+    CAPTURE.with(|capture| {
+        let mut capture = capture.borrow_mut();
+        match capture.as_mut() {
+            Some(active) => active.depth += 1,
+            None => {
+                let config = crate::config::current();
+                let journal = (config.replay != crate::journal::ReplayDump::Off).then(|| {
+                    let thread = std::thread::current();
+                    match thread.name() {
+                        Some(name) => name.to_string(),
+                        None => format!("{:?}", thread.id()),
+                    }
+                });
+                *capture = Some(Capture {
+                    depth: 1,
+                    buffer: Arc::new(Mutex::new(RingBuffer::new(config.capture_limit))),
+                    replayed: false,
+                    journal,
+                });
+            }
+        }
+    });
+}
+
+/// Ends one capture level, returning the retained lines if it was the
+/// last.
+///
+/// If the thread is panicking and the panic hook did not print the buffer
+/// (because another hook replaced it), it is printed here.
+fn end_capture() -> Option<Vec<String>> {
+    let finished = CAPTURE.with(|capture| {
+        let mut capture = capture.borrow_mut();
+        let active = capture.as_mut()?;
+        active.depth -= 1;
+        if active.depth > 0 {
+            return None;
+        }
+        capture.take()
+    })?;
+    let buffer = finished.buffer();
+    if std::thread::panicking() && !finished.replayed {
+        buffer.replay();
+    }
+    Some(buffer.lines().map(str::to_string).collect())
+}
+
+/// Starts capturing testlog output on the calling thread.
+///
+/// Output is held back until the returned guard is dropped. If the thread
+/// panics in the meantime, i.e. the test fails, the retained output is
+/// printed at that point; otherwise it is discarded. At most the configured
+/// [`CaptureLimit`] of the most recent output is kept
+/// (`TESTLOG_CAPTURE_LIMIT`, one MiB by default). Nested calls share the
+/// outermost capture.
+///
+/// # Examples
+///
+/// ```rust
+/// let _capture = testlog::capture();
+/// for i in 0..100_000 {
+///     testlog::test_trace!("iteration {}", i);
+/// }
+/// // Only the tail is printed, and only if the test panics.
+/// ```
+pub fn capture() -> CaptureGuard {
+    begin_capture();
+    CaptureGuard {
+        _not_send: PhantomData,
+    }
+}
+
+/// Starts capturing without a guard, for fixtures that set up logging
+/// before the test body runs.
+///
+/// Behaves like [`capture`] otherwise. The capture lasts until a matching
+/// [`stop_capture`] or until the thread exits; its contents can be read at
+/// any point with [`captured_lines`].
+pub fn start_capture() {
+    begin_capture();
+}
+
+/// Ends a capture started with [`start_capture`] and returns what it
+/// retained. Returns nothing if other captures are still active on the
+/// thread, since they share the same buffer.
+pub fn stop_capture() -> Vec<String> {
+    end_capture().unwrap_or_default()
+}
+
+/// Returns the output currently retained by the calling thread's capture,
+/// oldest first. Each entry is one record as it would have been printed,
+/// including its prefix; it is empty when no capture is active.
+///
+/// # Examples
+///
+/// ```rust
+/// testlog::start_capture();
+/// testlog::test_warn!("disk almost full");
+/// // A teardown hook can persist or inspect what was logged.
+/// let lines = testlog::captured_lines();
+/// # let _ = testlog::stop_capture();
+/// ```
+pub fn captured_lines() -> Vec<String> {
+    CAPTURE.with(|capture| {
+        capture
+            .borrow()
+            .as_ref()
+            .map(|c| c.buffer().lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!("500".parse(), Ok(CaptureLimit::lines(500)));
+        assert_eq!("64k".parse(), Ok(CaptureLimit::bytes(64 * 1024)));
+        assert_eq!(
+            "10, 2MiB".parse(),
+            Ok(CaptureLimit::lines(10).and_bytes(2 * 1024 * 1024))
+        );
+        assert!("lots".parse::<CaptureLimit>().is_err());
+    }
+
+    #[test]
+    fn evicts_oldest_lines_over_limit() {
+        let mut buffer = RingBuffer::new(CaptureLimit::lines(2));
+        for line in ["a", "b", "c"] {
+            buffer.push(Stream::Stderr, line);
+        }
+        assert_eq!(buffer.lines().collect::<Vec<_>>(), ["b", "c"]);
+        assert_eq!(buffer.dropped(), 1);
+    }
+
+    #[test]
+    fn byte_limit_counts_text() {
+        let mut buffer = RingBuffer::new(CaptureLimit::bytes(8));
+        buffer.push(Stream::Stderr, "12345");
+        buffer.push(Stream::Stderr, "6789");
+        assert_eq!(buffer.lines().collect::<Vec<_>>(), ["6789"]);
+        buffer.push(Stream::Stderr, "multi\nline");
+        assert_eq!(buffer.lines().count(), 0);
+        assert_eq!(buffer.dropped(), 4);
+    }
+
+    #[test]
+    fn guard_diverts_output_until_dropped() {
+        {
+            let _capture = capture();
+            let _nested = capture();
+            crate::test_log!("held back");
+            assert_eq!(captured_lines().len(), 1);
+        }
+        assert!(captured_lines().is_empty());
+    }
+
+    #[test]
+    fn guardless_capture_is_readable_and_stoppable() {
+        start_capture();
+        crate::test_warn!("from a fixture");
+        let lines = captured_lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("from a fixture"));
+        assert_eq!(stop_capture(), lines);
+        assert!(captured_lines().is_empty());
+    }
+
+    #[test]
+    fn panicking_thread_keeps_its_lines_after_replay() {
+        let lines = std::thread::spawn(|| {
+            start_capture();
+            crate::test_log!("before the panic");
+            let _ = std::panic::catch_unwind(|| panic!("intentional"));
+            crate::test_log!("written straight through");
+            captured_lines()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(lines.len(), 1);
+    }
+}
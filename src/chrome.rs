@@ -0,0 +1,125 @@
+//! Chrome trace-event export of groups, traced calls, timers and tests.
+//!
+//! With [`ConfigBuilder::chrome_trace`](crate::ConfigBuilder::chrome_trace)
+//! or `TESTLOG_CHROME_TRACE` set, every [`test_group!`](crate::test_group),
+//! [`trace_impl`](crate::trace_impl) call, [`test_timed!`](crate::test_timed)
+//! expression and [`#[testlog::test]`](macro@crate::test) becomes a duration
+//! event, and every [`test_section!`](crate::test_section) an instant event.
+//! The events are written when the process exits, as JSON that
+//! `chrome://tracing`, Perfetto and speedscope can open. Each thread gets its
+//! own track, named after the test running on it.
+
+use crate::spans::{Kind, Record};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+fn render(records: &[Record]) -> String {
+    let pid = std::process::id();
+    let mut out = String::from("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[");
+    for (i, record) in records.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('\n');
+        let name = escape(&record.name);
+        let tid = record.tid;
+        let ts = record.offset.as_micros();
+        out.push_str(&match record.kind {
+            Kind::Thread => format!(
+                "{{\"ph\":\"M\",\"name\":\"thread_name\",\"pid\":{pid},\"tid\":{tid},\
+                 \"args\":{{\"name\":\"{name}\"}}}}"
+            ),
+            Kind::Instant => format!(
+                "{{\"ph\":\"i\",\"s\":\"t\",\"cat\":\"{}\",\"name\":\"{name}\",\
+                 \"pid\":{pid},\"tid\":{tid},\"ts\":{ts}}}",
+                record.cat
+            ),
+            Kind::Span => format!(
+                "{{\"ph\":\"X\",\"cat\":\"{}\",\"name\":\"{name}\",\
+                 \"pid\":{pid},\"tid\":{tid},\"ts\":{ts},\"dur\":{}}}",
+                record.cat,
+                record.dur.as_micros()
+            ),
+        });
+    }
+    out.push_str("\n]}\n");
+    out
+}
+
+/// Escapes `text` for use inside a JSON string.
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Where the trace goes: `path` itself, or a file named after the test
+/// binary and process inside it when `path` is a directory, so that the
+/// binaries of a workspace run do not overwrite each other.
+fn destination(path: &Path) -> PathBuf {
+    if !path.is_dir() {
+        return path.to_path_buf();
+    }
+    let binary = crate::spans::binary_name();
+    path.join(format!("{binary}-{}.json", std::process::id()))
+}
+
+/// Writes `records` as a trace to `path`; runs from an exit hook.
+pub(crate) fn write(path: &Path, records: &[Record]) {
+    let path = destination(path);
+    let written =
+        std::fs::File::create(&path).and_then(|mut f| f.write_all(render(records).as_bytes()));
+    if let Err(err) = written {
+        let _ = writeln!(
+            std::io::stderr(),
+            "testlog: could not write chrome trace to {}: {err}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(kind: Kind, cat: &'static str, name: &str, at: u64, dur: u64) -> Record {
+        Record {
+            kind,
+            cat,
+            name: name.into(),
+            tid: 1,
+            id: 0,
+            parent: 0,
+            offset: Duration::from_micros(at),
+            dur: Duration::from_micros(dur),
+        }
+    }
+
+    #[test]
+    fn renders_trace_events() {
+        let records = [
+            record(Kind::Thread, "", "tests::a", 0, 0),
+            record(Kind::Span, "group", "load \"users\"", 10, 250),
+            record(Kind::Instant, "section", "assert", 300, 0),
+        ];
+        let pid = std::process::id();
+        let json = render(&records);
+        assert!(json.starts_with("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n"));
+        assert!(json.contains(&format!(
+            "{{\"ph\":\"X\",\"cat\":\"group\",\"name\":\"load \\\"users\\\"\",\
+             \"pid\":{pid},\"tid\":1,\"ts\":10,\"dur\":250}}"
+        )));
+        assert!(json.contains("\"args\":{\"name\":\"tests::a\"}"));
+        assert!(json.contains("\"ph\":\"i\",\"s\":\"t\",\"cat\":\"section\""));
+        assert!(json.ends_with("\n]}\n"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Virtual time for the timestamps in line prefixes: the `+0.000s` of the
+//! `full` format and the date of the `env_logger` format.
+//!
+//! While a [`VirtualClock`] is alive, the thread's timestamps come from it
+//! instead of the real clocks, so a recorded log or snapshot comes out the
+//! same on every run. It starts at zero, which is `2000-01-01T00:00:00Z`
+//! for dates, and moves only when the test moves it. `TESTLOG_CLOCK=virtual`
+//! stops every thread's timestamps at zero. Durations that are measured,
+//! such as those of [`test_timed!`](crate::test_timed), stay real.
+
+use crate::config::Config;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Seconds from the Unix epoch to the date virtual time starts at.
+pub(crate) const EPOCH_SECS: u64 = 946_684_800;
+
+thread_local! {
+    static NOW: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Guard returned by [`virtual_clock`]; the thread's timestamps follow it
+/// until it is dropped.
+#[must_use = "timestamps are only virtual while the clock is alive"]
+#[derive(Debug)]
+pub struct VirtualClock {
+    /// The clock of an enclosing guard, put back by the drop.
+    outer: Option<Duration>,
+    // The clock belongs to the thread that started it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl VirtualClock {
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.set(self.now() + by);
+    }
+
+    /// Sets the clock to `to` past its start.
+    pub fn set(&self, to: Duration) {
+        crate::emit::flush_pending();
+        let _ = NOW.try_with(|now| now.set(Some(to)));
+    }
+
+    /// The time on the clock, past its start.
+    pub fn now(&self) -> Duration {
+        NOW.try_with(Cell::get).ok().flatten().unwrap_or_default()
+    }
+}
+
+impl Drop for VirtualClock {
+    fn drop(&mut self) {
+        crate::emit::flush_pending();
+        let _ = NOW.try_with(|now| now.set(self.outer));
+    }
+}
+
+/// Has the calling thread's timestamps read from a clock that starts at
+/// zero and moves only with [`VirtualClock::advance`] and
+/// [`VirtualClock::set`], until the returned guard is dropped.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// let clock = testlog::virtual_clock();
+/// testlog::test_log!("request sent");
+/// // [2000-01-01T00:00:00Z INFO  app] request sent    (TESTLOG_FORMAT=env_logger)
+/// clock.advance(Duration::from_secs(90));
+/// testlog::test_log!("timed out");
+/// // [2000-01-01T00:01:30Z INFO  app] timed out
+/// ```
+pub fn virtual_clock() -> VirtualClock {
+    crate::emit::flush_pending();
+    let outer = NOW.with(|now| now.replace(Some(Duration::ZERO)));
+    VirtualClock {
+        outer,
+        _not_send: PhantomData,
+    }
+}
+
+/// The virtual time on the calling thread, if its timestamps are virtual.
+pub(crate) fn now(config: &Config) -> Option<Duration> {
+    let clock = NOW.try_with(Cell::get).ok().flatten();
+    clock.or(config.virtual_clock.then_some(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_follow_the_clock() {
+        let config = Config::default();
+        assert_eq!(now(&config), None);
+        let clock = virtual_clock();
+        assert_eq!(now(&config), Some(Duration::ZERO));
+        clock.advance(Duration::from_millis(1_500));
+        {
+            let inner = virtual_clock();
+            inner.set(Duration::from_secs(7));
+            assert_eq!(now(&config), Some(Duration::from_secs(7)));
+        }
+        assert_eq!(clock.now(), Duration::from_millis(1_500));
+        drop(clock);
+        assert_eq!(now(&config), None);
+        let frozen = Config::builder().virtual_clock(true).build();
+        assert_eq!(now(&frozen), Some(Duration::ZERO));
+    }
+}
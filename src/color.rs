@@ -0,0 +1,162 @@
+//! ANSI color support.
+//!
+//! On Windows, consoles only interpret escape sequences once virtual
+//! terminal processing is switched on. That happens the first time colors
+//! are resolved; if the console refuses, `Auto` falls back to plain output.
+
+use crate::output::Stream;
+use crate::theme::Style;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// When to emit ANSI color codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when the output stream is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    /// Always emit color codes.
+    Always,
+    /// Never emit color codes.
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolves the choice against the current environment, for output on
+    /// stderr.
+    pub fn enabled(self) -> bool {
+        self.enabled_for(Stream::Stderr)
+    }
+
+    /// Resolves the choice for output written to `stream`.
+    pub fn enabled_for(self, stream: Stream) -> bool {
+        match self {
+            ColorChoice::Always => {
+                ansi_supported();
+                true
+            }
+            ColorChoice::Never => false,
+            ColorChoice::Auto => auto_detect(stream) && ansi_supported(),
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" | "on" | "1" => Ok(ColorChoice::Always),
+            "never" | "off" | "0" => Ok(ColorChoice::Never),
+            other => Err(format!("unknown color choice `{other}`")),
+        }
+    }
+}
+
+fn auto_detect(stream: Stream) -> bool {
+    static STDERR: OnceLock<bool> = OnceLock::new();
+    static STDOUT: OnceLock<bool> = OnceLock::new();
+    let (cell, is_terminal): (_, fn() -> bool) = match stream {
+        Stream::Stderr => (&STDERR, || std::io::stderr().is_terminal()),
+        Stream::Stdout => (&STDOUT, || std::io::stdout().is_terminal()),
+    };
+    *cell.get_or_init(|| {
+        std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            && std::env::var_os("TERM").is_none_or(|t| t != "dumb")
+            && is_terminal()
+    })
+}
+
+/// Whether the attached console understands ANSI escape sequences, enabling
+/// them first where that is needed.
+fn ansi_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(sys::enable_ansi)
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+    }
+
+    fn enable(handle: *mut c_void) -> bool {
+        let mut mode = 0;
+        // SAFETY: `handle` is a standard stream handle owned by the process
+        // and `mode` is a valid out-pointer for the duration of the call.
+        unsafe {
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                return false;
+            }
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+
+    pub(super) fn enable_ansi() -> bool {
+        let stderr = enable(std::io::stderr().as_raw_handle().cast());
+        let stdout = enable(std::io::stdout().as_raw_handle().cast());
+        stderr || stdout
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    pub(super) fn enable_ansi() -> bool {
+        true
+    }
+}
+
+/// Starts `style` in `out` when `enabled`.
+pub(crate) fn open(out: &mut String, style: &Style, enabled: bool) {
+    if enabled && !style.is_plain() {
+        out.push_str("\x1b[");
+        style.write_sgr(out);
+        out.push('m');
+    }
+}
+
+/// Resets attributes started by [`open`] with the same `style`.
+pub(crate) fn close(out: &mut String, style: &Style, enabled: bool) {
+    if enabled && !style.is_plain() {
+        out.push_str("\x1b[0m");
+    }
+}
+
+/// Wraps `text` in `style` when `enabled`.
+pub(crate) fn paint(text: &str, style: &Style, enabled: bool) -> String {
+    let mut out = String::new();
+    open(&mut out, style, enabled);
+    out.push_str(text);
+    close(&mut out, style, enabled);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_choices() {
+        assert_eq!("Always".parse(), Ok(ColorChoice::Always));
+        assert_eq!("off".parse(), Ok(ColorChoice::Never));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn paint_is_identity_when_disabled() {
+        let bold = Style::new().bold();
+        assert_eq!(paint("x", &bold, false), "x");
+        assert_eq!(paint("x", &bold, true), "\x1b[1mx\x1b[0m");
+        assert_eq!(paint("x", &Style::new(), true), "x");
+    }
+}
@@ -0,0 +1,728 @@
+//! Runtime configuration.
+//!
+//! The active [`Config`] is read the first time it is needed, from
+//! `testlog.toml` and then from the environment. Test setup code can replace
+//! it with [`ConfigBuilder::install`].
+//!
+//! The file is found through `TESTLOG_CONFIG`, or as the nearest
+//! `testlog.toml` between the package being tested and its workspace root.
+//! It holds the settings below under the variable names without the
+//! `TESTLOG_` prefix, in lowercase, for a whole team's test suite:
+//!
+//! ```toml
+//! level = "debug"
+//! format = "pretty"
+//! tags = ["io", "-slow"]
+//! capture_limit = "64k"
+//! collapse = true
+//! ```
+//!
+//! Environment variables override the file:
+//!
+//! | Variable                | Meaning                                                               |
+//! |-------------------------|-----------------------------------------------------------------------|
+//! | `TESTLOG_COLOR`         | `auto` (default), `always` or `never`                                 |
+//! | `TESTLOG_WIDTH`         | width of section banners, in columns                                  |
+//! | `TESTLOG_LEVEL`         | most verbose level shown (default `trace`)                            |
+//! | `TESTLOG_VERBOSITY`     | `0` (errors) to `4` (trace), if `TESTLOG_LEVEL` is unset              |
+//! | `TESTLOG_MARKERS`       | `word` (default), `short` or `emoji`                                  |
+//! | `TESTLOG_FORMAT`        | `compact`, `pretty`, `full`, `workspace` or `env_logger`              |
+//! | `TESTLOG_CRATE_NAME`    | `1` to start lines with the crate name (the `workspace` default)      |
+//! | `TESTLOG_WRAP`          | `off` (default), `auto` or a column count                             |
+//! | `TESTLOG_STREAM`        | `stderr` (default) or `stdout`                                        |
+//! | `TESTLOG_OUTPUT`        | `stderr`, `stdout` or `file:/path`, or a list, each with `@level`     |
+//! | `TESTLOG_FLUSH`         | buffer file output, writing it out this often (e.g. `5s`)             |
+//! | `TESTLOG_BROKEN_PIPE`   | `drop` (default) or an output to use once a stream cannot be written  |
+//! | `TESTLOG_TAGS`          | tag filter such as `io,net,-slow`; see [`TagFilter`]                  |
+//! | `TESTLOG_CAPTURE_LIMIT` | output kept by [`capture`](crate::capture), e.g. `500` lines or `64k` |
+//! | `TESTLOG_REPLAY`        | `off` (default), `failure` or `always`; see [`ReplayDump`]            |
+//! | `TESTLOG_COLLAPSE`      | `1` to collapse consecutive identical messages into a repeat count    |
+//! | `TESTLOG_THEME`         | `dark` (default), `light` or `high-contrast`; see [`Theme`]           |
+//! | `TESTLOG_CHROME_TRACE`  | file, or directory, to write a Chrome trace of spans to at exit       |
+//! | `TESTLOG_STRICT`        | `1` to fail `#[testlog::test]` tests that log warnings or errors      |
+//! | `TESTLOG_OTLP_ENDPOINT` | OpenTelemetry collector for test spans (`otel` feature)               |
+//! | `TESTLOG_BASELINE`      | `off` (default), `compare` or `record`; see [`BaselineMode`]          |
+//! | `TESTLOG_BASELINE_DIR`  | directory of baselines, instead of the package's `tests/baselines`    |
+//! | `TESTLOG_NORMALIZE`     | values masked in baselines, `all` (default); see [`Normalize`]        |
+//! | `TESTLOG_WATCHDOG`      | report tests that log nothing for this long, e.g. `30s`               |
+//! | `TESTLOG_PROFILE`       | sample `test_timed!` expressions this often, e.g. `10ms` (`perf`)     |
+//! | `TESTLOG_SLOWEST`       | at exit, list this many of the slowest tests, or `all`                |
+//! | `TESTLOG_HOST_INFO`     | `1` to print OS, CPUs and rustc first; or env variables to add to it  |
+//! | `TESTLOG_RUN_ID`        | `1` to start lines with a random ID per process, or the ID to use     |
+//! | `TESTLOG_CLOCK`         | `real` (default) or `virtual`, which stops timestamps at zero         |
+//! | `TESTLOG_CONFIG`        | path of the configuration file, instead of searching for one          |
+
+use crate::baseline::{BaselineMode, Normalize};
+use crate::capture::CaptureLimit;
+use crate::color::ColorChoice;
+use crate::filter::TagFilter;
+use crate::format::{Format, Preset, Wrap};
+use crate::journal::ReplayDump;
+use crate::level::{Level, MarkerStyle};
+use crate::output::{BrokenPipe, Output, Sink, Stream};
+use crate::record::Metadata;
+use crate::theme::Theme;
+use crate::units::HumanDuration;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_WIDTH: usize = 60;
+
+/// Settings shared by all testlog macros.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub(crate) width: usize,
+    pub(crate) color: ColorChoice,
+    pub(crate) level: Level,
+    pub(crate) markers: MarkerStyle,
+    pub(crate) format: Format,
+    pub(crate) crate_name: Option<bool>,
+    pub(crate) hanging_indent: bool,
+    pub(crate) wrap: Wrap,
+    pub(crate) stream: Stream,
+    pub(crate) tags: TagFilter,
+    pub(crate) capture_limit: CaptureLimit,
+    pub(crate) replay: ReplayDump,
+    pub(crate) collapse_repeats: bool,
+    pub(crate) outputs: Vec<Sink>,
+    pub(crate) theme: Theme,
+    pub(crate) chrome_trace: Option<PathBuf>,
+    pub(crate) strict: bool,
+    pub(crate) otlp_endpoint: Option<String>,
+    pub(crate) baseline: BaselineMode,
+    pub(crate) baseline_dir: Option<PathBuf>,
+    pub(crate) normalize: Normalize,
+    pub(crate) watchdog: Option<Duration>,
+    pub(crate) profile: Option<Duration>,
+    pub(crate) slowest: Option<usize>,
+    pub(crate) run_id: Option<String>,
+    pub(crate) virtual_clock: bool,
+    pub(crate) flush_interval: Option<Duration>,
+    pub(crate) host_info: Option<Vec<String>>,
+    pub(crate) broken_pipe: BrokenPipe,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: DEFAULT_WIDTH,
+            color: ColorChoice::Auto,
+            level: Level::Trace,
+            markers: MarkerStyle::default(),
+            format: Format::default(),
+            crate_name: None,
+            hanging_indent: true,
+            wrap: Wrap::Off,
+            stream: Stream::Stderr,
+            tags: TagFilter::default(),
+            capture_limit: CaptureLimit::default(),
+            replay: ReplayDump::Off,
+            collapse_repeats: false,
+            outputs: Vec::new(),
+            theme: Theme::dark(),
+            chrome_trace: None,
+            strict: false,
+            otlp_endpoint: None,
+            baseline: BaselineMode::Off,
+            baseline_dir: None,
+            normalize: Normalize::ALL,
+            watchdog: None,
+            profile: None,
+            slowest: None,
+            run_id: None,
+            virtual_clock: false,
+            flush_interval: None,
+            host_info: None,
+            broken_pipe: BrokenPipe::Drop,
+        }
+    }
+}
+
+impl Config {
+    /// Starts a builder seeded from the environment.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::from_env(),
+        }
+    }
+
+    /// Starts a builder from the defaults alone, so that tests do not
+    /// depend on the environment they run in.
+    #[cfg(test)]
+    pub(crate) fn default_builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::default(),
+        }
+    }
+
+    /// Reads the configuration from `testlog.toml`, if there is one, and
+    /// then from `TESTLOG_*` environment variables, which take precedence.
+    ///
+    /// Environment values that fail to parse are ignored; problems in the
+    /// file are reported on stderr.
+    pub fn from_env() -> Config {
+        let mut config = Config::default();
+        if let Some(path) = config_file() {
+            config.load_file(&path);
+        }
+        for key in KEYS {
+            let name = format!("TESTLOG_{}", key.to_ascii_uppercase());
+            if let Ok(value) = std::env::var(name) {
+                let _ = config.set(key, value.trim());
+            }
+        }
+        config
+    }
+
+    /// Applies the settings in the `testlog.toml` at `path`.
+    fn load_file(&mut self, path: &Path) {
+        let report = |line: usize, msg: &str| {
+            eprintln!("testlog: {}:{line}: {msg}", path.display());
+        };
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(err) => return report(0, &err.to_string()),
+        };
+        match crate::toml::parse(&src) {
+            Ok(entries) => {
+                for entry in entries {
+                    if let Err(msg) = self.set(&entry.key, &entry.value) {
+                        report(entry.line, &msg);
+                    }
+                }
+            }
+            Err((line, msg)) => report(line, &msg),
+        }
+    }
+
+    /// Sets one setting by its name in [`KEYS`].
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(value: &str) -> Result<T, String>
+        where
+            T::Err: fmt::Display,
+        {
+            value
+                .parse()
+                .map_err(|err| format!("invalid value `{value}`: {err}"))
+        }
+        match key {
+            "width" => self.width = parse(value)?,
+            "color" => self.color = parse(value)?,
+            "verbosity" => self.level = Level::from_verbosity(parse(value)?),
+            "level" => self.level = parse(value)?,
+            "markers" => self.markers = parse(value)?,
+            "format" => self.format = parse::<Preset>(value)?.format(),
+            "crate_name" => {
+                self.crate_name =
+                    Some(flag(value).ok_or_else(|| format!("invalid value `{value}`"))?)
+            }
+            "wrap" => self.wrap = parse(value)?,
+            "stream" => self.stream = parse(value)?,
+            "output" => self.outputs = crate::output::parse_sinks(value)?,
+            "tags" => self.tags = parse(value)?,
+            "capture_limit" => self.capture_limit = parse(value)?,
+            "replay" => self.replay = parse(value)?,
+            "collapse" => {
+                self.collapse_repeats =
+                    flag(value).ok_or_else(|| format!("invalid value `{value}`"))?
+            }
+            "theme" => self.theme = parse(value)?,
+            "chrome_trace" => self.chrome_trace = Some(PathBuf::from(value)),
+            "otlp_endpoint" => self.otlp_endpoint = Some(value.to_string()),
+            "strict" => {
+                self.strict = flag(value).ok_or_else(|| format!("invalid value `{value}`"))?
+            }
+            "baseline" => self.baseline = parse(value)?,
+            "baseline_dir" => self.baseline_dir = Some(PathBuf::from(value)),
+            "normalize" => self.normalize = parse(value)?,
+            "watchdog" => {
+                self.watchdog = match value.to_ascii_lowercase().as_str() {
+                    "off" | "" => None,
+                    _ => Some(parse::<HumanDuration>(value)?.0).filter(|d| !d.is_zero()),
+                }
+            }
+            "profile" => {
+                self.profile = match value.to_ascii_lowercase().as_str() {
+                    "off" | "" => None,
+                    _ => Some(parse::<HumanDuration>(value)?.0),
+                }
+            }
+            "slowest" => {
+                self.slowest = match value.to_ascii_lowercase().as_str() {
+                    "all" => Some(usize::MAX),
+                    "off" | "" => None,
+                    _ => Some(parse::<usize>(value)?).filter(|&rows| rows > 0),
+                }
+            }
+            "flush" => {
+                self.flush_interval = match value.to_ascii_lowercase().as_str() {
+                    "off" | "" => None,
+                    _ => Some(parse::<HumanDuration>(value)?.0).filter(|d| !d.is_zero()),
+                }
+            }
+            "broken_pipe" => self.broken_pipe = parse(value)?,
+            "host_info" => {
+                self.host_info = match flag(value) {
+                    Some(true) => Some(Vec::new()),
+                    Some(false) => None,
+                    None => Some(value.split(',').map(|v| v.trim().to_string()).collect()),
+                }
+            }
+            "run_id" => {
+                self.run_id = match flag(value) {
+                    Some(true) => Some(random_run_id()),
+                    Some(false) => None,
+                    None => Some(value.to_string()),
+                }
+            }
+            "clock" => {
+                self.virtual_clock = match value.to_ascii_lowercase().as_str() {
+                    "real" | "" => false,
+                    "virtual" => true,
+                    _ => return Err(format!("invalid value `{value}`")),
+                }
+            }
+            _ => return Err(format!("unknown setting `{key}`")),
+        }
+        Ok(())
+    }
+
+    /// Whether output should carry ANSI color codes.
+    ///
+    /// With several outputs, only if every one of them would have colors.
+    pub fn color_enabled(&self) -> bool {
+        if self.outputs.is_empty() {
+            return self.color.enabled_for(self.stream);
+        }
+        self.outputs.iter().all(|sink| match &sink.output {
+            Output::File(_) => self.color == ColorChoice::Always,
+            output => self
+                .color
+                .enabled_for(output.stream().unwrap_or(self.stream)),
+        })
+    }
+
+    /// Whether records at `level` pass the level filter.
+    ///
+    /// The filter is raised for threads that called
+    /// [`bump_verbosity`](crate::bump_verbosity).
+    pub fn enabled(&self, level: Level) -> bool {
+        level <= crate::level::bumped(self.level)
+    }
+
+    /// Whether a record from `meta` passes every filter, taking the
+    /// thread's [`set_level_for`](crate::set_level_for) overrides into
+    /// account.
+    pub(crate) fn allows(&self, meta: &Metadata) -> bool {
+        let enabled = match crate::level::override_for(meta.target) {
+            Some(max) => meta.level <= max,
+            None => self.enabled(meta.level),
+        };
+        enabled && self.tags.allows(meta.tags)
+    }
+}
+
+/// Builder for [`Config`], obtained from [`Config::builder`].
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Width in columns used for banners such as `test_section!`.
+    pub fn width(mut self, width: usize) -> Self {
+        self.config.width = width;
+        self
+    }
+
+    /// When to emit ANSI colors.
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.config.color = color;
+        self
+    }
+
+    /// Colors used for level markers, prefixes and headings.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.config.theme = theme;
+        self
+    }
+
+    /// Most verbose level that is shown.
+    pub fn level(mut self, level: Level) -> Self {
+        self.config.level = level;
+        self
+    }
+
+    /// Sets the level filter from a `-v`-style count; see
+    /// [`Level::from_verbosity`].
+    pub fn verbosity(self, verbosity: u8) -> Self {
+        self.level(Level::from_verbosity(verbosity))
+    }
+
+    /// Only shows records whose tags pass `filter`.
+    pub fn tags(mut self, filter: TagFilter) -> Self {
+        self.config.tags = filter;
+        self
+    }
+
+    /// How much output [`capture`](crate::capture) retains.
+    pub fn capture_limit(mut self, limit: CaptureLimit) -> Self {
+        self.config.capture_limit = limit;
+        self
+    }
+
+    /// Whether captured output from all threads is also journaled and
+    /// printed in emission order when the process exits.
+    pub fn replay_dump(mut self, replay: ReplayDump) -> Self {
+        self.config.replay = replay;
+        self
+    }
+
+    /// Whether consecutive identical messages from the same call site are
+    /// printed once, followed by a note with the number of repeats.
+    pub fn collapse_repeats(mut self, enabled: bool) -> Self {
+        self.config.collapse_repeats = enabled;
+        self
+    }
+
+    /// How level markers are drawn.
+    pub fn markers(mut self, markers: MarkerStyle) -> Self {
+        self.config.markers = markers;
+        self
+    }
+
+    /// Selects which prefix elements appear in front of each message.
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.config.format = preset.format();
+        self
+    }
+
+    /// Whether lines start with the emitting crate's name, whatever the
+    /// preset says.
+    pub fn crate_name(mut self, enabled: bool) -> Self {
+        self.config.crate_name = Some(enabled);
+        self
+    }
+
+    /// Whether continuation lines of multi-line messages are aligned under
+    /// the message (the default) instead of repeating the prefix.
+    pub fn hanging_indent(mut self, enabled: bool) -> Self {
+        self.config.hanging_indent = enabled;
+        self
+    }
+
+    /// Soft-wraps long lines, keeping prefixes aligned on wrapped lines.
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.config.wrap = wrap;
+        self
+    }
+
+    /// Stream that output goes to unless a macro picks one itself.
+    pub fn stream(mut self, stream: Stream) -> Self {
+        self.config.stream = stream;
+        self
+    }
+
+    /// Sends all output to `output`, whatever stream a record asked for.
+    pub fn output(mut self, output: Output) -> Self {
+        self.config.outputs = vec![Sink {
+            output,
+            level: None,
+        }];
+        self
+    }
+
+    /// Sends records up to `level` to `output` as well, alongside the
+    /// outputs already set; sections and group markers go to all of them.
+    /// The global level and tag filters still apply first.
+    ///
+    /// ```rust
+    /// use testlog::{Config, Level, Output};
+    ///
+    /// Config::builder()
+    ///     .output(Output::Stderr)
+    ///     .add_output(Output::File("target/test.log".into()), Level::Trace)
+    ///     .level(Level::Trace)
+    ///     .build();
+    /// ```
+    pub fn add_output(mut self, output: Output, level: Level) -> Self {
+        self.config.outputs.push(Sink {
+            output,
+            level: Some(level),
+        });
+        self
+    }
+
+    /// What happens to output for a standard stream that can no longer be
+    /// written, such as stdout piped into `head`.
+    pub fn on_broken_pipe(mut self, policy: BrokenPipe) -> Self {
+        self.config.broken_pipe = policy;
+        self
+    }
+
+    /// Records groups, traced calls, timers and tests as a Chrome trace
+    /// written to `path` when the process exits. When `path` is a
+    /// directory the file is named after the test binary.
+    pub fn chrome_trace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.chrome_trace = Some(path.into());
+        self
+    }
+
+    /// Whether tests run with [`#[testlog::test]`](macro@crate::test) fail
+    /// when they log anything at `WARN` or `ERROR`.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.config.strict = enabled;
+        self
+    }
+
+    /// Sends test spans to the OpenTelemetry collector at `endpoint`, such
+    /// as `http://localhost:4318`, when the process exits.
+    #[cfg(feature = "otel")]
+    pub fn otlp_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.config.otlp_endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Whether tests record their log as a [baseline](crate::baseline) or
+    /// compare it with the recorded one.
+    pub fn baseline(mut self, mode: BaselineMode) -> Self {
+        self.config.baseline = mode;
+        self
+    }
+
+    /// Directory holding baselines, instead of the package's
+    /// `tests/baselines`.
+    pub fn baseline_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.baseline_dir = Some(dir.into());
+        self
+    }
+
+    /// Values replaced by placeholders in baselines.
+    pub fn normalize(mut self, normalize: Normalize) -> Self {
+        self.config.normalize = normalize;
+        self
+    }
+
+    /// Buffers lines written to an [`Output::File`] and writes them out
+    /// every `interval` from a background thread, so a process that is
+    /// killed loses at most that much output. Without it every line is
+    /// written through at once.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.config.flush_interval = Some(interval).filter(|d| !d.is_zero());
+        self
+    }
+
+    /// Has [`#[testlog::test]`](macro@crate::test) tests report themselves
+    /// when they log nothing for `timeout`; see [`watchdog`](crate::watchdog).
+    pub fn watchdog(mut self, timeout: Duration) -> Self {
+        self.config.watchdog = Some(timeout);
+        self
+    }
+
+    /// Has `test_timed!` expressions note where their thread is at most
+    /// once per `interval` and end their record with a summary of where
+    /// the time went; see the `perf` feature.
+    #[cfg(feature = "perf")]
+    pub fn profile(mut self, interval: Duration) -> Self {
+        self.config.profile = Some(interval);
+        self
+    }
+
+    /// Prints the `rows` slowest [`#[testlog::test]`](macro@crate::test)
+    /// tests when the process exits; `usize::MAX` lists them all.
+    pub fn slowest(mut self, rows: usize) -> Self {
+        self.config.slowest = Some(rows).filter(|&rows| rows > 0);
+        self
+    }
+
+    /// Starts every line with `id`, so that logs of several runs of the
+    /// same tests, such as CI retries, can be told apart once aggregated.
+    pub fn run_id(mut self, id: impl Into<String>) -> Self {
+        self.config.run_id = Some(id.into());
+        self
+    }
+
+    /// Starts every line with a short random ID, the same for the whole
+    /// process; see [`run_id`](Self::run_id).
+    pub fn random_run_id(self) -> Self {
+        self.run_id(random_run_id())
+    }
+
+    /// Stops the timestamps of every thread at zero, so recorded logs do
+    /// not change between runs; a test can still move its own with
+    /// [`virtual_clock`](crate::virtual_clock).
+    pub fn virtual_clock(mut self, enabled: bool) -> Self {
+        self.config.virtual_clock = enabled;
+        self
+    }
+
+    /// Prints the OS, CPU count, compiler version and the values of the
+    /// environment variables in `env` once, before the first output of the
+    /// process.
+    pub fn host_info(mut self, env: &[&str]) -> Self {
+        self.config.host_info = Some(env.iter().map(|v| v.to_string()).collect());
+        self
+    }
+
+    /// Finishes the builder without installing it.
+    pub fn build(self) -> Config {
+        self.config
+    }
+
+    /// Makes this configuration the active one for the whole process.
+    pub fn install(self) {
+        *global().write().unwrap_or_else(|e| e.into_inner()) = Arc::new(self.config);
+    }
+}
+
+/// Settings that can be given in `testlog.toml`, or as `TESTLOG_<KEY>` in
+/// the environment. `level` comes after `verbosity` so that it wins when
+/// both are set.
+const KEYS: [&str; 29] = [
+    "width",
+    "color",
+    "verbosity",
+    "level",
+    "markers",
+    "format",
+    "crate_name",
+    "wrap",
+    "stream",
+    "output",
+    "flush",
+    "broken_pipe",
+    "tags",
+    "capture_limit",
+    "replay",
+    "collapse",
+    "theme",
+    "chrome_trace",
+    "strict",
+    "otlp_endpoint",
+    "baseline",
+    "baseline_dir",
+    "normalize",
+    "watchdog",
+    "profile",
+    "slowest",
+    "run_id",
+    "clock",
+    "host_info",
+];
+
+/// Reads a boolean switch: `1`, `true`, `yes` or `on`, or their opposites.
+fn flag(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// Six hex digits chosen once per process.
+fn random_run_id() -> String {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        let now = SystemTime::now().duration_since(UNIX_EPOCH);
+        hasher.write_u128(now.map_or(0, |d| d.as_nanos()));
+        format!("{:06x}", hasher.finish() & 0xff_ffff)
+    })
+    .clone()
+}
+
+/// The configuration file: `TESTLOG_CONFIG` if set, otherwise the nearest
+/// `testlog.toml` from the package directory up to the workspace root,
+/// which is recognized by its `Cargo.lock`.
+fn config_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("TESTLOG_CONFIG").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let start = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())?;
+    for dir in start.ancestors() {
+        let candidate = dir.join("testlog.toml");
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir.join("Cargo.lock").is_file() {
+            break;
+        }
+    }
+    None
+}
+
+fn global() -> &'static RwLock<Arc<Config>> {
+    static GLOBAL: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+    GLOBAL.get_or_init(|| RwLock::new(Arc::new(Config::from_env())))
+}
+
+/// The active configuration.
+pub(crate) fn current() -> Arc<Config> {
+    global().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_overrides_defaults() {
+        let config = Config::default_builder()
+            .width(20)
+            .color(ColorChoice::Never)
+            .build();
+        assert_eq!(config.width, 20);
+        assert!(!config.color_enabled());
+    }
+
+    #[test]
+    fn file_settings_apply_in_order() {
+        let dir = std::env::temp_dir().join(format!("testlog-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("testlog.toml");
+        std::fs::write(
+            &path,
+            "verbosity = 1\nlevel = \"info\"\nwidth = 72\nbogus = 1\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.load_file(&path);
+        assert_eq!(config.level, Level::Info);
+        assert_eq!(config.width, 72);
+        assert!(config.set("bogus", "1").is_err());
+        assert!(config.set("collapse", "yes").is_ok() && config.collapse_repeats);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn level_filter_is_inclusive() {
+        let config = Config::default_builder().level(Level::Info).build();
+        assert!(config.enabled(Level::Warn));
+        assert!(config.enabled(Level::Info));
+        assert!(!config.enabled(Level::Debug));
+    }
+
+    #[test]
+    fn run_id_is_random_unless_given() {
+        let mut config = Config::default();
+        config.set("run_id", "on").unwrap();
+        let id = config.run_id.clone().unwrap();
+        assert_eq!(id.len(), 6);
+        assert_eq!(
+            Config::default_builder().random_run_id().build().run_id,
+            Some(id)
+        );
+        config.set("run_id", "attempt-2").unwrap();
+        assert_eq!(config.run_id.as_deref(), Some("attempt-2"));
+        config.set("run_id", "off").unwrap();
+        assert_eq!(config.run_id, None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Per-thread panic context pushed by [`test_context!`](crate::test_context).
+//!
+//! Each entry describes what the thread was doing. The panic hook prints
+//! the stack, outermost first, so a failure deep inside library code still
+//! says which step of the test it happened in.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+thread_local! {
+    static STACK: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Guard returned by `push`; pops its entry when dropped.
+#[must_use = "the context is popped when the guard is dropped"]
+#[derive(Debug)]
+pub struct ContextGuard {
+    // Entries belong to the thread that pushed them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let _ = STACK.try_with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+/// Pushes `text` onto the calling thread's context stack.
+pub fn push(text: String) -> ContextGuard {
+    crate::panic::install();
+    STACK.with(|stack| stack.borrow_mut().push(text));
+    ContextGuard {
+        _not_send: PhantomData,
+    }
+}
+
+/// The context stack as printed by the panic hook, if it is not empty.
+pub(crate) fn render() -> Option<String> {
+    STACK
+        .try_with(|stack| {
+            let stack = stack.try_borrow().ok()?;
+            if stack.is_empty() {
+                return None;
+            }
+            let mut out = String::from("panic context (outermost first):");
+            for entry in stack.iter() {
+                let entry = entry.replace('\n', "\n    ");
+                out.push_str("\n  - ");
+                out.push_str(&entry);
+            }
+            Some(out)
+        })
+        .ok()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_popped_in_scope_order() {
+        assert_eq!(render(), None);
+        let _outer = push("loading fixture".into());
+        {
+            crate::test_context!("while parsing header {}", 3);
+            assert_eq!(
+                render().as_deref(),
+                Some(
+                    "panic context (outermost first):\n  - loading fixture\n  - while parsing header 3"
+                )
+            );
+        }
+        assert_eq!(
+            render().as_deref(),
+            Some("panic context (outermost first):\n  - loading fixture")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Line diffs between two texts.
+
+/// One line of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Above this many table cells, the differing middle is reported as removed
+/// and re-added wholesale instead of being aligned.
+const MAX_CELLS: usize = 4_000_000;
+
+/// Aligns `old` and `new` on a longest common subsequence.
+pub(crate) fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Op<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old_rest[..old_rest.len() - suffix];
+    let b = &new_rest[..new_rest.len() - suffix];
+
+    let mut ops: Vec<Op> = old[..prefix].iter().map(|l| Op::Same(l)).collect();
+    if (a.len() + 1) * (b.len() + 1) > MAX_CELLS {
+        ops.extend(a.iter().map(|l| Op::Removed(l)));
+        ops.extend(b.iter().map(|l| Op::Added(l)));
+    } else {
+        // lcs[i][j]: length of the common subsequence of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(Op::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len()
+                || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                ops.push(Op::Removed(a[i]));
+                i += 1;
+            } else {
+                ops.push(Op::Added(b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(
+        old_rest[old_rest.len() - suffix..]
+            .iter()
+            .map(|l| Op::Same(l)),
+    );
+    ops
+}
+
+/// Indices of the ops to show with `context` unchanged lines around each
+/// change, with `None` where a run of unchanged lines is elided.
+pub(crate) fn visible(ops: &[Op], context: usize) -> Vec<Option<usize>> {
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let near_change = |i: usize| {
+        let at = changed.partition_point(|&c| c + context < i);
+        changed.get(at).is_some_and(|&c| c <= i + context)
+    };
+    let mut shown = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Same(_)) && !near_change(i) {
+            if shown.last() != Some(&None) {
+                shown.push(None);
+            }
+        } else {
+            shown.push(Some(i));
+        }
+    }
+    shown
+}
+
+/// Renders `ops` as `- `, `+ ` and `  ` lines, keeping `context` unchanged
+/// lines around each change and eliding the rest with `…`.
+pub(crate) fn render(ops: &[Op], context: usize) -> String {
+    let mut out = String::new();
+    for shown in visible(ops, context) {
+        let (mark, line) = match shown.map(|i| ops[i]) {
+            None => (' ', "…"),
+            Some(Op::Same(line)) => (' ', line),
+            Some(Op::Removed(line)) => ('-', line),
+            Some(Op::Added(line)) => ('+', line),
+        };
+        out.push(mark);
+        out.push(' ');
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.pop();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_on_common_lines() {
+        let ops = diff(&["a", "b", "c", "d"], &["a", "c", "x", "d"]);
+        assert_eq!(
+            ops,
+            [
+                Op::Same("a"),
+                Op::Removed("b"),
+                Op::Same("c"),
+                Op::Added("x"),
+                Op::Same("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn elides_distant_context() {
+        let old: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[5] = "five".into();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        assert_eq!(
+            render(&diff(&old, &new), 1),
+            "  …\n  4\n- 5\n+ five\n  6\n  …"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! The shared output path behind every macro.
+
+use crate::config;
+use crate::group;
+use crate::level::Level;
+use crate::output::{self, Stream};
+use crate::record::Metadata;
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+
+/// A line started by `test_write!` that has not been finished yet.
+struct Pending {
+    meta: &'static Metadata,
+    text: String,
+}
+
+/// Scratch space reused between records so the common case does not
+/// allocate.
+#[derive(Default)]
+struct Buffers {
+    msg: String,
+    line: String,
+}
+
+/// Buffers grown past this many bytes are released after use.
+const MAX_RETAINED: usize = 4096;
+
+thread_local! {
+    static PENDING: RefCell<Option<Pending>> = const { RefCell::new(None) };
+    static BUFFERS: RefCell<Buffers> = RefCell::new(Buffers::default());
+}
+
+/// Runs `f` with this thread's cleared scratch buffers.
+///
+/// Falls back to fresh buffers when they are already in use, which happens
+/// when a `Display` impl being logged logs something itself, or already
+/// gone, when logging from another thread-local's destructor.
+fn with_buffers<R>(f: impl FnOnce(&mut Buffers) -> R) -> R {
+    let mut f = Some(f);
+    let reused = BUFFERS.try_with(|cell| match cell.try_borrow_mut() {
+        Ok(mut buffers) => {
+            buffers.msg.clear();
+            buffers.line.clear();
+            let result = (f.take().unwrap())(&mut buffers);
+            if buffers.msg.capacity() > MAX_RETAINED || buffers.line.capacity() > MAX_RETAINED {
+                *buffers = Buffers::default();
+            }
+            Some(result)
+        }
+        Err(_) => None,
+    });
+    match reused {
+        Ok(Some(result)) => result,
+        _ => (f.take().unwrap())(&mut Buffers::default()),
+    }
+}
+
+/// Hands finished output to the active capture, or writes it out; `level`
+/// is that of the record it belongs to, if any.
+pub(crate) fn deliver(stream: Stream, level: Option<Level>, text: &str) {
+    if crate::suppress::active() {
+        return;
+    }
+    crate::host::banner_once();
+    if !crate::capture::push(stream, text) {
+        output::write_record(stream, level, text);
+    }
+}
+
+/// Writes one already-rendered block of text, indented for the current group.
+pub(crate) fn write(stream: Stream, level: Option<Level>, text: &str) {
+    deliver(stream, level, &group::indent_lines(group::depth(), text));
+}
+
+/// Renders and writes one record into `line`.
+fn emit_into(line: &mut String, config: &config::Config, meta: &Metadata, msg: &str) {
+    crate::format::render_into(line, config, meta, msg, group::depth());
+    deliver(meta.stream.unwrap_or(config.stream), Some(meta.level), line);
+}
+
+/// Renders and writes one record.
+fn emit(config: &config::Config, meta: &Metadata, msg: &str) {
+    with_buffers(|buffers| emit_into(&mut buffers.line, config, meta, msg));
+}
+
+/// Writes a line generated by testlog itself with the prefix of `meta`.
+pub(crate) fn note(meta: &Metadata, text: &str) {
+    emit(&config::current(), meta, text);
+}
+
+/// Emits the pending `test_write!` line, if any.
+fn flush_line() {
+    let pending = PENDING.try_with(|p| p.borrow_mut().take()).ok().flatten();
+    if let Some(pending) = pending {
+        crate::repeat::flush();
+        emit(&config::current(), pending.meta, &pending.text);
+    }
+}
+
+/// Emits everything held back for the current line, i.e. a pending
+/// `test_write!` line and repeat counts, so that it is not interleaved with
+/// the output that follows.
+pub(crate) fn flush_pending() {
+    crate::repeat::flush();
+    flush_line();
+}
+
+/// Whether a record from `meta` would currently be emitted, or counted by
+/// strict mode.
+pub fn enabled(meta: &Metadata) -> bool {
+    #[cfg(feature = "perf")]
+    crate::profile::sample_point();
+    crate::strict::wants(meta.level) || shown(meta)
+}
+
+/// Whether a record from `meta` would currently be written out.
+pub(crate) fn shown(meta: &Metadata) -> bool {
+    !crate::suppress::active() && config::current().allows(meta)
+}
+
+/// Backend of `test_log!` and the level macros.
+pub fn log(meta: &Metadata, args: fmt::Arguments) {
+    #[cfg(feature = "perf")]
+    crate::profile::sample_point();
+    crate::strict::record(meta.level, args);
+    if crate::suppress::active() {
+        return;
+    }
+    let config = config::current();
+    if config.allows(meta) {
+        record(&config, meta, args);
+    }
+}
+
+/// Backend of `test_log_if_env!`: emits regardless of the level filter.
+pub fn log_unfiltered(meta: &Metadata, args: fmt::Arguments) {
+    crate::strict::record(meta.level, args);
+    record(&config::current(), meta, args);
+}
+
+/// Formats and writes a record that has passed filtering.
+pub(crate) fn record(config: &config::Config, meta: &Metadata, args: fmt::Arguments) {
+    flush_line();
+    with_buffers(|buffers| {
+        let msg = match args.as_str() {
+            Some(msg) => msg,
+            None => {
+                let _ = buffers.msg.write_fmt(args);
+                &buffers.msg
+            }
+        };
+        crate::baseline::record(config, meta, msg);
+        crate::watchdog::record(meta, msg);
+        if config.collapse_repeats && crate::repeat::is_repeat(meta, msg) {
+            return;
+        }
+        emit_into(&mut buffers.line, config, meta, msg);
+    });
+}
+
+/// Formats and writes records that have passed filtering as one block, so
+/// output from other threads cannot come between their lines.
+pub(crate) fn record_lines(config: &config::Config, meta: &Metadata, msgs: &[String]) {
+    flush_pending();
+    let mut block = String::new();
+    for msg in msgs {
+        crate::baseline::record(config, meta, msg);
+        crate::watchdog::record(meta, msg);
+        if !block.is_empty() {
+            block.push('\n');
+        }
+        crate::format::render_into(&mut block, config, meta, msg, group::depth());
+    }
+    deliver(
+        meta.stream.unwrap_or(config.stream),
+        Some(meta.level),
+        &block,
+    );
+}
+
+/// Backend of `test_write!`: appends to the current line without ending it.
+///
+/// The level and prefix of the line come from the call that started it.
+pub fn write_partial(meta: &'static Metadata, args: fmt::Arguments) {
+    let Ok(start) = PENDING.try_with(|p| p.borrow().is_none()) else {
+        return;
+    };
+    if start && !config::current().allows(meta) {
+        return;
+    }
+    PENDING.with(|p| {
+        let mut p = p.borrow_mut();
+        let pending = p.get_or_insert_with(|| Pending {
+            meta,
+            text: String::new(),
+        });
+        let _ = pending.text.write_fmt(args);
+    });
+}
+
+/// Backend of `test_writeln!`: appends to the current line and ends it.
+pub fn finish_line(meta: &'static Metadata, args: fmt::Arguments) {
+    write_partial(meta, args);
+    flush_pending();
+}
+
+/// Backend of `test_section!`.
+pub fn section(args: fmt::Arguments) {
+    flush_pending();
+    let config = config::current();
+    let title = args.to_string();
+    crate::spans::instant("section", &title);
+    write(
+        config.stream,
+        None,
+        &crate::section::render(&config, &title, group::depth()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending() -> Option<String> {
+        PENDING.with(|p| p.borrow().as_ref().map(|p| p.text.clone()))
+    }
+
+    #[test]
+    fn partial_writes_accumulate_until_finished() {
+        crate::test_write!("progress:");
+        for i in 0..3 {
+            crate::test_write!(" {i}");
+        }
+        assert_eq!(pending().as_deref(), Some("progress: 0 1 2"));
+        crate::test_writeln!(" done");
+        assert_eq!(pending(), None);
+    }
+
+    #[test]
+    fn lazy_closure_runs_once_when_enabled() {
+        let mut calls = 0;
+        crate::test_log_lazy!(|| {
+            calls += 1;
+            "expensive"
+        });
+        crate::test_log_lazy!(crate::Level::Trace, || format!("{:?}", [1, 2]));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn buffers_are_reused_between_records() {
+        crate::test_log!("warm up {}", 1);
+        let before = BUFFERS.with(|b| b.borrow().line.capacity());
+        crate::test_log!("again {}", 2);
+        assert!(before > 0);
+        assert_eq!(BUFFERS.with(|b| b.borrow().line.capacity()), before);
+    }
+
+    #[test]
+    fn logging_from_display_impls_does_not_panic() {
+        struct Nested;
+        impl fmt::Display for Nested {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                crate::test_log!("inner");
+                f.write_str("outer")
+            }
+        }
+        crate::test_log!("{}", Nested);
+    }
+
+    #[test]
+    fn other_records_flush_the_pending_line() {
+        crate::test_write!("half a line");
+        crate::test_log!("interrupting");
+        assert_eq!(pending(), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Hooks that run when the process exits, after the test harness is done.
+
+/// Runs `callback` at process exit, after `main` returns.
+///
+/// Output from the callback bypasses the test harness's capture, which is
+/// gone by then, and it must not panic: nothing may unwind out of it.
+pub(crate) fn at_exit(callback: extern "C" fn()) {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+    }
+    // SAFETY: `callback` is a plain function that lives for the whole process.
+    unsafe {
+        atexit(callback);
+    }
+}
@@ -0,0 +1,53 @@
+//! Backend of [`test_log_fields_of!`](crate::test_log_fields_of).
+
+use crate::record::Metadata;
+
+/// Logs `what { label: value, ... }` on one line.
+#[doc(hidden)]
+pub fn log_fields(meta: &Metadata, what: &str, fields: &[(&str, String)]) {
+    crate::emit::log(meta, format_args!("{}", render(what, fields)));
+}
+
+fn render(what: &str, fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(label, value)| format!("{label}: {value}"))
+        .collect();
+    format!("{what} {{ {} }}", fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    #[derive(Debug)]
+    struct Limits {
+        max: u32,
+    }
+
+    struct Service {
+        name: &'static str,
+        limits: Limits,
+        peers: Vec<&'static str>,
+        _cache: [u8; 64],
+    }
+
+    #[test]
+    fn logs_only_the_named_fields() {
+        let _capture = crate::capture();
+        let svc = Service {
+            name: "auth",
+            limits: Limits { max: 8 },
+            peers: vec!["a", "b"],
+            _cache: [0; 64],
+        };
+        let retries = 2;
+        crate::test_log_fields_of!(svc, name, limits.max, peers.len(), retries = retries + 1);
+        crate::test_log_fields_of!(svc.limits, max);
+        assert_eq!(
+            crate::captured_lines(),
+            [
+                "INFO  svc { name: \"auth\", limits.max: 8, peers.len(): 2, retries: 3 }",
+                "INFO  svc.limits { max: 8 }",
+            ]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Record filtering beyond the global level.
+
+use std::str::FromStr;
+
+/// Which tags a record must (or must not) carry to be shown.
+///
+/// Parsed from a comma-separated list such as `io,net,-slow`: a record is
+/// shown if it carries at least one of the plain tags (when any are given)
+/// and none of the `-`-prefixed ones. Untagged records are hidden as soon
+/// as a plain tag is listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Whether a record carrying `tags` passes the filter.
+    pub fn allows(&self, tags: &[&str]) -> bool {
+        if tags.iter().any(|t| self.exclude.iter().any(|e| e == t)) {
+            return false;
+        }
+        self.include.is_empty() || tags.iter().any(|t| self.include.iter().any(|i| i == t))
+    }
+}
+
+impl FromStr for TagFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = TagFilter::default();
+        for tag in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match tag.strip_prefix('-') {
+                Some(excluded) => filter.exclude.push(excluded.to_string()),
+                None => filter.include.push(tag.to_string()),
+            }
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(s: &str) -> TagFilter {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_filter_allows_everything() {
+        assert!(filter("").allows(&[]));
+        assert!(filter("").allows(&["io"]));
+    }
+
+    #[test]
+    fn includes_require_a_matching_tag() {
+        let f = filter("io, net");
+        assert!(f.allows(&["net", "slow"]));
+        assert!(!f.allows(&["db"]));
+        assert!(!f.allows(&[]));
+    }
+
+    #[test]
+    fn excludes_win_over_includes() {
+        let f = filter("io,-slow");
+        assert!(f.allows(&["io"]));
+        assert!(!f.allows(&["io", "slow"]));
+        assert!(filter("-slow").allows(&[]));
+    }
+}
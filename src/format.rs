@@ -0,0 +1,525 @@
+//! Rendering of log records into output lines.
+//!
+//! Which prefix elements appear in front of a message is controlled by a
+//! [`Format`], usually picked through one of the named [`Preset`]s.
+
+use crate::color;
+use crate::config::Config;
+use crate::group;
+use crate::level::MarkerStyle;
+use crate::output::Stream;
+use crate::record::Metadata;
+use crate::width;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Prefix elements shown in front of each message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Format {
+    /// Name of the emitting crate, for output from several workspace members.
+    pub(crate) krate: bool,
+    /// Time elapsed since the first record, e.g. `+0.012s`.
+    pub(crate) time: bool,
+    /// Level marker, e.g. `WARN`.
+    pub(crate) level: bool,
+    /// Name of the emitting thread, which libtest sets to the test name, or
+    /// the test set up by an attribute such as `tokio_test`.
+    pub(crate) thread: bool,
+    /// Module path of the call site.
+    pub(crate) target: bool,
+    /// `file:line` of the call site.
+    pub(crate) location: bool,
+    /// Replaces all of the above with env_logger's default layout.
+    pub(crate) env_logger: bool,
+}
+
+impl Default for Format {
+    /// Level marker and message.
+    fn default() -> Self {
+        Format {
+            krate: false,
+            time: false,
+            level: true,
+            thread: false,
+            target: false,
+            location: false,
+            env_logger: false,
+        }
+    }
+}
+
+/// Soft-wrapping of long lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// Never wrap.
+    #[default]
+    Off,
+    /// Wrap at the width of the terminal the output goes to, when there is
+    /// one.
+    Auto,
+    /// Wrap at a fixed number of columns.
+    Columns(usize),
+}
+
+impl Wrap {
+    /// Column count to wrap at for output written to `stream`, if wrapping
+    /// applies.
+    pub(crate) fn columns(self, stream: Stream) -> Option<usize> {
+        match self {
+            Wrap::Off => None,
+            Wrap::Auto => {
+                static DETECTED: [OnceLock<Option<usize>>; 2] = [OnceLock::new(), OnceLock::new()];
+                let detected = match stream {
+                    Stream::Stderr => &DETECTED[0],
+                    Stream::Stdout => &DETECTED[1],
+                };
+                *detected.get_or_init(|| width::terminal_width(stream))
+            }
+            Wrap::Columns(cols) => Some(cols),
+        }
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "never" | "0" => Ok(Wrap::Off),
+            "auto" => Ok(Wrap::Auto),
+            cols => cols
+                .parse()
+                .map(Wrap::Columns)
+                .map_err(|_| format!("unknown wrap setting `{cols}`")),
+        }
+    }
+}
+
+/// Named output formats, selectable with `TESTLOG_FORMAT` or
+/// [`ConfigBuilder::preset`](crate::ConfigBuilder::preset).
+///
+/// Without a preset each line carries only its level marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Just the message, exactly as formatted.
+    Compact,
+    /// Level, thread name and module path.
+    Pretty,
+    /// Everything: elapsed time, level, thread, module path and location.
+    Full,
+    /// Like [`Pretty`](Preset::Pretty), led by the emitting crate's name, for
+    /// `cargo test --workspace` runs where module paths alone are ambiguous.
+    Workspace,
+    /// env_logger's default format, `[2024-01-01T00:00:00Z INFO  app::net] msg`,
+    /// for tooling that parses it. Messages are written verbatim, without
+    /// group indentation, wrapping or hanging indents.
+    EnvLogger,
+}
+
+impl Preset {
+    pub(crate) fn format(self) -> Format {
+        match self {
+            Preset::Compact => Format {
+                krate: false,
+                time: false,
+                level: false,
+                thread: false,
+                target: false,
+                location: false,
+                env_logger: false,
+            },
+            Preset::Pretty => Format {
+                krate: false,
+                time: false,
+                level: true,
+                thread: true,
+                target: true,
+                location: false,
+                env_logger: false,
+            },
+            Preset::Full => Format {
+                krate: false,
+                time: true,
+                level: true,
+                thread: true,
+                target: true,
+                location: true,
+                env_logger: false,
+            },
+            Preset::Workspace => Format {
+                krate: true,
+                ..Preset::Pretty.format()
+            },
+            Preset::EnvLogger => Format {
+                env_logger: true,
+                ..Preset::Compact.format()
+            },
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "compact" => Ok(Preset::Compact),
+            "pretty" => Ok(Preset::Pretty),
+            "full" => Ok(Preset::Full),
+            "workspace" => Ok(Preset::Workspace),
+            "env_logger" | "env-logger" => Ok(Preset::EnvLogger),
+            other => Err(format!("unknown format preset `{other}`")),
+        }
+    }
+}
+
+/// Time since the first record was rendered.
+fn elapsed() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp with
+/// whole seconds, as env_logger does by default.
+fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from a day count, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Writes env_logger's `[timestamp LEVEL target] ` prefix.
+fn write_env_logger_prefix(out: &mut String, config: &Config, meta: &Metadata) {
+    let color = config.color_enabled();
+    let bracket = config.theme.prefix();
+    let level = config.theme.level(meta.level);
+    let now = match crate::clock::now(config) {
+        Some(time) => crate::clock::EPOCH_SECS + time.as_secs(),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    };
+    if let Some(id) = &config.run_id {
+        out.push_str(&color::paint(&format!("#{id} "), &bracket, color));
+    }
+    out.push_str(&color::paint("[", &bracket, color));
+    let _ = write!(out, "{} ", rfc3339(now));
+    color::open(out, &level, color);
+    let _ = write!(out, "{:<5}", meta.level.as_str());
+    color::close(out, &level, color);
+    let _ = write!(out, " {}", meta.target);
+    out.push_str(&color::paint("]", &bracket, color));
+    out.push(' ');
+    if let Some(scope) = crate::scope::current() {
+        out.push_str(&color::paint(&scope, &bracket, color));
+        out.push(' ');
+    }
+}
+
+/// Writes the level marker for `meta`, padded so messages line up.
+fn write_marker(out: &mut String, config: &Config, meta: &Metadata) {
+    let marker = meta.level.marker(config.markers);
+    let color = config.color_enabled();
+    let style = config.theme.level(meta.level);
+    color::open(out, &style, color);
+    match config.markers {
+        MarkerStyle::Word => {
+            let _ = write!(out, "{marker:<5}");
+        }
+        MarkerStyle::Emoji | MarkerStyle::Short => out.push_str(marker),
+    }
+    color::close(out, &style, color);
+}
+
+/// Writes the prefix for `meta`, including the trailing space, or nothing
+/// when the format shows no prefix elements.
+pub(crate) fn write_prefix(out: &mut String, config: &Config, meta: &Metadata) {
+    let format = config.format;
+    let color = config.color_enabled();
+    let dim = config.theme.prefix();
+    let start = out.len();
+    let sep = |out: &mut String| {
+        if out.len() > start {
+            out.push(' ');
+        }
+    };
+    if let Some(id) = &config.run_id {
+        color::open(out, &dim, color);
+        let _ = write!(out, "#{id}");
+        color::close(out, &dim, color);
+    }
+    if config.crate_name.unwrap_or(format.krate) {
+        sep(out);
+        color::open(out, &dim, color);
+        out.push_str(meta.crate_name());
+        color::close(out, &dim, color);
+    }
+    if format.time {
+        sep(out);
+        color::open(out, &dim, color);
+        let time = crate::clock::now(config).unwrap_or_else(elapsed);
+        let _ = write!(out, "+{:.3}s", time.as_secs_f64());
+        color::close(out, &dim, color);
+    }
+    if format.level {
+        sep(out);
+        write_marker(out, config, meta);
+    }
+    if format.thread {
+        sep(out);
+        let thread = std::thread::current();
+        let name = crate::test::current().or(thread.name());
+        color::open(out, &dim, color);
+        let _ = write!(out, "[{}]", name.unwrap_or("<unnamed>"));
+        color::close(out, &dim, color);
+    }
+    if format.target {
+        sep(out);
+        color::open(out, &dim, color);
+        out.push_str(meta.target);
+        color::close(out, &dim, color);
+    }
+    if format.location {
+        sep(out);
+        color::open(out, &dim, color);
+        let _ = write!(out, "{}:{}", meta.file, meta.line);
+        color::close(out, &dim, color);
+    }
+    if let Some(scope) = crate::scope::current() {
+        sep(out);
+        color::open(out, &dim, color);
+        out.push_str(&scope);
+        color::close(out, &dim, color);
+    }
+    if out.len() > start {
+        out.push(' ');
+    }
+}
+
+/// Appends a record's message with its prefix to `out`.
+///
+/// Every physical line starts with the indentation of `depth` enclosing
+/// groups. Continuation lines, both from newlines in the message and from
+/// wrapping, are either aligned under the first line (hanging indent) or
+/// repeat the full prefix. Single-line messages without wrapping take a
+/// fast path that only appends to `out`.
+pub(crate) fn render_into(
+    out: &mut String,
+    config: &Config,
+    meta: &Metadata,
+    msg: &str,
+    depth: usize,
+) {
+    if config.format.env_logger {
+        write_env_logger_prefix(out, config, meta);
+        out.push_str(msg);
+        return;
+    }
+    group::push_indent(out, depth);
+    let prefix_start = out.len();
+    write_prefix(out, config, meta);
+    // The terminal the line goes to: the first stream among the outputs,
+    // which override the record's own.
+    let stream = config
+        .outputs
+        .iter()
+        .find_map(|sink| sink.output.stream())
+        .unwrap_or(meta.stream.unwrap_or(config.stream));
+    let wrap = config.wrap.columns(stream);
+    if wrap.is_none() && !msg.contains('\n') {
+        out.push_str(msg);
+        return;
+    }
+    let prefix = out[prefix_start..].to_string();
+    let prefix_width = width::str_width(&prefix);
+    let wrap_at = wrap.map(|cols| cols.saturating_sub(depth * group::INDENT.len() + prefix_width));
+    let lines = msg.split('\n').flat_map(|line| match wrap_at {
+        Some(cols) => width::wrap(line, cols),
+        None => vec![line.to_string()],
+    });
+    for (i, line) in lines.enumerate() {
+        if i > 0 {
+            out.push('\n');
+            group::push_indent(out, depth);
+            if config.hanging_indent {
+                out.extend(std::iter::repeat_n(' ', prefix_width));
+            } else {
+                out.push_str(&prefix);
+            }
+        }
+        out.push_str(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorChoice;
+    use crate::level::Level;
+
+    static WARN: Metadata = Metadata::new(Level::Warn, "app::net", "src/net.rs", 7);
+
+    fn config(markers: MarkerStyle) -> Config {
+        Config::default_builder()
+            .markers(markers)
+            .color(ColorChoice::Never)
+            .build()
+    }
+
+    fn render(config: &Config, meta: &Metadata, msg: &str, depth: usize) -> String {
+        let mut out = String::new();
+        render_into(&mut out, config, meta, msg, depth);
+        out
+    }
+
+    fn preset(preset: Preset) -> Config {
+        Config::default_builder()
+            .preset(preset)
+            .markers(MarkerStyle::Word)
+            .color(ColorChoice::Never)
+            .build()
+    }
+
+    #[test]
+    fn words_are_padded() {
+        assert_eq!(
+            render(&config(MarkerStyle::Word), &WARN, "hi", 0),
+            "WARN  hi"
+        );
+    }
+
+    #[test]
+    fn short_and_emoji_markers() {
+        assert_eq!(render(&config(MarkerStyle::Short), &WARN, "hi", 0), "W hi");
+        assert_eq!(render(&config(MarkerStyle::Emoji), &WARN, "hi", 0), "⚠️ hi");
+    }
+
+    #[test]
+    fn compact_is_just_the_message() {
+        assert_eq!(render(&preset(Preset::Compact), &WARN, "hi", 0), "hi");
+    }
+
+    #[test]
+    fn pretty_shows_thread_and_target() {
+        let line = render(&preset(Preset::Pretty), &WARN, "hi", 0);
+        assert_eq!(
+            line,
+            "WARN  [format::tests::pretty_shows_thread_and_target] app::net hi"
+        );
+    }
+
+    #[test]
+    fn full_shows_everything() {
+        let line = render(&preset(Preset::Full), &WARN, "hi", 0);
+        assert!(line.starts_with('+'), "{line}");
+        assert!(line.ends_with("app::net src/net.rs:7 hi"), "{line}");
+        assert!(line.contains(" WARN  [format::tests::full_shows_everything] "));
+    }
+
+    #[test]
+    fn workspace_leads_with_the_crate() {
+        let meta = WARN.krate(Some("net-client"));
+        let line = render(&preset(Preset::Workspace), &meta, "hi", 0);
+        assert_eq!(
+            line,
+            "net-client WARN  [format::tests::workspace_leads_with_the_crate] app::net hi"
+        );
+        let config = Config::default_builder()
+            .preset(Preset::Compact)
+            .crate_name(true)
+            .build();
+        assert_eq!(render(&config, &WARN, "hi", 0), "app hi");
+    }
+
+    #[test]
+    fn run_id_comes_first() {
+        let config = Config::default_builder()
+            .preset(Preset::Compact)
+            .crate_name(true)
+            .run_id("ci-2")
+            .color(ColorChoice::Never)
+            .build();
+        assert_eq!(
+            render(&config, &WARN, "a\nb", 0),
+            "#ci-2 app a\n          b"
+        );
+    }
+
+    #[test]
+    fn env_logger_layout() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1_709_210_096), "2024-02-29T12:34:56Z");
+        let line = render(&preset(Preset::EnvLogger), &WARN, "a\nb", 2);
+        let (stamp, rest) = line.split_at(21);
+        assert!(stamp.starts_with('[') && stamp.ends_with('Z'), "{line}");
+        assert_eq!(rest, " WARN  app::net] a\nb");
+    }
+
+    #[test]
+    fn virtual_clock_sets_timestamps() {
+        let clock = crate::virtual_clock();
+        clock.advance(Duration::from_millis(90_250));
+        assert_eq!(
+            render(&preset(Preset::EnvLogger), &WARN, "a", 2),
+            "[2000-01-01T00:01:30Z WARN  app::net] a"
+        );
+        let line = render(&preset(Preset::Full), &WARN, "a", 0);
+        assert!(line.contains("+90.250s"), "{line}");
+    }
+
+    #[test]
+    fn continuation_lines_hang_under_message() {
+        let line = render(&config(MarkerStyle::Word), &WARN, "a {\n  b\n}", 0);
+        assert_eq!(line, "WARN  a {\n        b\n      }");
+    }
+
+    #[test]
+    fn continuation_lines_can_repeat_prefix() {
+        let config = Config::default_builder()
+            .markers(MarkerStyle::Short)
+            .color(ColorChoice::Never)
+            .hanging_indent(false)
+            .build();
+        assert_eq!(render(&config, &WARN, "a\nb", 0), "W a\nW b");
+    }
+
+    #[test]
+    fn wrapped_lines_keep_prefix_alignment() {
+        let config = Config::default_builder()
+            .markers(MarkerStyle::Word)
+            .color(ColorChoice::Never)
+            .wrap(Wrap::Columns(16))
+            .build();
+        let line = render(&config, &WARN, "one two three four", 1);
+        assert_eq!(line, "  WARN  one two\n        three\n        four");
+    }
+
+    #[test]
+    fn parses_wrap_settings() {
+        assert_eq!("auto".parse(), Ok(Wrap::Auto));
+        assert_eq!("100".parse(), Ok(Wrap::Columns(100)));
+        assert_eq!("off".parse(), Ok(Wrap::Off));
+    }
+
+    #[test]
+    fn parses_presets() {
+        assert_eq!("FULL".parse(), Ok(Preset::Full));
+        assert!("verbose".parse::<Preset>().is_err());
+    }
+}
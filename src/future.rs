@@ -0,0 +1,450 @@
+//! Logging adapters for futures and streams, behind the `async` feature.
+
+use crate::caller::caller_metadata;
+use crate::emit;
+use crate::level::Level;
+use crate::record::Metadata;
+use crate::units::HumanDuration;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+/// Adds logging adapters to every [`Future`].
+pub trait TestLogFuture: Future + Sized {
+    /// Logs every poll of this future at [`Level::Trace`]: whether it was
+    /// `Pending` or `Ready`, and the time since the previous poll. A future
+    /// dropped before completing says so, which is usually what a stuck
+    /// test needs to know.
+    ///
+    /// Like [`log_at_caller`](crate::log_at_caller) this is not compiled
+    /// out, so use it only in test code.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use testlog::TestLogFuture;
+    ///
+    /// # async fn fetch() -> u8 { 7 }
+    /// # async fn test() {
+    /// let value = fetch().log_polls("fetch").await;
+    /// // TRACE fetch: poll #1 → Ready (12.0µs total)
+    /// # }
+    /// ```
+    #[track_caller]
+    fn log_polls(self, label: &'static str) -> LogPolls<Self> {
+        LogPolls {
+            inner: self,
+            label,
+            meta: caller_metadata(Level::Trace),
+            polls: 0,
+            first: None,
+            last: None,
+            done: false,
+        }
+    }
+}
+
+impl<F: Future> TestLogFuture for F {}
+
+/// Future returned by [`TestLogFuture::log_polls`].
+#[must_use = "futures do nothing unless polled"]
+pub struct LogPolls<F> {
+    inner: F,
+    label: &'static str,
+    meta: Metadata,
+    polls: u64,
+    first: Option<Instant>,
+    last: Option<Instant>,
+    done: bool,
+}
+
+impl<F: Future> Future for LogPolls<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `inner` is never moved out of the pinned wrapper, and no
+        // other field is treated as pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        let now = Instant::now();
+        let since = this.last.map(|last| now - last);
+        this.first.get_or_insert(now);
+        this.last = Some(now);
+        this.polls += 1;
+        let result = inner.poll(cx);
+        if emit::enabled(&this.meta) {
+            let state = if result.is_ready() {
+                "Ready"
+            } else {
+                "Pending"
+            };
+            let timing = match (since, result.is_ready()) {
+                (None, false) => String::new(),
+                (Some(since), false) => format!(" (+{})", HumanDuration(since)),
+                (since, true) => format!(
+                    " ({}{} total)",
+                    since.map_or(String::new(), |s| format!("+{}, ", HumanDuration(s))),
+                    HumanDuration(this.total(now))
+                ),
+            };
+            emit::log(
+                &this.meta,
+                format_args!("{}: poll #{} → {state}{timing}", this.label, this.polls),
+            );
+        }
+        this.done = result.is_ready();
+        result
+    }
+}
+
+impl<F> LogPolls<F> {
+    fn total(&self, now: Instant) -> Duration {
+        self.first.map_or(Duration::ZERO, |first| now - first)
+    }
+}
+
+impl<F> Drop for LogPolls<F> {
+    fn drop(&mut self) {
+        if !self.done && emit::enabled(&self.meta) {
+            emit::log(
+                &self.meta,
+                format_args!(
+                    "{}: dropped while pending after {} polls ({})",
+                    self.label,
+                    self.polls,
+                    HumanDuration(self.total(Instant::now()))
+                ),
+            );
+        }
+    }
+}
+
+/// A source of values produced over time, with the same shape as
+/// `futures_core::Stream`.
+///
+/// The standard library has no stream trait and testlog has no
+/// dependencies, so streams to be logged with
+/// [`log_stream_items`](TestLogStream::log_stream_items) implement this one;
+/// a `poll_next` written for `futures_core::Stream` can be forwarded as it
+/// is.
+pub trait AsyncStream {
+    /// Values yielded by the stream.
+    type Item;
+
+    /// Attempts to pull out the next value: `Ready(None)` once the stream
+    /// has ended.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+
+    /// A future resolving to the next value, or `None` once the stream has
+    /// ended.
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+}
+
+/// Future returned by [`AsyncStream::next`].
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<S: AsyncStream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+/// Adds logging adapters to every [`AsyncStream`].
+pub trait TestLogStream: AsyncStream + Sized {
+    /// Logs every item this stream yields at [`Level::Trace`], with its
+    /// `Debug` form, and the end of the stream with the number of items. A
+    /// stream dropped before it ended says so.
+    ///
+    /// Like [`log_polls`](TestLogFuture::log_polls) this is not compiled
+    /// out, so use it only in test code.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::pin::Pin;
+    /// use std::task::{Context, Poll};
+    /// use testlog::{AsyncStream, TestLogStream};
+    ///
+    /// struct Countdown(u8);
+    ///
+    /// impl AsyncStream for Countdown {
+    ///     type Item = u8;
+    ///
+    ///     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u8>> {
+    ///         self.0 = self.0.saturating_sub(1);
+    ///         Poll::Ready((self.0 > 0).then_some(self.0))
+    ///     }
+    /// }
+    ///
+    /// # async fn test() {
+    /// let mut ticks = Countdown(3).log_stream_items("ticks");
+    /// while ticks.next().await.is_some() {}
+    /// // TRACE ticks: item #1: 2
+    /// // TRACE ticks: item #2: 1
+    /// // TRACE ticks: ended after 2 items
+    /// # }
+    /// ```
+    #[track_caller]
+    fn log_stream_items(self, label: &'static str) -> LogStreamItems<Self>
+    where
+        Self::Item: Debug,
+    {
+        LogStreamItems {
+            inner: self,
+            label,
+            meta: caller_metadata(Level::Trace),
+            items: 0,
+            done: false,
+        }
+    }
+}
+
+impl<S: AsyncStream> TestLogStream for S {}
+
+/// Stream returned by [`TestLogStream::log_stream_items`].
+#[must_use = "streams do nothing unless polled"]
+pub struct LogStreamItems<S> {
+    inner: S,
+    label: &'static str,
+    meta: Metadata,
+    items: u64,
+    done: bool,
+}
+
+impl<S: AsyncStream> AsyncStream for LogStreamItems<S>
+where
+    S::Item: Debug,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        // SAFETY: as in `LogPolls::poll`, only `inner` is treated as pinned
+        // and it is never moved out.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        let result = inner.poll_next(cx);
+        match &result {
+            Poll::Pending => {}
+            Poll::Ready(Some(item)) => {
+                this.items += 1;
+                if emit::enabled(&this.meta) {
+                    emit::log(
+                        &this.meta,
+                        format_args!("{}: item #{}: {item:?}", this.label, this.items),
+                    );
+                }
+            }
+            Poll::Ready(None) => {
+                if !this.done && emit::enabled(&this.meta) {
+                    let s = if this.items == 1 { "" } else { "s" };
+                    emit::log(
+                        &this.meta,
+                        format_args!("{}: ended after {} item{s}", this.label, this.items),
+                    );
+                }
+                this.done = true;
+            }
+        }
+        result
+    }
+}
+
+impl<S> Drop for LogStreamItems<S> {
+    fn drop(&mut self) {
+        if !self.done && emit::enabled(&self.meta) {
+            let s = if self.items == 1 { "" } else { "s" };
+            emit::log(
+                &self.meta,
+                format_args!(
+                    "{}: dropped before the end after {} item{s}",
+                    self.label, self.items
+                ),
+            );
+        }
+    }
+}
+
+/// A [`Waker`] that counts how often it is woken, for tests that poll
+/// futures by hand. Pass its count to
+/// [`test_log_poll_state!`](crate::test_log_poll_state) to see whether a
+/// pending future arranged to be woken.
+///
+/// ```rust
+/// use std::task::Context;
+/// use testlog::WakeCounter;
+///
+/// let counter = WakeCounter::new();
+/// let waker = counter.waker();
+/// let _cx = Context::from_waker(&waker);
+/// waker.wake_by_ref();
+/// assert_eq!(counter.wakes(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WakeCounter {
+    count: Arc<Count>,
+}
+
+#[derive(Debug, Default)]
+struct Count(AtomicU64);
+
+impl Wake for Count {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl WakeCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A waker that adds to this counter; clones of it do too.
+    pub fn waker(&self) -> Waker {
+        Waker::from(Arc::clone(&self.count))
+    }
+
+    /// Wakeups so far, from all of the counter's wakers.
+    pub fn wakes(&self) -> u64 {
+        self.count.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Backend of `test_log_poll_state!`; `ready` holds the described output
+/// of a ready poll.
+#[doc(hidden)]
+pub fn log_poll_state(meta: &Metadata, expr: &str, ready: Option<String>, wakes: Option<u64>) {
+    let state = match ready {
+        Some(value) => format!("Ready({value})"),
+        None => "Pending".to_string(),
+    };
+    match wakes {
+        Some(wakes) => {
+            let s = if wakes == 1 { "" } else { "s" };
+            emit::log(meta, format_args!("`{expr}` → {state} ({wakes} wake{s})"));
+        }
+        None => emit::log(meta, format_args!("`{expr}` → {state}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+
+    /// Pending on the first poll, ready on the second.
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = u8;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u8> {
+            if std::mem::replace(&mut self.0, true) {
+                Poll::Ready(7)
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn logs_each_poll_and_early_drops() {
+        let _capture = crate::capture();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut future = Box::pin(Yield(false).log_polls("yield"));
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(7));
+        drop(future);
+        drop(Yield(false).log_polls("never polled"));
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("yield: poll #1 → Pending"));
+        assert!(lines[1].contains("yield: poll #2 → Ready (+"));
+        assert!(lines[2].contains("never polled: dropped while pending after 0 polls"));
+    }
+
+    #[test]
+    fn logs_poll_results_and_wakes() {
+        let _capture = crate::capture();
+        let counter = WakeCounter::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(Yield(false));
+        let first = crate::test_log_poll_state!(future.as_mut().poll(&mut cx), wakes: counter);
+        assert!(first.is_pending());
+        let second = crate::test_log_poll_state!(future.as_mut().poll(&mut cx));
+        assert_eq!(second, Poll::Ready(7));
+        assert_eq!(
+            crate::captured_lines(),
+            [
+                "INFO  `future.as_mut().poll(&mut cx)` → Pending (1 wake)",
+                "INFO  `future.as_mut().poll(&mut cx)` → Ready(7)",
+            ]
+        );
+    }
+
+    /// Yields the items of a vector, pending before each one.
+    struct Items(Vec<u8>, bool);
+
+    impl AsyncStream for Items {
+        type Item = u8;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+            if !std::mem::replace(&mut self.1, true) {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.1 = false;
+            Poll::Ready((!self.0.is_empty()).then(|| self.0.remove(0)))
+        }
+    }
+
+    #[test]
+    fn logs_stream_items_and_the_end() {
+        let _capture = crate::capture();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut stream = Items(vec![4, 2], false).log_stream_items("items");
+        let mut seen = Vec::new();
+        loop {
+            match Pin::new(&mut stream.next()).poll(&mut cx) {
+                Poll::Pending => continue,
+                Poll::Ready(Some(item)) => seen.push(item),
+                Poll::Ready(None) => break,
+            }
+        }
+        assert_eq!(seen, [4, 2]);
+        drop(stream);
+        let mut early = Items(vec![1, 2], true).log_stream_items("early");
+        assert_eq!(
+            Pin::new(&mut early).poll_next(&mut cx),
+            Poll::Ready(Some(1))
+        );
+        drop(early);
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 5, "{lines:#?}");
+        assert!(lines[0].ends_with("items: item #1: 4"));
+        assert!(lines[1].ends_with("items: item #2: 2"));
+        assert!(lines[2].ends_with("items: ended after 2 items"));
+        assert!(lines[3].ends_with("early: item #1: 1"));
+        assert!(lines[4].ends_with("early: dropped before the end after 1 item"));
+    }
+}
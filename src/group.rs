@@ -0,0 +1,145 @@
+//! Collapsible output groups used by [`test_group!`](crate::test_group).
+//!
+//! On GitHub Actions the outermost group on a thread is emitted as a
+//! `::group::`/`::endgroup::` pair so the runner folds it. Everywhere else
+//! (and for nested groups) a begin/end marker is printed and everything
+//! logged inside the group is indented.
+
+use crate::color;
+use crate::spans::SpanStart;
+use std::cell::Cell;
+use std::fmt;
+
+/// Spaces added per nesting level inside a group.
+pub(crate) const INDENT: &str = "  ";
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static NATIVE_OPEN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Current indentation depth of the calling thread.
+pub(crate) fn depth() -> usize {
+    DEPTH.with(Cell::get)
+}
+
+/// Appends the indentation for `depth` to `out`.
+pub(crate) fn push_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
+
+/// Prefixes every line of `msg` with the indentation for `depth`.
+pub(crate) fn indent_lines(depth: usize, msg: &str) -> String {
+    if depth == 0 {
+        return msg.to_string();
+    }
+    let pad = INDENT.repeat(depth);
+    msg.split('\n')
+        .map(|line| format!("{pad}{line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Indents later output on the thread by one level.
+pub(crate) fn indent() {
+    DEPTH.with(|d| d.set(d.get() + 1));
+}
+
+/// Undoes one [`indent`].
+pub(crate) fn outdent() {
+    DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+}
+
+/// Writes a begin/end marker line at the current depth.
+fn marker(text: String) {
+    let config = crate::config::current();
+    let text = color::paint(&text, &config.theme.heading(), config.color_enabled());
+    crate::emit::write(config.stream, None, &text);
+}
+
+/// Writes a workflow command, which must start at column zero.
+fn command(text: &str) {
+    crate::emit::deliver(crate::config::current().stream, None, text);
+}
+
+fn github_actions() -> bool {
+    std::env::var_os("GITHUB_ACTIONS").is_some_and(|v| v == "true")
+}
+
+/// Guard created by `test_group!`; prints the end marker when dropped.
+#[doc(hidden)]
+pub struct GroupGuard {
+    title: String,
+    native: bool,
+    trace: Option<SpanStart>,
+}
+
+impl GroupGuard {
+    pub fn begin(title: &dyn fmt::Display) -> Self {
+        crate::emit::flush_pending();
+        let title = title.to_string();
+        let native = github_actions() && !NATIVE_OPEN.with(Cell::get);
+        if native {
+            NATIVE_OPEN.with(|open| open.set(true));
+            command(&format!("::group::{title}"));
+        } else {
+            marker(format!("┌─ {title}"));
+            indent();
+        }
+        GroupGuard {
+            title,
+            native,
+            trace: crate::spans::start(),
+        }
+    }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        crate::emit::flush_pending();
+        crate::spans::end("group", &self.title, self.trace);
+        if self.native {
+            NATIVE_OPEN.with(|open| open.set(false));
+            command("::endgroup::");
+        } else {
+            outdent();
+            marker(format!("└─ {}", self.title));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indents_every_line() {
+        assert_eq!(indent_lines(0, "a\nb"), "a\nb");
+        assert_eq!(indent_lines(2, "a\nb"), "    a\n    b");
+    }
+
+    #[test]
+    fn nested_groups_track_depth() {
+        if github_actions() {
+            return;
+        }
+        assert_eq!(depth(), 0);
+        crate::test_group!("outer", {
+            assert_eq!(depth(), 1);
+            crate::test_group!("inner", {
+                crate::test_log!("deepest");
+                assert_eq!(depth(), 2);
+            });
+            assert_eq!(depth(), 1);
+        });
+        assert_eq!(depth(), 0);
+    }
+
+    #[test]
+    fn group_returns_block_value() {
+        let v = crate::test_group!("compute", { 40 + 2 });
+        assert_eq!(v, 42);
+    }
+}
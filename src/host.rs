@@ -0,0 +1,97 @@
+//! One-time banner describing the machine, printed before the first output
+//! of the process when `TESTLOG_HOST_INFO` is set.
+//!
+//! The banner bypasses libtest's capture, so it appears once near the top
+//! of a CI log whichever test fails.
+
+use std::sync::Once;
+
+/// Name and value pairs shown in the banner, in order.
+fn facts(env: &[String]) -> Vec<(String, String)> {
+    let mut os = format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH);
+    if let Some(release) = os_release() {
+        os = format!("{os}, {release}");
+    }
+    let cpus =
+        std::thread::available_parallelism().map_or_else(|_| "unknown".into(), |n| n.to_string());
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let mut facts = vec![
+        ("os".to_string(), os),
+        ("cpus".to_string(), cpus),
+        (
+            "rustc".to_string(),
+            env!("TESTLOG_RUSTC_VERSION").to_string(),
+        ),
+        ("profile".to_string(), profile.to_string()),
+    ];
+    for name in env {
+        let value = std::env::var(name).unwrap_or_else(|_| "(unset)".to_string());
+        facts.push((name.clone(), value));
+    }
+    facts
+}
+
+/// The distribution's name from `/etc/os-release`, where there is one.
+fn os_release() -> Option<String> {
+    let text = std::fs::read_to_string("/etc/os-release").ok()?;
+    let line = text.lines().find_map(|l| l.strip_prefix("PRETTY_NAME="))?;
+    Some(line.trim_matches('"').to_string()).filter(|name| !name.is_empty())
+}
+
+fn render(facts: &[(String, String)], width: usize) -> String {
+    let pad = facts.iter().map(|(name, _)| name.len()).max().unwrap_or(0) + 1;
+    let mut out = crate::section::banner("host", width);
+    for (name, value) in facts {
+        out.push_str(&format!("\n{:<pad$} {value}", format!("{name}:")));
+    }
+    out
+}
+
+/// Prints the banner if it is enabled and has not been printed yet.
+pub(crate) fn banner_once() {
+    static PRINTED: Once = Once::new();
+    if PRINTED.is_completed() {
+        return;
+    }
+    PRINTED.call_once(|| {
+        let config = crate::config::current();
+        if let Some(env) = &config.host_info {
+            crate::output::write_uncaptured(&render(&facts(env), config.width));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_values() {
+        let facts = [
+            ("os".to_string(), "linux (x86_64)".to_string()),
+            ("GITHUB_SHA".to_string(), "0f3c".to_string()),
+        ];
+        assert_eq!(
+            render(&facts, 20),
+            "──── host ──────────\n\
+             os:         linux (x86_64)\n\
+             GITHUB_SHA: 0f3c"
+        );
+    }
+
+    #[test]
+    fn reports_selected_variables() {
+        let facts = facts(&["TESTLOG_SURELY_UNSET".to_string()]);
+        let names: Vec<&str> = facts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["os", "cpus", "rustc", "profile", "TESTLOG_SURELY_UNSET"]
+        );
+        assert_eq!(facts[4].1, "(unset)");
+        assert!(facts[2].1.starts_with("rustc "), "{}", facts[2].1);
+    }
+}
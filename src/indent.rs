@@ -0,0 +1,104 @@
+//! Manual nesting, for output shaped like something other than the call
+//! stack: a recursion tree, or a structure walked by a loop.
+//!
+//! The levels are the ones [`test_group!`](crate::test_group) and traced
+//! functions use, so all three nest inside each other.
+
+/// Guard returned by [`indented`]; the level is removed when it is dropped.
+#[must_use = "output is only indented while the guard is alive"]
+#[derive(Debug)]
+pub struct IndentGuard {
+    _private: (),
+}
+
+impl Drop for IndentGuard {
+    fn drop(&mut self) {
+        dedent();
+    }
+}
+
+/// Indents later output on the calling thread by one level, until a
+/// matching [`dedent`].
+///
+/// ```rust
+/// fn walk(node: &str, depth: u32) {
+///     testlog::test_log!("{node}");
+///     if depth < 2 {
+///         testlog::indent();
+///         walk("left", depth + 1);
+///         walk("right", depth + 1);
+///         testlog::dedent();
+///     }
+/// }
+/// walk("root", 0);
+/// ```
+pub fn indent() {
+    crate::emit::flush_pending();
+    crate::group::indent();
+}
+
+/// Removes one level added by [`indent`]; does nothing at the outermost
+/// level.
+pub fn dedent() {
+    crate::emit::flush_pending();
+    crate::group::outdent();
+}
+
+/// Indents later output on the calling thread by one level until the
+/// returned guard is dropped, so early returns and `?` cannot leave the
+/// level behind.
+///
+/// ```rust
+/// fn sum(values: &[i64]) -> i64 {
+///     testlog::test_log!("sum of {} values", values.len());
+///     let _nested = testlog::indented();
+///     match values {
+///         [] => 0,
+///         [first, rest @ ..] => first + sum(rest),
+///     }
+/// }
+/// assert_eq!(sum(&[1, 2, 3]), 6);
+/// ```
+pub fn indented() -> IndentGuard {
+    indent();
+    IndentGuard { _private: () }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(depth: u32) {
+        crate::test_log!("node {depth}");
+        if depth < 2 {
+            let _nested = indented();
+            tree(depth + 1);
+            tree(depth + 1);
+        }
+    }
+
+    #[test]
+    fn output_follows_the_recursion() {
+        let _capture = crate::capture();
+        tree(0);
+        indent();
+        crate::test_log!("manual");
+        dedent();
+        dedent();
+        crate::test_log!("back");
+        assert_eq!(
+            crate::captured_lines(),
+            [
+                "INFO  node 0",
+                "  INFO  node 1",
+                "    INFO  node 2",
+                "    INFO  node 2",
+                "  INFO  node 1",
+                "    INFO  node 2",
+                "    INFO  node 2",
+                "  INFO  manual",
+                "INFO  back",
+            ]
+        );
+    }
+}
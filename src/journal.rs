@@ -0,0 +1,139 @@
+//! Process-wide journal of captured output, dumped when the process exits.
+//!
+//! Per-thread captures replay each thread's output on its own, which loses
+//! the relative order of records from different threads. When
+//! [`ReplayDump`] is enabled every captured record is also stamped with a
+//! global sequence number and kept here, and the merged journal is printed
+//! from an exit hook in true emission order.
+
+use crate::capture::{CaptureLimit, RingBuffer};
+use crate::output::Stream;
+use std::io::Write as _;
+use std::str::FromStr;
+use std::sync::{Mutex, Once};
+
+/// When the merged journal of captured output is printed at exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayDump {
+    /// Nothing is journaled.
+    #[default]
+    Off,
+    /// Printed only if some thread panicked.
+    OnFailure,
+    /// Always printed.
+    Always,
+}
+
+impl FromStr for ReplayDump {
+    type Err = String;
+
+    /// Accepts `off`, `failure` (also `1`/`true`) and `always`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "0" | "false" | "" => Ok(ReplayDump::Off),
+            "failure" | "on-failure" | "1" | "true" => Ok(ReplayDump::OnFailure),
+            "always" => Ok(ReplayDump::Always),
+            other => Err(format!("unknown replay mode `{other}`")),
+        }
+    }
+}
+
+/// Records from every thread, in the order they were captured.
+struct Journal {
+    next: u64,
+    buffer: RingBuffer,
+}
+
+impl Journal {
+    fn new(limit: CaptureLimit) -> Self {
+        Journal {
+            next: 0,
+            buffer: RingBuffer::new(limit),
+        }
+    }
+
+    /// Stamps `text` with the next sequence number and keeps it.
+    fn push(&mut self, thread: &str, stream: Stream, text: &str) {
+        let seq = self.next;
+        self.next += 1;
+        let pad = " ".repeat(thread.len() + 11);
+        let body = text.replace('\n', &format!("\n{pad}"));
+        self.buffer
+            .push(stream, &format!("#{seq:06} [{thread}] {body}"));
+    }
+
+    /// The dump as printed at exit.
+    fn render(&self, width: usize) -> String {
+        let mut out = crate::section::banner(
+            &format!("replay: {} records in emission order", self.next),
+            width,
+        );
+        if self.buffer.dropped() > 0 {
+            out.push_str(&format!(
+                "\n… {} earlier lines dropped by the capture limit …",
+                self.buffer.dropped()
+            ));
+        }
+        for line in self.buffer.lines() {
+            out.push('\n');
+            out.push_str(line);
+        }
+        out
+    }
+}
+
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+/// Adds a captured record from `thread` to the journal.
+pub(crate) fn record(thread: &str, stream: Stream, text: &str) {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| crate::exit::at_exit(dump));
+    let mut journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    journal
+        .get_or_insert_with(|| Journal::new(crate::config::current().capture_limit))
+        .push(thread, stream, text);
+}
+
+/// Exit hook: prints the journal if the configured mode asks for it.
+///
+/// Writes straight to stderr, since the test harness's capture is gone by
+/// now, and ignores write errors because nothing may unwind out of here.
+extern "C" fn dump() {
+    let config = crate::config::current();
+    let wanted = match config.replay {
+        ReplayDump::Off => false,
+        ReplayDump::OnFailure => crate::panic::any_panicked(),
+        ReplayDump::Always => true,
+    };
+    let journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(journal) = journal.as_ref().filter(|_| wanted) {
+        let _ = writeln!(std::io::stderr(), "{}", journal.render(config.width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("failure".parse(), Ok(ReplayDump::OnFailure));
+        assert_eq!("1".parse(), Ok(ReplayDump::OnFailure));
+        assert_eq!("Always".parse(), Ok(ReplayDump::Always));
+        assert!("sometimes".parse::<ReplayDump>().is_err());
+    }
+
+    #[test]
+    fn records_are_numbered_in_emission_order() {
+        let mut journal = Journal::new(CaptureLimit::lines(3));
+        journal.push("a", Stream::Stderr, "first");
+        journal.push("b", Stream::Stderr, "second\nline");
+        journal.push("a", Stream::Stdout, "third");
+        let lines: Vec<_> = journal.buffer.lines().collect();
+        assert_eq!(
+            lines,
+            ["#000001 [b] second\n            line", "#000002 [a] third"]
+        );
+        assert!(journal.render(20).contains("1 earlier lines dropped"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Pretty-printing for [`test_log_pretty_json!`](crate::test_log_pretty_json).
+//!
+//! A small validating parser, so the feature needs no dependencies. Numbers
+//! and strings keep their source text, escapes included; only whitespace and
+//! the order of object keys change.
+
+use crate::record::Metadata;
+
+enum Value<'a> {
+    /// `null`, `true`, `false`, a number or a string, as written.
+    Scalar(&'a str),
+    Array(Vec<Value<'a>>),
+    /// Members sorted by key; duplicate keys keep their order.
+    Object(Vec<(&'a str, Value<'a>)>),
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, what: &str) -> String {
+        format!("{what} at byte {}", self.pos)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_ws();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", byte as char)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, String> {
+        self.skip_ws();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::Scalar),
+            Some(b'-' | b'0'..=b'9') => self.number().map(Value::Scalar),
+            Some(_) => ["null", "true", "false"]
+                .into_iter()
+                .find(|word| self.src[self.pos..].starts_with(word))
+                .map(|word| {
+                    self.pos += word.len();
+                    Value::Scalar(word)
+                })
+                .ok_or_else(|| self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value<'a>, String> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => break,
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+        self.pos += 1;
+        members.sort_by(|a, b| a.0.cmp(b.0));
+        Ok(Value::Object(members))
+    }
+
+    fn array(&mut self) -> Result<Value<'a>, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => break,
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+        self.pos += 1;
+        Ok(Value::Array(items))
+    }
+
+    /// A string literal, returned with its quotes.
+    fn string(&mut self) -> Result<&'a str, String> {
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {}
+                        Some(b'u') => {
+                            let hex = self.src.get(self.pos + 1..self.pos + 5);
+                            if !hex.is_some_and(|h| h.bytes().all(|b| b.is_ascii_hexdigit())) {
+                                return Err(self.error("invalid `\\u` escape"));
+                            }
+                            self.pos += 4;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                Some(0..=0x1f) => return Err(self.error("control character in string")),
+                Some(_) => {}
+                None => return Err(self.error("unterminated string")),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        Ok(&self.src[start..self.pos])
+    }
+
+    fn number(&mut self) -> Result<&'a str, String> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while p.peek().is_some_and(|b| b.is_ascii_digit()) {
+                p.pos += 1;
+            }
+            p.pos > from
+        };
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("expected digits"));
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error("expected digits"));
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("expected digits"));
+            }
+        }
+        Ok(&self.src[start..self.pos])
+    }
+}
+
+/// Parses `src` and writes it indented by two spaces per level.
+fn pretty(src: &str) -> Result<String, String> {
+    let mut parser = Parser { src, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_ws();
+    if parser.pos < src.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    let mut out = String::new();
+    write_value(&mut out, &value, 0);
+    Ok(out)
+}
+
+fn write_value(out: &mut String, value: &Value, depth: usize) {
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.extend(std::iter::repeat_n("  ", depth));
+    };
+    match value {
+        Value::Scalar(text) => out.push_str(text),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(members) if members.is_empty() => out.push_str("{}"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                write_value(out, item, depth + 1);
+            }
+            newline(out, depth);
+            out.push(']');
+        }
+        Value::Object(members) => {
+            out.push('{');
+            for (i, (key, item)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                out.push_str(key);
+                out.push_str(": ");
+                write_value(out, item, depth + 1);
+            }
+            newline(out, depth);
+            out.push('}');
+        }
+    }
+}
+
+/// Backend of `test_log_pretty_json!`.
+///
+/// Input that does not parse is logged as it is, after the parse error.
+#[doc(hidden)]
+pub fn log_json(meta: &Metadata, src: &str) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    match pretty(src) {
+        Ok(text) => crate::emit::log(meta, format_args!("{text}")),
+        Err(err) => crate::emit::log(meta, format_args!("invalid JSON ({err}): {src}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_keys_and_keeps_scalars_verbatim() {
+        let src = r#"{"b":[1,2.5e3,{}],"a":{"z":null,"y":"x\"\u00e9"},"c":[]}"#;
+        assert_eq!(
+            pretty(src).unwrap(),
+            "{\n  \"a\": {\n    \"y\": \"x\\\"\\u00e9\",\n    \"z\": null\n  },\n  \
+             \"b\": [\n    1,\n    2.5e3,\n    {}\n  ],\n  \"c\": []\n}"
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_position() {
+        assert_eq!(pretty(r#"{"a" 1}"#).unwrap_err(), "expected `:` at byte 5");
+        assert_eq!(pretty("[1,]").unwrap_err(), "expected a value at byte 3");
+        assert_eq!(pretty("01").unwrap_err(), "trailing characters at byte 1");
+        assert!(pretty(r#""\x""#).is_err());
+    }
+}
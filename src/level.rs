@@ -0,0 +1,315 @@
+//! Log levels and the markers used to display them.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::str::FromStr;
+
+/// Severity of a log record, from most to least severe.
+///
+/// Levels are ordered so that `Level::Error < Level::Trace`; a record is
+/// shown when its level is at most the configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// All levels, most severe first.
+    pub const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    /// Maps a `-v`-style verbosity count onto a level: `0` shows only
+    /// errors, `4` (or more) shows everything.
+    pub fn from_verbosity(verbosity: u8) -> Level {
+        Level::ALL[usize::from(verbosity).min(Level::ALL.len() - 1)]
+    }
+
+    /// The verbosity count that maps to this level.
+    pub fn verbosity(self) -> u8 {
+        self as u8 - 1
+    }
+
+    /// Upper-case name of the level, e.g. `"WARN"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    /// The marker shown in front of records of this level.
+    pub fn marker(self, style: MarkerStyle) -> &'static str {
+        match (style, self) {
+            (MarkerStyle::Emoji, Level::Error) => "❌",
+            (MarkerStyle::Emoji, Level::Warn) => "⚠️",
+            (MarkerStyle::Emoji, Level::Info) => "ℹ️",
+            (MarkerStyle::Emoji, Level::Debug) => "🐛",
+            (MarkerStyle::Emoji, Level::Trace) => "🔎",
+            (MarkerStyle::Short, Level::Error) => "E",
+            (MarkerStyle::Short, Level::Warn) => "W",
+            (MarkerStyle::Short, Level::Info) => "I",
+            (MarkerStyle::Short, Level::Debug) => "D",
+            (MarkerStyle::Short, Level::Trace) => "T",
+            (MarkerStyle::Word, level) => level.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            other => Err(format!("unknown level `{other}`")),
+        }
+    }
+}
+
+thread_local! {
+    static BUMP: Cell<u8> = const { Cell::new(0) };
+    static OVERRIDES: RefCell<Vec<Override>> = const { RefCell::new(Vec::new()) };
+    static NEXT_OVERRIDE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Guard returned by [`bump_verbosity`]; takes its steps back off the
+/// thread's verbosity when dropped.
+#[must_use = "verbosity is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct VerbosityGuard {
+    /// Steps this guard added, which may be fewer than asked for at the top.
+    steps: u8,
+}
+
+impl Drop for VerbosityGuard {
+    fn drop(&mut self) {
+        let _ = BUMP.try_with(|b| b.set(b.get().saturating_sub(self.steps)));
+    }
+}
+
+/// Raises the verbosity of the calling thread by `steps` levels until the
+/// returned guard is dropped.
+///
+/// Only records emitted on this thread are affected, so a test can turn on
+/// extra detail for itself without changing what parallel tests print.
+///
+/// ```rust
+/// let _more = testlog::bump_verbosity(2);
+/// testlog::test_trace!("shown even if TESTLOG_VERBOSITY=2");
+/// ```
+pub fn bump_verbosity(steps: u8) -> VerbosityGuard {
+    let previous = BUMP.with(|b| b.replace(b.get().saturating_add(steps)));
+    VerbosityGuard {
+        steps: BUMP.with(Cell::get) - previous,
+    }
+}
+
+/// `max` raised by the calling thread's verbosity bump.
+pub(crate) fn bumped(max: Level) -> Level {
+    Level::from_verbosity(max.verbosity().saturating_add(BUMP.with(Cell::get)))
+}
+
+/// One [`set_level_for`] override, identified so that its guard removes
+/// only its own entry.
+struct Override {
+    id: u64,
+    target: String,
+    level: Level,
+}
+
+/// Guard returned by [`set_level_for`]; removes the override when dropped.
+#[must_use = "the override is removed when the guard is dropped"]
+#[derive(Debug)]
+pub struct LevelOverrideGuard {
+    id: u64,
+}
+
+impl Drop for LevelOverrideGuard {
+    fn drop(&mut self) {
+        let _ = OVERRIDES.try_with(|o| o.borrow_mut().retain(|entry| entry.id != self.id));
+    }
+}
+
+/// Shows records from `target` and the modules below it up to `level`
+/// until the returned guard is dropped, whatever the global level filter.
+///
+/// `target` is a module path such as `my_crate::parser`. The override can
+/// lower the level as well as raise it, and when several match, the one
+/// with the longest target wins, the newest among equals. Like [`bump_verbosity`] it applies to the
+/// calling thread only, so a test can trace one module for its own duration
+/// without flooding parallel tests.
+///
+/// ```rust
+/// use testlog::{set_level_for, Level};
+///
+/// let _trace = set_level_for("my_crate::parser", Level::Trace);
+/// let _quiet = set_level_for("my_crate::parser::lexer", Level::Warn);
+/// ```
+pub fn set_level_for(target: &str, level: Level) -> LevelOverrideGuard {
+    let target = target.trim_end_matches("::").to_string();
+    let id = NEXT_OVERRIDE.with(|next| next.replace(next.get() + 1));
+    OVERRIDES.with(|o| o.borrow_mut().push(Override { id, target, level }));
+    LevelOverrideGuard { id }
+}
+
+/// The calling thread's override for records from `target`, if any.
+pub(crate) fn override_for(target: &str) -> Option<Level> {
+    OVERRIDES
+        .try_with(|o| {
+            let o = o.try_borrow().ok()?;
+            o.iter()
+                .filter(|entry| {
+                    target
+                        .strip_prefix(entry.target.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+                })
+                .max_by_key(|entry| entry.target.len())
+                .map(|entry| entry.level)
+        })
+        .ok()
+        .flatten()
+}
+
+/// How level markers are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkerStyle {
+    /// Emoji such as `⚠️` and `❌`.
+    Emoji,
+    /// Single-letter codes such as `W` and `E`.
+    Short,
+    /// Full upper-case words such as `WARN` and `ERROR`.
+    #[default]
+    Word,
+}
+
+impl FromStr for MarkerStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "emoji" => Ok(MarkerStyle::Emoji),
+            "short" | "symbols" => Ok(MarkerStyle::Short),
+            "word" | "words" | "plain" => Ok(MarkerStyle::Word),
+            other => Err(format!("unknown marker style `{other}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_order_by_verbosity() {
+        assert!(Level::Error < Level::Warn);
+        assert!(Level::Debug < Level::Trace);
+    }
+
+    #[test]
+    fn parses_levels_case_insensitively() {
+        assert_eq!("WARNING".parse(), Ok(Level::Warn));
+        assert_eq!(" trace ".parse(), Ok(Level::Trace));
+        assert!("loud".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn verbosity_maps_onto_levels() {
+        assert_eq!(Level::from_verbosity(0), Level::Error);
+        assert_eq!(Level::from_verbosity(2), Level::Info);
+        assert_eq!(Level::from_verbosity(9), Level::Trace);
+        for level in Level::ALL {
+            assert_eq!(Level::from_verbosity(level.verbosity()), level);
+        }
+    }
+
+    #[test]
+    fn bump_is_scoped_to_the_guard() {
+        assert_eq!(bumped(Level::Warn), Level::Warn);
+        {
+            let _outer = bump_verbosity(1);
+            assert_eq!(bumped(Level::Warn), Level::Info);
+            let _inner = bump_verbosity(10);
+            assert_eq!(bumped(Level::Warn), Level::Trace);
+        }
+        assert_eq!(bumped(Level::Warn), Level::Warn);
+    }
+
+    #[test]
+    fn bumps_can_end_in_any_order() {
+        let outer = bump_verbosity(1);
+        let inner = bump_verbosity(2);
+        drop(outer);
+        assert_eq!(bumped(Level::Warn), Level::Debug);
+        drop(inner);
+        assert_eq!(bumped(Level::Warn), Level::Warn);
+    }
+
+    #[test]
+    fn overrides_match_module_prefixes() {
+        let _parser = set_level_for("app::parser", Level::Trace);
+        assert_eq!(override_for("app::parser::lexer"), Some(Level::Trace));
+        assert_eq!(override_for("app::parsers"), None);
+        {
+            let _lexer = set_level_for("app::parser::lexer", Level::Error);
+            let _again = set_level_for("app", Level::Info);
+            assert_eq!(override_for("app::parser::lexer"), Some(Level::Error));
+            assert_eq!(override_for("app::parser"), Some(Level::Trace));
+        }
+        assert_eq!(override_for("app::parser::lexer"), Some(Level::Trace));
+        assert_eq!(override_for("app"), None);
+    }
+
+    #[test]
+    fn overrides_can_end_in_any_order() {
+        let first = set_level_for("app::db", Level::Trace);
+        let second = set_level_for("app::net", Level::Debug);
+        drop(first);
+        assert_eq!(override_for("app::db"), None);
+        assert_eq!(override_for("app::net"), Some(Level::Debug));
+        let third = set_level_for("app::db", Level::Warn);
+        drop(second);
+        assert_eq!(override_for("app::net"), None);
+        assert_eq!(override_for("app::db"), Some(Level::Warn));
+        drop(third);
+        assert_eq!(override_for("app::db"), None);
+    }
+
+    #[test]
+    fn inner_override_of_the_same_target_wins() {
+        let _outer = set_level_for("app", Level::Warn);
+        {
+            let _inner = set_level_for("app", Level::Trace);
+            assert_eq!(override_for("app::db"), Some(Level::Trace));
+        }
+        assert_eq!(override_for("app::db"), Some(Level::Warn));
+    }
+
+    #[test]
+    fn markers_follow_style() {
+        assert_eq!(Level::Warn.marker(MarkerStyle::Emoji), "⚠️");
+        assert_eq!(Level::Error.marker(MarkerStyle::Short), "E");
+        assert_eq!(Level::Info.marker(MarkerStyle::Word), "INFO");
+    }
+}
@@ -1,6 +1,6 @@
 //! # testlog
 //!
-//! A tiny, focused crate that provides a single macro: `test_log!`
+//! A tiny, focused crate built around one macro: `test_log!`
 //!
 //! This macro prints to stderr **only when tests are running** and **only for the crate where it's used**.
 //! Perfect for debugging test failures without cluttering production output.
@@ -16,12 +16,327 @@
 //!     // Output only appears when running tests
 //! }
 //! ```
+//!
+//! Alongside `test_log!` (which logs at [`Level::Info`]) there is one macro
+//! per level: [`test_error!`], [`test_warn!`], [`test_info!`], [`test_debug!`]
+//! and [`test_trace!`]. Each line starts with a level marker whose style is
+//! chosen with [`MarkerStyle`]; a [`Preset`] adds or removes the other
+//! prefix elements (time, thread, module path, location).
+//!
+//! Long setup output can be wrapped in [`test_group!`] so it is folded on
+//! GitHub Actions and indented everywhere else, and test phases can be
+//! separated with [`test_section!`] banners.
+//!
+//! Output honours a small runtime [`Config`]; see the [`config`] module for
+//! the environment variables it reads.
+#![allow(clippy::test_attr_in_doctest)]
+// The original smoke tests below assert constants on purpose.
+#![cfg_attr(test, allow(clippy::assertions_on_constants))]
+
+// Lets code generated by `testlog-macros`, which names `::testlog`, be
+// used inside this crate too.
+extern crate self as testlog;
+
+#[cfg(feature = "alloc-stats")]
+mod alloc;
+mod artifact;
+mod assert;
+mod baseline;
+mod block;
+mod bytes_diff;
+mod caller;
+mod capture;
+mod chrome;
+mod clock;
+mod color;
+pub mod config;
+mod context;
+mod diff;
+mod emit;
+mod exit;
+mod fields;
+mod filter;
+mod format;
+#[cfg(feature = "async")]
+mod future;
+mod group;
+mod host;
+mod indent;
+mod journal;
+#[cfg(feature = "json")]
+mod json;
+mod level;
+mod lines;
+#[cfg(feature = "mem")]
+mod mem;
+mod once;
+#[cfg(feature = "otel")]
+mod otel;
+mod output;
+mod panic;
+#[cfg(feature = "perf")]
+mod profile;
+mod record;
+mod repeat;
+mod retry;
+mod scope;
+mod section;
+mod slowest;
+mod spans;
+mod str_diff;
+mod strict;
+mod suppress;
+mod test;
+mod theme;
+mod timing;
+mod todo;
+mod toml;
+mod trace;
+mod units;
+mod utf8;
+mod watchdog;
+mod width;
+#[cfg(feature = "xml")]
+mod xml;
+
+#[cfg(feature = "alloc-stats")]
+pub use alloc::{alloc_stats, AllocStats};
+pub use artifact::artifact_dir;
+pub use baseline::{baseline, BaselineGuard, BaselineMode, Normalize};
+pub use caller::log_at_caller;
+pub use capture::{
+    capture, captured_lines, start_capture, stop_capture, CaptureGuard, CaptureLimit,
+};
+pub use clock::{virtual_clock, VirtualClock};
+pub use color::ColorChoice;
+pub use config::{Config, ConfigBuilder};
+pub use filter::TagFilter;
+pub use format::{Preset, Wrap};
+#[cfg(feature = "async")]
+pub use future::{
+    AsyncStream, LogPolls, LogStreamItems, Next, TestLogFuture, TestLogStream, WakeCounter,
+};
+pub use indent::{dedent, indent, indented, IndentGuard};
+pub use journal::ReplayDump;
+pub use level::{
+    bump_verbosity, set_level_for, Level, LevelOverrideGuard, MarkerStyle, VerbosityGuard,
+};
+#[cfg(feature = "mem")]
+pub use mem::{memory_usage, MemoryUsage};
+pub use output::{BrokenPipe, Output, Stream};
+pub use scope::{push_scope, ScopeGuard};
+pub use strict::{strict, StrictGuard};
+pub use suppress::{suppress, SuppressGuard};
+pub use theme::{Color, Style, Theme};
+pub use units::HumanDuration;
+pub use watchdog::{watchdog, WatchdogGuard};
+
+/// Logs every call to the methods of an impl block under test.
+///
+/// Each instrumented method logs its arguments when it is entered and its
+/// return value when it returns; calls made in between are indented, so a
+/// state machine reads as a call tree. Values are shown with their `Debug`
+/// impl, or as `…` if they have none. Records are logged at
+/// [`Level::Trace`] unless a level is given, as in `#[trace_impl(debug)]`.
+///
+/// The original methods are compiled unchanged outside of tests. Methods
+/// marked `#[trace_impl(skip)]`, `const fn`s and `async fn`s are never
+/// instrumented.
+///
+/// # Examples
+///
+/// ```rust
+/// struct Door {
+///     open: bool,
+/// }
+///
+/// #[testlog::trace_impl]
+/// impl Door {
+///     fn toggle(&mut self, force: bool) -> bool {
+///         self.open = !self.open || force;
+///         self.open
+///     }
+/// }
+/// // Under test: `→ Door::toggle(force = false)` … `← Door::toggle = true`
+/// # assert!(Door { open: false }.toggle(false));
+/// ```
+pub use testlog_macros::trace_impl;
+
+/// `#[test]` plus testlog's per-test setup, in one attribute.
+///
+/// For the duration of the test the panic hook is installed, output is
+/// [captured](capture) and only printed if the test fails, and the thread
+/// field of the [`Preset::Pretty`] and [`Preset::Full`] formats shows the
+/// test's name.
+///
+/// With `#[testlog::test(check_env)]` the environment variables and the
+/// working directory are snapshotted when the test starts, and anything
+/// the test left changed is logged as a warning when it ends. With
+/// `#[testlog::test(strict)]`, or for every such test when
+/// `TESTLOG_STRICT=1`, the test fails if it logs anything at `WARN` or
+/// `ERROR`; see [`strict`](fn@strict). With `#[testlog::test(baseline)]`,
+/// or for every such test when `TESTLOG_BASELINE=compare`, the test fails
+/// if its log differs from the one an earlier run recorded; see
+/// [`baseline`](fn@baseline). Other arguments are passed on to `#[test]`.
+///
+/// ```rust
+/// #[testlog::test(check_env)]
+/// fn leaves_no_trace() {
+///     std::env::set_var("TESTLOG_EXAMPLE", "1");
+///     // WARN  test left the environment changed:
+///     //       +TESTLOG_EXAMPLE=1
+/// }
+/// ```
+// Left out of this crate's own unit tests, whose `use super::*` would make
+// the built-in `#[test]` ambiguous.
+#[cfg(not(test))]
+pub use testlog_macros::test;
+
+/// `#[tokio::test]` plus testlog's per-test setup, in one attribute.
+///
+/// Arguments are passed on to `#[tokio::test]`, so
+/// `#[testlog::tokio_test(flavor = "multi_thread")]` works as expected; the
+/// crate using it must depend on `tokio` with its `macros` and `rt`
+/// features. The setup, including the `check_env` option, is the same as
+/// for [`test`](macro@test).
+///
+/// The setup belongs to the thread the test body runs on. With
+/// `flavor = "multi_thread"`, tasks passed to `tokio::spawn` run on
+/// tokio's worker threads instead, so their records carry no test name,
+/// are not part of the test's capture and are not counted by `strict`;
+/// libtest still holds back their output until the test fails. Log from
+/// the test body, or use the default `current_thread` flavor, where that
+/// matters.
+///
+/// ```rust,ignore
+/// #[testlog::tokio_test]
+/// async fn reconnects() {
+///     testlog::test_log!("only printed if this test fails");
+/// }
+/// ```
+pub use testlog_macros::tokio_test;
+
+/// Asserts that a condition holds; when it does not, logs the `Debug` value
+/// of every variable the condition mentions, then panics like `assert!`.
+///
+/// An optional message with `format!` arguments follows the condition and
+/// becomes the panic message. Variables are picked from the condition's
+/// tokens: lowercase identifiers that are not fields, methods, functions,
+/// path segments or closure parameters. Values without `Debug` show as `…`.
+/// The variables are read after the condition has run, so it must not move
+/// them. Outside of tests the assertion is compiled out and the condition
+/// is not evaluated.
+///
+/// ```rust
+/// let (a, b) = (3, 5);
+/// testlog::test_assert!(a < b, "a must stay below b");
+/// // On failure, before the panic:
+/// // ERROR assertion failed: `a < b`
+/// //         a = 5
+/// //         b = 3
+/// ```
+pub use testlog_macros::test_assert;
+
+/// Logs selected fields of a value at [`Level::Info`], instead of its whole
+/// `Debug` output.
+///
+/// After the value come the fields to show: paths such as `name` or
+/// `limits.max`, method calls such as `items.len()`, or `label = expr` for
+/// any other expression, evaluated as written. Each is shown with `Debug`,
+/// or as `…` without it, so the value itself need not implement `Debug`.
+/// The value is borrowed, and nothing is evaluated outside of tests or
+/// when the record would be filtered out.
+///
+/// ```rust
+/// struct Request { id: u64, path: String, body: Vec<u8> }
+///
+/// let req = Request { id: 7, path: "/login".into(), body: vec![0; 4096] };
+/// testlog::test_log_fields_of!(req, id, path, body.len(), large = req.body.len() > 1024);
+/// // INFO  req { id: 7, path: "/login", body.len(): 4096, large: true }
+/// ```
+pub use testlog_macros::test_log_fields_of;
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "alloc-stats")]
+    pub use crate::alloc::AllocScope;
+    pub use crate::artifact::write_artifact;
+    pub use crate::assert::assert_failed;
+    pub use crate::block::log_block;
+    pub use crate::bytes_diff::log_bytes_diff;
+    pub use crate::caller::log_caller;
+    pub use crate::context::{push as push_context, ContextGuard};
+    pub use crate::emit::{enabled, finish_line, log, log_unfiltered, section, write_partial};
+    pub use crate::fields::log_fields;
+    #[cfg(feature = "async")]
+    pub use crate::future::log_poll_state;
+    pub use crate::group::GroupGuard;
+    #[cfg(feature = "json")]
+    pub use crate::json::log_json;
+    pub use crate::lines::log_lines;
+    #[cfg(feature = "mem")]
+    pub use crate::mem::log_mem;
+    pub use crate::once::first_occurrence;
+    pub use crate::record::Metadata;
+    pub use crate::retry::retry;
+    pub use crate::str_diff::log_str_diff;
+    pub use crate::test::TestGuard;
+    pub use crate::timing::{log_timed, Sampler};
+    pub use crate::todo::reached;
+    pub use crate::trace::{TraceDebug, TraceOpaque, TraceSpan, TraceValue};
+    pub use crate::utf8::log_utf8_lossy;
+    pub use crate::watchdog::heartbeat;
+    #[cfg(feature = "xml")]
+    pub use crate::xml::log_xml;
+
+    /// Whether the environment variable `name` is set to a non-empty value.
+    pub fn env_set(name: &str) -> bool {
+        std::env::var_os(name).is_some_and(|v| !v.is_empty())
+    }
+}
+
+/// Shared expansion of the logging macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __test_log_at {
+    ($level:expr, tags: [$($tag:expr),* $(,)?], $($arg:tt)+) => {
+        $crate::__test_log_at!(
+            @meta $crate::__test_metadata!($level).tags(&[$($tag),*]),
+            $($arg)+
+        )
+    };
+    (@meta $meta:expr, $($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $meta;
+            $crate::__private::log(&METADATA, ::core::format_args!($($arg)+));
+        }
+    };
+    ($level:expr, $($arg:tt)+) => {
+        $crate::__test_log_at!(@meta $crate::__test_metadata!($level), $($arg)+)
+    };
+}
+
+/// Call-site metadata for the invoking location.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __test_metadata {
+    ($level:expr) => {
+        $crate::__private::Metadata::new(
+            $level,
+            ::core::module_path!(),
+            ::core::file!(),
+            ::core::line!(),
+        )
+        .krate(::core::option_env!("CARGO_PKG_NAME"))
+    };
+}
 
 /// A macro that prints to stderr only during test execution for the current crate.
 ///
 /// This macro checks `cfg!(test)` at compile time to determine if the current crate
 /// is being compiled for testing. If so, it prints the formatted message to stderr
-/// using `eprintln!`. If not, the macro expands to nothing and has zero runtime cost.
+/// using `eprintln!`, prefixed with an `INFO` marker. If not, the macro expands to
+/// nothing and has zero runtime cost.
 ///
 /// # Key Behavior
 ///
@@ -50,11 +365,1065 @@
 ///
 /// The debug output will only appear when running `cargo test`, not when using
 /// the function in production code.
+///
+/// # Tags
+///
+/// `test_log!` and the level macros accept a leading `tags: [...]` list. Setting
+/// `TESTLOG_TAGS` then filters records by tag (see [`TagFilter`]), which
+/// suits cross-cutting concerns that module paths do not capture:
+///
+/// ```rust
+/// use testlog::{test_debug, test_log};
+///
+/// test_log!(tags: ["io", "slow"], "read {} bytes", 512);
+/// test_debug!(tags: ["net"], "connecting");
+/// ```
 #[macro_export]
 macro_rules! test_log {
+    () => {
+        $crate::test_log!("")
+    };
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Info, $($arg)+)
+    };
+}
+
+/// Like [`test_log!`], but always writes to stdout.
+///
+/// Useful when a harness treats stderr specially or when the output should
+/// interleave with `println!`-based diagnostics. To move *all* output to
+/// stdout instead, set `TESTLOG_STREAM=stdout` or use
+/// [`ConfigBuilder::stream`].
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_println;
+///
+/// println!("plain diagnostics");
+/// test_println!("interleaved with them: {}", 42);
+/// ```
+#[macro_export]
+macro_rules! test_println {
+    () => {
+        $crate::test_println!("")
+    };
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!(
+            @meta $crate::__test_metadata!($crate::Level::Info).stream($crate::Stream::Stdout),
+            $($arg)+
+        )
+    };
+}
+
+/// Logs the result of a closure, calling it only if the record would be shown.
+///
+/// The closure returns anything that implements `Display`. It is not called
+/// outside of tests, nor when the level is filtered out, so expensive
+/// formatting in hot loops costs nothing unless the output is wanted. The
+/// level defaults to [`Level::Info`].
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_log_lazy, Level};
+///
+/// let big = vec![0u8; 4096];
+/// test_log_lazy!(|| format!("{:?}", big));
+/// test_log_lazy!(Level::Trace, || format!("checksum {}", big.iter().map(|&b| b as u32).sum::<u32>()));
+/// ```
+#[macro_export]
+macro_rules! test_log_lazy {
+    ($level:expr, $f:expr) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            if $crate::__private::enabled(&METADATA) {
+                $crate::__private::log(&METADATA, ::core::format_args!("{}", ($f)()));
+            }
+        }
+    };
+    ($f:expr) => {
+        $crate::test_log_lazy!($crate::Level::Info, $f)
+    };
+}
+
+/// Runs a block and logs how many allocations it made, and how many bytes
+/// they requested, at [`Level::Info`].
+///
+/// Requires the `alloc-stats` feature. Only allocations made on the
+/// calling thread are counted. The macro evaluates to the value of the
+/// block; use [`alloc_stats`] directly to assert on the numbers.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_alloc_stats;
+///
+/// let names = test_alloc_stats!("building names", {
+///     (0..10).map(|i| format!("user{i}")).collect::<Vec<_>>()
+/// });
+/// // INFO  building names: 11 allocations (240 B), 0 frees
+/// # assert_eq!(names.len(), 10);
+/// ```
+#[cfg(feature = "alloc-stats")]
+#[macro_export]
+macro_rules! test_alloc_stats {
+    ($label:expr, $body:block) => {{
+        let _testlog_alloc = if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            ::core::option::Option::Some($crate::__private::AllocScope::begin(&METADATA, &$label))
+        } else {
+            ::core::option::Option::None
+        };
+        $body
+    }};
+}
+
+/// Logs a JSON document pretty-printed, with object keys sorted so the
+/// output is stable between runs.
+///
+/// Requires the `json` feature. Takes anything that is `AsRef<str>`, logged
+/// at [`Level::Info`] unless a level is given first. Strings and numbers are
+/// kept exactly as written. Input that is not valid JSON is logged as it is,
+/// after the parse error.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_log_pretty_json, Level};
+///
+/// let body = r#"{"status":"ok","items":[1,2],"count":2}"#;
+/// test_log_pretty_json!(body);
+/// // INFO  {
+/// //         "count": 2,
+/// //         "items": [
+/// //           1,
+/// //           2
+/// //         ],
+/// //         "status": "ok"
+/// //       }
+/// test_log_pretty_json!(Level::Debug, String::from("[true, null]"));
+/// ```
+#[cfg(feature = "json")]
+#[macro_export]
+macro_rules! test_log_pretty_json {
+    ($level:expr, $json:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_json(&METADATA, ::core::convert::AsRef::<str>::as_ref(&$json));
+        }
+    };
+    ($json:expr $(,)?) => {
+        $crate::test_log_pretty_json!($crate::Level::Info, $json)
+    };
+}
+
+/// Logs an XML or HTML document re-indented, one element per line.
+///
+/// Requires the `xml` feature. Takes anything that is `AsRef<str>`, logged
+/// at [`Level::Info`] unless a level is given first. Elements that only
+/// hold text stay on one line; tags and text are otherwise kept as written.
+/// Markup is never rejected, so broken documents are laid out as well as
+/// they can be.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_log_xml, Level};
+///
+/// let body = r#"<soap:Envelope><soap:Body><GetUser><Id>42</Id></GetUser></soap:Body></soap:Envelope>"#;
+/// test_log_xml!(body);
+/// // INFO  <soap:Envelope>
+/// //         <soap:Body>
+/// //           <GetUser>
+/// //             <Id>42</Id>
+/// //           </GetUser>
+/// //         </soap:Body>
+/// //       </soap:Envelope>
+/// test_log_xml!(Level::Debug, String::from("<p>hi<br>there</p>"));
+/// ```
+#[cfg(feature = "xml")]
+#[macro_export]
+macro_rules! test_log_xml {
+    ($level:expr, $xml:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_xml(&METADATA, ::core::convert::AsRef::<str>::as_ref(&$xml));
+        }
+    };
+    ($xml:expr $(,)?) => {
+        $crate::test_log_xml!($crate::Level::Info, $xml)
+    };
+}
+
+/// Compares two byte strings and logs where they differ as a hex dump.
+///
+/// Takes anything that is `AsRef<[u8]>`, logged at [`Level::Info`] unless
+/// a level is given first. Each row that differs is shown twice, expected
+/// (`-`) above actual (`+`), with the differing bytes marked and a row of
+/// context on either side; past the first eight runs of differences the
+/// rest are only counted. Equal inputs log one line saying so.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_bytes_diff;
+///
+/// let expected = b"\x01\x00\x05hello".to_vec();
+/// let actual = b"\x01\x00\x05jello".to_vec();
+/// test_log_bytes_diff!(expected, actual);
+/// // INFO  `expected` and `actual` differ in 1 byte (lengths 8 and 8)
+/// //       - 00000000  01 00 05 68 65 6c 6c 6f                           |...hello|
+/// //       + 00000000  01 00 05 6a 65 6c 6c 6f                           |...jello|
+/// //                            ^^
+/// ```
+#[macro_export]
+macro_rules! test_log_bytes_diff {
+    ($level:expr, $expected:expr, $actual:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_bytes_diff(
+                &METADATA,
+                (::core::stringify!($expected), ::core::stringify!($actual)),
+                ::core::convert::AsRef::<[u8]>::as_ref(&$expected),
+                ::core::convert::AsRef::<[u8]>::as_ref(&$actual),
+            );
+        }
+    };
+    ($expected:expr, $actual:expr $(,)?) => {
+        $crate::test_log_bytes_diff!($crate::Level::Info, $expected, $actual)
+    };
+}
+
+/// Compares two texts and logs a unified diff of them, with changes inside
+/// a line highlighted.
+///
+/// Takes anything that is `AsRef<str>`, logged at [`Level::Info`] unless a
+/// level is given first. Three unchanged lines are kept around each change.
+/// Where a removed line is followed by a similar added one, the characters
+/// that differ are bold and underlined when colors are on, or marked with
+/// `^` underneath when they are off. Equal texts log one line saying so.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_str_diff;
+///
+/// let expected = "name = \"demo\"\nversion = \"1.0.0\"";
+/// let actual = String::from("name = \"demo\"\nversion = \"1.1.0\"");
+/// test_log_str_diff!(expected, actual);
+/// // INFO  `expected` and `actual` differ:
+/// //         name = "demo"
+/// //       - version = "1.0.0"
+/// //                      ^
+/// //       + version = "1.1.0"
+/// //                      ^
+/// ```
+#[macro_export]
+macro_rules! test_log_str_diff {
+    ($level:expr, $expected:expr, $actual:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_str_diff(
+                &METADATA,
+                (::core::stringify!($expected), ::core::stringify!($actual)),
+                ::core::convert::AsRef::<str>::as_ref(&$expected),
+                ::core::convert::AsRef::<str>::as_ref(&$actual),
+            );
+        }
+    };
+    ($expected:expr, $actual:expr $(,)?) => {
+        $crate::test_log_str_diff!($crate::Level::Info, $expected, $actual)
+    };
+}
+
+/// Logs a byte string that may not be UTF-8, such as a parser input found
+/// by a fuzzer, without letting it garble the terminal.
+///
+/// Takes anything that is `AsRef<[u8]>`, logged at [`Level::Info`] unless
+/// a level is given first. The text is quoted, with control characters,
+/// terminal escapes and bidirectional overrides escaped as in `Debug`, and
+/// bytes that are not valid UTF-8 written as `\xNN`. The record ends with
+/// the length and whether the bytes were valid UTF-8; past 1 KiB the rest
+/// is only counted.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_utf8_lossy;
+///
+/// let input = b"GET /\xff\x1b[2J HTTP/1.1\r\n";
+/// test_log_utf8_lossy!(input);
+/// // INFO  `input` = "GET /\xff\u{1b}[2J HTTP/1.1\r\n" (21 bytes, invalid UTF-8: 1 bad byte, first at offset 5)
+/// ```
+#[macro_export]
+macro_rules! test_log_utf8_lossy {
+    ($level:expr, $bytes:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_utf8_lossy(
+                &METADATA,
+                ::core::stringify!($bytes),
+                ::core::convert::AsRef::<[u8]>::as_ref(&$bytes),
+            );
+        }
+    };
+    ($bytes:expr $(,)?) => {
+        $crate::test_log_utf8_lossy!($crate::Level::Info, $bytes)
+    };
+}
+
+/// Logs every item of an iterator on its own numbered line, written out
+/// in one go.
+///
+/// Takes anything that is `IntoIterator` with `Display` items, logged at
+/// [`Level::Info`] unless a level is given first. Each line carries the
+/// usual prefix, followed by the item's index from zero. Unlike a loop of
+/// [`test_log!`] calls the lines cannot be interleaved with output from
+/// other threads, and they are formatted and written at once. An empty
+/// iterator logs `(no items)`.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_log_lines, Level};
+///
+/// let routes = vec!["GET /", "GET /users", "POST /users"];
+/// test_log_lines!(&routes);
+/// // INFO  0: GET /
+/// // INFO  1: GET /users
+/// // INFO  2: POST /users
+/// test_log_lines!(Level::Debug, routes.iter().filter(|r| r.starts_with("GET")));
+/// ```
+#[macro_export]
+macro_rules! test_log_lines {
+    ($level:expr, $items:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_lines(&METADATA, $items);
+        }
+    };
+    ($items:expr $(,)?) => {
+        $crate::test_log_lines!($crate::Level::Info, $items)
+    };
+}
+
+/// Logs the result of polling a future by hand and evaluates to it:
+/// `Pending`, or `Ready` with the output's `Debug` form.
+///
+/// Requires the `async` feature. The poll expression itself is logged as
+/// the context, at [`Level::Info`]. With `wakes: counter`, a
+/// [`WakeCounter`], the number of wakeups so far is added, which shows
+/// whether a pending future arranged to be woken. Outputs without a
+/// `Debug` impl are shown as `…`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::future::Future;
+/// use std::task::Context;
+/// use testlog::{test_log_poll_state, WakeCounter};
+///
+/// let counter = WakeCounter::new();
+/// let waker = counter.waker();
+/// let mut cx = Context::from_waker(&waker);
+/// let mut fut = Box::pin(async { 42 });
+/// let poll = test_log_poll_state!(fut.as_mut().poll(&mut cx), wakes: counter);
+/// // INFO  `fut.as_mut().poll(&mut cx)` → Ready(42) (0 wakes)
+/// assert!(poll.is_ready());
+/// ```
+#[cfg(feature = "async")]
+#[macro_export]
+macro_rules! test_log_poll_state {
+    ($poll:expr, wakes: $wakes:expr $(,)?) => {
+        $crate::__test_log_poll_state!(
+            $poll,
+            ::core::option::Option::Some($crate::WakeCounter::wakes(&$wakes))
+        )
+    };
+    ($poll:expr $(,)?) => {
+        $crate::__test_log_poll_state!($poll, ::core::option::Option::None)
+    };
+}
+
+/// Shared expansion of `test_log_poll_state!`.
+#[cfg(feature = "async")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __test_log_poll_state {
+    ($poll:expr, $wakes:expr) => {{
+        let poll: ::core::task::Poll<_> = $poll;
+        if cfg!(test) {
+            #[allow(unused_imports)]
+            use $crate::__private::{TraceDebug as _, TraceOpaque as _};
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            if $crate::__private::enabled(&METADATA) {
+                let ready = match &poll {
+                    ::core::task::Poll::Ready(value) => ::core::option::Option::Some(
+                        (&$crate::__private::TraceValue(value)).describe(),
+                    ),
+                    ::core::task::Poll::Pending => ::core::option::Option::None,
+                };
+                $crate::__private::log_poll_state(
+                    &METADATA,
+                    ::core::stringify!($poll),
+                    ready,
+                    $wakes,
+                );
+            }
+        }
+        poll
+    }};
+}
+
+/// Logs the process's resident memory at [`Level::Info`]: the current RSS,
+/// its change since the previous checkpoint, and the peak.
+///
+/// Requires the `mem` feature. An optional label takes the same arguments
+/// as `format!`. The measurement covers the whole process, so parallel
+/// tests affect it. Use [`memory_usage`] to assert on the numbers.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_mem;
+///
+/// test_log_mem!();
+/// let cache = vec![0u8; 8 << 20];
+/// test_log_mem!("after filling cache");
+/// // INFO  after filling cache: rss 12.4 MiB (+8.1 MiB since last), peak 12.4 MiB
+/// # drop(cache);
+/// ```
+#[cfg(feature = "mem")]
+#[macro_export]
+macro_rules! test_log_mem {
+    () => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_mem(&METADATA, ::core::option::Option::None);
+        }
+    };
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_mem(
+                &METADATA,
+                ::core::option::Option::Some(::core::format_args!($($arg)+)),
+            );
+        }
+    };
+}
+
+/// Evaluates an expression, logs how long it took together with its source
+/// text, and returns its value.
+///
+/// Being an expression, it can wrap a single call in place. The record is
+/// logged at [`Level::Info`] unless a level is given first. Outside of
+/// tests the expression is evaluated without timing it.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_timed, Level};
+///
+/// let sorted = test_timed!({
+///     let mut v = vec![3, 1, 2];
+///     v.sort();
+///     v
+/// });
+/// let max = test_timed!(Level::Debug, sorted.iter().max());
+/// // INFO  `{ let mut v = vec![3, 1, 2]; v.sort(); v }` took 1.2µs
+/// # assert_eq!(max, Some(&3));
+/// ```
+#[macro_export]
+macro_rules! test_timed {
+    ($level:expr, $e:expr) => {{
+        let start = if cfg!(test) {
+            ::core::option::Option::Some((
+                ::std::time::Instant::now(),
+                $crate::__private::Sampler::start(),
+            ))
+        } else {
+            ::core::option::Option::None
+        };
+        let value = $e;
+        if let ::core::option::Option::Some((start, sampler)) = start {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::log_timed(
+                &METADATA,
+                ::core::stringify!($e),
+                start.elapsed(),
+                sampler,
+            );
+        }
+        value
+    }};
+    ($e:expr) => {
+        $crate::test_timed!($crate::Level::Info, $e)
+    };
+}
+
+/// Retries a fallible expression up to `attempts` times and returns the
+/// last [`Result`], logging every failed attempt's error (`Debug`) at
+/// [`Level::Warn`].
+///
+/// With `backoff: duration`, waits that long after the first failure and
+/// doubles the wait after each one that follows. The expression is
+/// evaluated in a closure, so `?` inside it applies to the attempt rather
+/// than to the enclosing function. Outside of tests it is retried the same
+/// way without logging.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use testlog::test_retry;
+///
+/// let mut port = 8079;
+/// let bound: Result<u16, String> = test_retry!(5, backoff: Duration::from_millis(1), {
+///     port += 1;
+///     if port < 8081 { Err(format!("port {port} in use")) } else { Ok(port) }
+/// });
+/// // WARN  `{ port += 1; ... }` attempt 1/5 failed: "port 8080 in use"; retrying in 1.0ms
+/// // INFO  `{ port += 1; ... }` succeeded on attempt 2/5
+/// # assert_eq!(bound, Ok(8081));
+/// ```
+#[macro_export]
+macro_rules! test_retry {
+    ($attempts:expr, backoff: $backoff:expr, $e:expr) => {{
+        static METADATA: $crate::__private::Metadata =
+            $crate::__test_metadata!($crate::Level::Warn);
+        $crate::__private::retry(
+            cfg!(test).then_some(&METADATA),
+            ::core::stringify!($e),
+            $attempts,
+            $backoff,
+            || $e,
+        )
+    }};
+    ($attempts:expr, $e:expr) => {
+        $crate::test_retry!($attempts, backoff: ::std::time::Duration::ZERO, $e)
+    };
+}
+
+/// Marks the calling thread as making progress for its
+/// [`watchdog`](fn@watchdog), remembering the call site and its backtrace for
+/// the stall report.
+///
+/// With arguments, also logs them like [`test_debug!`]. Does nothing on
+/// threads that are not watched, or outside of tests.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_heartbeat;
+///
+/// for attempt in 0..3 {
+///     test_heartbeat!("polling, attempt {attempt}");
+/// }
+/// ```
+#[macro_export]
+macro_rules! test_heartbeat {
+    () => {
+        if cfg!(test) {
+            $crate::__private::heartbeat();
+        }
+    };
+    ($($arg:tt)+) => {{
+        $crate::test_debug!($($arg)+);
+        $crate::test_heartbeat!();
+    }};
+}
+
+/// Logs like [`test_log!`], but only when `cond` is true.
+///
+/// The condition itself is only evaluated under test, so it may be as
+/// expensive as needed.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_if;
+///
+/// for attempt in 0..5 {
+///     test_log_if!(attempt > 2, "still retrying, attempt {}", attempt);
+/// }
+/// ```
+#[macro_export]
+macro_rules! test_log_if {
+    ($cond:expr, $($arg:tt)+) => {
+        if cfg!(test) && $cond {
+            $crate::test_log!($($arg)+);
+        }
+    };
+}
+
+/// Logs like [`test_log!`], but only when the environment variable `var` is
+/// set to a non-empty value.
+///
+/// This is independent of the global level: a record whose variable is set
+/// is shown even if `TESTLOG_LEVEL` would hide `INFO`. Use it for
+/// diagnostics that only matter in one debugging mode.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_if_env;
+///
+/// let frames = [1, 2, 3];
+/// test_log_if_env!("DUMP_FRAMES", "frames: {:?}", frames);
+/// ```
+#[macro_export]
+macro_rules! test_log_if_env {
+    ($var:expr, $($arg:tt)+) => {
+        if cfg!(test) && $crate::__private::env_set($var) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_unfiltered(&METADATA, ::core::format_args!($($arg)+));
+        }
+    };
+}
+
+/// Logs where the enclosing function was called from.
+///
+/// Annotate the enclosing function with `#[track_caller]`; the logged
+/// location is then its caller, which is what matters in utilities shared by
+/// many tests. Without the attribute the macro's own location is reported.
+/// An optional message in `format!` syntax is logged alongside.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_caller;
+///
+/// #[track_caller]
+/// fn fixture(name: &str) -> String {
+///     test_log_caller!("creating fixture {}", name);
+///     name.to_uppercase()
+/// }
+///
+/// fixture("users");
+/// ```
+///
+/// ```text
+/// INFO  creating fixture users (called from tests/db.rs:42:5)
+/// ```
+#[macro_export]
+macro_rules! test_log_caller {
+    () => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_caller(&METADATA, ::core::option::Option::None);
+        }
+    };
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_caller(
+                &METADATA,
+                ::core::option::Option::Some(::core::format_args!($($arg)+)),
+            );
+        }
+    };
+}
+
+/// Writes a file into the test's [`artifact_dir`] and logs its path.
+///
+/// `contents` is anything that implements `AsRef<[u8]>`, such as a `&str`,
+/// `String`, `Vec<u8>` or byte array; `name` may contain subdirectories but
+/// must stay inside the directory, so absolute paths and `..` are refused. A
+/// refused name or failed write is logged as an error rather than
+/// panicking. Nothing is written outside of tests.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_artifact;
+///
+/// let rendered: Vec<u8> = vec![0x89, b'P', b'N', b'G'];
+/// test_artifact!("render.png", &rendered);
+/// test_artifact!("state/after.json", r#"{"step": 3}"#);
+/// ```
+#[macro_export]
+macro_rules! test_artifact {
+    ($name:expr, $contents:expr) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::write_artifact(
+                &METADATA,
+                ::core::convert::AsRef::<str>::as_ref(&$name),
+                ::core::convert::AsRef::<[u8]>::as_ref(&$contents),
+            );
+        }
+    };
+}
+
+/// Appends to the current log line without ending it.
+///
+/// The first `test_write!` on a thread starts a line (with the usual `INFO`
+/// prefix); later calls append to it. The line is written out by
+/// [`test_writeln!`], or as soon as any other testlog output happens on the
+/// same thread. A line that is never finished is not printed.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_write, test_writeln};
+///
+/// test_write!("| {:>4} |", "n");
+/// for n in [1, 2, 3] {
+///     test_write!(" {n:>3} |");
+/// }
+/// test_writeln!();
+/// ```
+#[macro_export]
+macro_rules! test_write {
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::write_partial(&METADATA, ::core::format_args!($($arg)+));
+        }
+    };
+}
+
+/// Appends to the current log line started by [`test_write!`] and ends it.
+///
+/// With no arguments it simply finishes the line; without a pending line it
+/// behaves like [`test_log!`].
+#[macro_export]
+macro_rules! test_writeln {
+    () => {
+        $crate::test_writeln!("")
+    };
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::finish_line(&METADATA, ::core::format_args!($($arg)+));
+        }
+    };
+}
+
+/// Logs at [`Level::Error`]. Takes the same arguments as [`test_log!`].
+#[macro_export]
+macro_rules! test_error {
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Error, $($arg)+)
+    };
+}
+
+/// Logs at [`Level::Warn`]. Takes the same arguments as [`test_log!`].
+#[macro_export]
+macro_rules! test_warn {
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Warn, $($arg)+)
+    };
+}
+
+/// Logs at [`Level::Info`]; an alias of [`test_log!`].
+#[macro_export]
+macro_rules! test_info {
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Info, $($arg)+)
+    };
+}
+
+/// Logs at [`Level::Debug`]. Takes the same arguments as [`test_log!`].
+#[macro_export]
+macro_rules! test_debug {
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Debug, $($arg)+)
+    };
+}
+
+/// Logs at [`Level::Trace`]. Takes the same arguments as [`test_log!`].
+#[macro_export]
+macro_rules! test_trace {
+    ($($arg:tt)+) => {
+        $crate::__test_log_at!($crate::Level::Trace, $($arg)+)
+    };
+}
+
+/// Logs at [`Level::Warn`], but only the first time this call site is
+/// reached on the current thread.
+///
+/// Later occurrences are counted instead, so a deprecation-style warning
+/// inside a loop appears once. The count is logged when a
+/// [`#[testlog::test]`](macro@test) test ends; plain `#[test]` functions
+/// only show the first occurrence.
+/// Takes the same arguments as [`test_log!`].
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_warn_once;
+///
+/// for id in 0..1000 {
+///     test_warn_once!("lookup by id is deprecated (id {})", id);
+/// }
+/// // WARN  lookup by id is deprecated (id 0)
+/// // WARN  (warning above repeated 999 more times)
+/// ```
+#[macro_export]
+macro_rules! test_warn_once {
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Warn);
+            if $crate::__private::first_occurrence(&METADATA) {
+                $crate::__private::log(&METADATA, ::core::format_args!($($arg)+));
+            }
+        }
+    };
+}
+
+/// Wraps a block in a collapsible, visually delimited group.
+///
+/// Everything logged inside the block is indented and framed by begin/end
+/// markers. When running on GitHub Actions (`GITHUB_ACTIONS=true`) the
+/// outermost group is emitted as `::group::`/`::endgroup::` so the log viewer
+/// folds it. The block always runs; only the markers are test-gated. The
+/// macro evaluates to the value of the block.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_group, test_log};
+///
+/// let rows = test_group!("loading fixtures", {
+///     test_log!("reading users.csv");
+///     vec![1, 2, 3]
+/// });
+/// assert_eq!(rows.len(), 3);
+/// ```
+#[macro_export]
+macro_rules! test_group {
+    ($title:expr, $body:block) => {{
+        let _testlog_group = if cfg!(test) {
+            ::core::option::Option::Some($crate::__private::GroupGuard::begin(&$title))
+        } else {
+            ::core::option::Option::None
+        };
+        $body
+    }};
+}
+
+/// Runs a block with all testlog output on the thread discarded, including
+/// output from helpers it calls.
+///
+/// Useful for phases that are known to be noisy, such as bulk fixture
+/// loading. The block always runs and the macro evaluates to its value; see
+/// [`suppress`] for the guard it uses.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::{test_log, test_log_off};
+///
+/// let rows = test_log_off! {
+///     test_log!("inserting 10 000 rows");
+///     10_000
+/// };
+/// test_log!("loaded {} rows", rows);
+/// ```
+#[macro_export]
+macro_rules! test_log_off {
+    ($($body:tt)*) => {{
+        let _testlog_off = if cfg!(test) {
+            ::core::option::Option::Some($crate::suppress())
+        } else {
+            ::core::option::Option::None
+        };
+        $($body)*
+    }};
+}
+
+/// Prints multi-line text verbatim between begin and end markers.
+///
+/// Takes a title and any `Display` value, such as generated code or a
+/// config file. Lines are not reformatted: no level prefix, wrapping or
+/// hanging indent is applied, only the indentation of enclosing groups.
+/// By default each line has a `│ ` gutter; pass `border: false` to print
+/// the lines bare so the block can be copied out of the log. The block obeys
+/// the level filter at [`Level::Info`].
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_log_block;
+///
+/// let generated = "fn answer() -> u32 {\n    42\n}\n";
+/// test_log_block!("generated.rs", generated);
+/// // ┌──── generated.rs ─────────────────────────────────────────
+/// // │ fn answer() -> u32 {
+/// // │     42
+/// // │ }
+/// // └───────────────────────────────────────────────────────────
+/// test_log_block!("Cargo.toml", "[package]\nname = \"demo\"", border: false);
+/// ```
+#[macro_export]
+macro_rules! test_log_block {
+    ($title:expr, $content:expr, border: $border:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata =
+                $crate::__test_metadata!($crate::Level::Info);
+            $crate::__private::log_block(&METADATA, &$title, &$content, $border);
+        }
+    };
+    ($title:expr, $content:expr $(,)?) => {
+        $crate::test_log_block!($title, $content, border: true)
+    };
+}
+
+/// Marks a branch that is not implemented yet, logging a `▲` line with
+/// the call site when a test reaches it.
+///
+/// Unlike `todo!` it does not panic, so a test can run past the gap; write
+/// `test_todo!(panic)` or `test_todo!(panic, "...")` to panic as well. The
+/// message takes `format!` arguments. The line is logged at [`Level::Warn`]
+/// whatever the level filter, and the macro is compiled out of non-test
+/// builds.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_todo;
+///
+/// fn retry_delay(attempt: u32) -> u64 {
+///     if attempt > 3 {
+///         test_todo!("backoff for attempt {}", attempt);
+///     }
+///     100
+/// }
+/// // WARN  ▲ not yet implemented: backoff for attempt 4 (src/net.rs:4)
+/// # assert_eq!(retry_delay(4), 100);
+/// ```
+#[macro_export]
+macro_rules! test_todo {
+    (panic $(, $($arg:tt)+)?) => {
+        $crate::__test_reached!($crate::Level::Warn, "not yet implemented", true $(, $($arg)+)?)
+    };
     ($($arg:tt)*) => {
+        $crate::__test_reached!($crate::Level::Warn, "not yet implemented", false, $($arg)*)
+    };
+}
+
+/// Marks code that should be unreachable, logging a `▲` line with the call
+/// site at [`Level::Error`] when a test reaches it.
+///
+/// Takes the same forms as [`test_todo!`]: `test_unreachable!(panic, ...)`
+/// also panics, like `unreachable!`. Compiled out of non-test builds.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_unreachable;
+///
+/// match 2u8 % 2 {
+///     0 | 1 => {}
+///     n => test_unreachable!("remainder {}", n),
+/// }
+/// ```
+#[macro_export]
+macro_rules! test_unreachable {
+    (panic $(, $($arg:tt)+)?) => {
+        $crate::__test_reached!($crate::Level::Error, "entered unreachable code", true $(, $($arg)+)?)
+    };
+    ($($arg:tt)*) => {
+        $crate::__test_reached!($crate::Level::Error, "entered unreachable code", false, $($arg)*)
+    };
+}
+
+/// Shared expansion of `test_todo!` and `test_unreachable!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __test_reached {
+    ($level:expr, $what:expr, $panic:expr $(,)?) => {
+        if cfg!(test) {
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::reached(&METADATA, $what, ::core::option::Option::None, $panic);
+        }
+    };
+    ($level:expr, $what:expr, $panic:expr, $($arg:tt)+) => {
         if cfg!(test) {
-            eprintln!($($arg)*);
+            static METADATA: $crate::__private::Metadata = $crate::__test_metadata!($level);
+            $crate::__private::reached(
+                &METADATA,
+                $what,
+                ::core::option::Option::Some(::core::format_args!($($arg)+)),
+                $panic,
+            );
+        }
+    };
+}
+
+/// Describes what the test is doing for the rest of the enclosing scope.
+///
+/// Contexts stack up per thread. When the thread panics, the panic hook
+/// prints the stack, outermost first, just before the panic message, even
+/// if the panic comes from library code that logs nothing. Accepts the same
+/// arguments as `format!`; the entry is removed at the end of the scope.
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_context;
+///
+/// fn parse(input: &str) -> usize {
+///     test_context!("while parsing header of {} bytes", input.len());
+///     input.lines().count()
+/// }
+/// assert_eq!(parse("a\nb"), 2);
+/// ```
+#[macro_export]
+macro_rules! test_context {
+    ($($arg:tt)+) => {
+        let _testlog_context = if cfg!(test) {
+            ::core::option::Option::Some($crate::__private::push_context(
+                ::std::format!($($arg)+),
+            ))
+        } else {
+            ::core::option::Option::None
+        };
+    };
+}
+
+/// Prints a banner line that marks the start of a test phase.
+///
+/// The banner is padded with rules to the configured width (`TESTLOG_WIDTH`,
+/// 60 columns by default) and drawn in bold when colors are enabled. It
+/// accepts the same arguments as `format!`.
+///
+/// ```text
+/// ──── setup ─────────────────────────────────────────────────
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// use testlog::test_section;
+///
+/// test_section!("arrange");
+/// let input = vec![3, 1, 2];
+/// test_section!("act");
+/// let mut output = input.clone();
+/// output.sort();
+/// test_section!("assert: {} items", output.len());
+/// assert_eq!(output, [1, 2, 3]);
+/// ```
+#[macro_export]
+macro_rules! test_section {
+    ($($arg:tt)+) => {
+        if cfg!(test) {
+            $crate::__private::section(::core::format_args!($($arg)+));
         }
     };
 }
@@ -85,4 +1454,51 @@ mod tests {
         test_log!("About to panic - this helps debug the failure");
         panic!("Intentional panic to show test_log output");
     }
+
+    #[test]
+    fn level_macros_accept_format_args() {
+        let value = 7;
+        test_error!("error {}", value);
+        test_warn!("warn {value}");
+        test_info!("info");
+        test_debug!("debug {:?}", Some(value));
+        test_trace!("trace {}", "end");
+        assert!(Level::Error < Level::Trace);
+    }
+
+    #[test]
+    fn log_if_skips_arguments_when_false() {
+        let mut evaluated = 0;
+        let mut count = || {
+            evaluated += 1;
+            evaluated
+        };
+        test_log_if!(false, "never {}", count());
+        test_log_if!(1 < 2, "always {}", count());
+        assert_eq!(evaluated, 1);
+    }
+
+    #[test]
+    fn log_if_env_checks_the_variable() {
+        let mut evaluated = false;
+        test_log_if_env!("TESTLOG_SURELY_UNSET_VARIABLE", "{}", {
+            evaluated = true;
+            "hidden"
+        });
+        assert!(!evaluated);
+        test_log_if_env!("PATH", "PATH is set");
+    }
+
+    #[test]
+    fn tags_are_accepted_by_every_macro() {
+        test_log!(tags: ["io"], "tagged {}", 1);
+        test_warn!(tags: ["io", "slow",], "tagged");
+        test_trace!(tags: [], "no tags after all");
+    }
+
+    #[test]
+    fn println_writes_to_stdout() {
+        test_println!("to stdout: {}", 1);
+        test_println!();
+    }
 }
@@ -0,0 +1,54 @@
+//! Backend of [`test_log_lines!`](crate::test_log_lines).
+
+use crate::record::Metadata;
+use std::fmt::Display;
+
+/// Numbers `items` from zero, with the numbers right-aligned.
+fn number<T: Display>(items: impl IntoIterator<Item = T>) -> Vec<String> {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+    if items.is_empty() {
+        return vec!["(no items)".to_string()];
+    }
+    let width = (items.len() - 1).to_string().len();
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| format!("{i:>width$}: {item}"))
+        .collect()
+}
+
+/// Logs each item on its own line, all in one write.
+#[doc(hidden)]
+pub fn log_lines<T: Display>(meta: &Metadata, items: impl IntoIterator<Item = T>) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    let lines = number(items);
+    for line in &lines {
+        crate::strict::record(meta.level, format_args!("{line}"));
+    }
+    if !crate::emit::shown(meta) {
+        return;
+    }
+    crate::emit::record_lines(&crate::config::current(), meta, &lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_every_item() {
+        let lines = number((5..16).map(|n| n * n));
+        assert_eq!(lines[0], " 0: 25");
+        assert_eq!(lines[10], "10: 225");
+        assert_eq!(number(Vec::<u8>::new()), ["(no items)"]);
+    }
+
+    #[test]
+    fn lines_are_written_together() {
+        let _capture = crate::capture();
+        crate::test_log_lines!(crate::Level::Warn, ["a", "b"]);
+        assert_eq!(crate::captured_lines(), ["WARN  0: a\nWARN  1: b"]);
+    }
+}
@@ -0,0 +1,222 @@
+//! Process memory checkpoints behind the `mem` feature.
+
+use crate::record::Metadata;
+use crate::units;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Memory used by the whole process; see [`memory_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Current resident set size in bytes, where the platform reports it.
+    pub rss: Option<u64>,
+    /// Highest resident set size so far, in bytes.
+    pub peak: Option<u64>,
+}
+
+/// Reads the process's current and peak resident memory.
+///
+/// Linux reads `/proc/self/status` and Windows asks the process API; other
+/// Unix systems only report the peak.
+pub fn memory_usage() -> MemoryUsage {
+    sys::memory_usage()
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::MemoryUsage;
+
+    /// The value of a `Name:   1234 kB` line, in bytes.
+    pub(super) fn field(status: &str, name: &str) -> Option<u64> {
+        let line = status.lines().find(|l| l.starts_with(name))?;
+        let kib: u64 = line[name.len()..]
+            .trim_start_matches(':')
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(kib * 1024)
+    }
+
+    pub(super) fn memory_usage() -> MemoryUsage {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        MemoryUsage {
+            rss: field(&status, "VmRSS"),
+            peak: field(&status, "VmHWM"),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod sys {
+    use super::MemoryUsage;
+    use std::os::raw::{c_int, c_long};
+
+    /// `struct rusage`: two `timeval`s followed by fourteen `long`s.
+    #[repr(C)]
+    struct Rusage {
+        times: [[c_long; 2]; 2],
+        maxrss: c_long,
+        rest: [c_long; 13],
+    }
+
+    extern "C" {
+        fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+    }
+
+    pub(super) fn memory_usage() -> MemoryUsage {
+        const RUSAGE_SELF: c_int = 0;
+        // SAFETY: `Rusage` matches the C layout and zero is a valid value
+        // for each of its fields.
+        let mut usage: Rusage = unsafe { std::mem::zeroed() };
+        // SAFETY: `usage` is a valid out-pointer for the duration of the call.
+        if unsafe { getrusage(RUSAGE_SELF, &mut usage) } != 0 {
+            return MemoryUsage::default();
+        }
+        // macOS reports bytes, the BSDs KiB.
+        let scale = if cfg!(target_vendor = "apple") {
+            1
+        } else {
+            1024
+        };
+        MemoryUsage {
+            rss: None,
+            peak: Some(usage.maxrss as u64 * scale),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::MemoryUsage;
+    use std::os::raw::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn K32GetProcessMemoryInfo(
+            process: *mut c_void,
+            counters: *mut ProcessMemoryCounters,
+            size: u32,
+        ) -> i32;
+    }
+
+    pub(super) fn memory_usage() -> MemoryUsage {
+        let size = std::mem::size_of::<ProcessMemoryCounters>() as u32;
+        let mut counters = ProcessMemoryCounters {
+            cb: size,
+            ..Default::default()
+        };
+        // SAFETY: the pseudo-handle needs no cleanup and `counters` is a
+        // valid out-pointer of the size passed.
+        if unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) } == 0 {
+            return MemoryUsage::default();
+        }
+        MemoryUsage {
+            rss: Some(counters.working_set_size as u64),
+            peak: Some(counters.peak_working_set_size as u64),
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use super::MemoryUsage;
+
+    pub(super) fn memory_usage() -> MemoryUsage {
+        MemoryUsage::default()
+    }
+}
+
+/// RSS at the previous checkpoint, zero before the first one.
+static LAST_RSS: AtomicU64 = AtomicU64::new(0);
+
+/// The checkpoint message for `usage`; `last` is the previous RSS.
+fn describe(label: Option<&str>, usage: MemoryUsage, last: u64) -> String {
+    let mut out = String::from(label.unwrap_or("memory"));
+    out.push(':');
+    match usage.rss {
+        Some(rss) => {
+            out.push_str(&format!(" rss {}", units::bytes(rss)));
+            if last > 0 {
+                let (sign, delta) = if rss >= last {
+                    ('+', rss - last)
+                } else {
+                    ('-', last - rss)
+                };
+                out.push_str(&format!(" ({sign}{} since last)", units::bytes(delta)));
+            }
+        }
+        None if usage.peak.is_none() => out.push_str(" unavailable on this platform"),
+        None => {}
+    }
+    if let Some(peak) = usage.peak {
+        out.push_str(&format!(", peak {}", units::bytes(peak)));
+    }
+    out
+}
+
+/// Backend of `test_log_mem!`.
+#[doc(hidden)]
+pub fn log_mem(meta: &Metadata, label: Option<std::fmt::Arguments>) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    let usage = memory_usage();
+    let last = usage
+        .rss
+        .map_or(0, |rss| LAST_RSS.swap(rss, Ordering::Relaxed));
+    let label = label.map(|l| l.to_string());
+    crate::emit::log(
+        meta,
+        format_args!("{}", describe(label.as_deref(), usage, last)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_rss_change_and_peak() {
+        let usage = MemoryUsage {
+            rss: Some(3 * 1024 * 1024),
+            peak: Some(4 * 1024 * 1024),
+        };
+        assert_eq!(
+            describe(Some("loaded"), usage, 2 * 1024 * 1024),
+            "loaded: rss 3.0 MiB (+1.0 MiB since last), peak 4.0 MiB"
+        );
+        assert_eq!(
+            describe(None, MemoryUsage::default(), 0),
+            "memory: unavailable on this platform"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_proc_status() {
+        assert_eq!(
+            sys::field("VmRSS:\t  2048 kB\n", "VmRSS"),
+            Some(2048 * 1024)
+        );
+        let usage = memory_usage();
+        assert!(usage.rss.is_some_and(|rss| rss > 0));
+        assert!(usage.peak >= usage.rss);
+    }
+}
@@ -0,0 +1,101 @@
+//! Per-call-site deduplication for [`test_warn_once!`](crate::test_warn_once).
+//!
+//! The first record from a call site on a thread is emitted and later ones
+//! are only counted. When a [`#[testlog::test]`](macro@crate::test) test
+//! ends, each call site that was repeated logs how many records it held
+//! back. Nothing is logged when the thread exits: by then libtest has
+//! stopped capturing, so the counts would land on the terminal of a test
+//! that passed.
+
+use crate::record::Metadata;
+use std::cell::RefCell;
+
+/// Call sites seen on this thread, with the number of suppressed repeats.
+#[derive(Default)]
+struct Seen(Vec<(&'static Metadata, usize)>);
+
+impl Seen {
+    /// Records an occurrence; returns whether it is the first.
+    fn first(&mut self, meta: &'static Metadata) -> bool {
+        match self.0.iter_mut().find(|(m, _)| std::ptr::eq(*m, meta)) {
+            Some((_, suppressed)) => {
+                *suppressed += 1;
+                false
+            }
+            None => {
+                self.0.push((meta, 0));
+                true
+            }
+        }
+    }
+
+    fn report(&mut self) {
+        for (meta, suppressed) in self.0.drain(..) {
+            if suppressed > 0 {
+                crate::emit::log(
+                    meta,
+                    format_args!("(warning above repeated {suppressed} more times)"),
+                );
+            }
+        }
+    }
+}
+
+thread_local! {
+    static SEEN: RefCell<Seen> = RefCell::new(Seen::default());
+}
+
+/// Backend of `test_warn_once!`: whether this is the first occurrence of
+/// `meta` on the thread.
+#[doc(hidden)]
+pub fn first_occurrence(meta: &'static Metadata) -> bool {
+    SEEN.try_with(|seen| seen.borrow_mut().first(meta))
+        .unwrap_or(false)
+}
+
+/// Logs the suppressed counts and forgets the call sites; called when a
+/// test ends.
+pub(crate) fn report() {
+    let _ = SEEN.try_with(|seen| seen.borrow_mut().report());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_first_occurrence_is_logged() {
+        let _capture = crate::capture();
+        for i in 0..5 {
+            crate::test_warn_once!("deprecated call #{}", i);
+        }
+        report();
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("deprecated call #0"));
+        assert!(lines[1].ends_with("(warning above repeated 4 more times)"));
+    }
+
+    #[test]
+    #[ignore = "run in a child process by `plain_tests_print_nothing_after_returning`"]
+    fn repeated_warning_in_a_plain_test() {
+        for _ in 0..5 {
+            crate::test_warn_once!("repeated warning");
+        }
+    }
+
+    #[test]
+    fn plain_tests_print_nothing_after_returning() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--ignored",
+                "--exact",
+                "once::tests::repeated_warning_in_a_plain_test",
+            ])
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{stderr}");
+        assert!(!stderr.contains("repeated"), "{stderr}");
+    }
+}
@@ -0,0 +1,233 @@
+//! OpenTelemetry export of test spans over OTLP/HTTP.
+//!
+//! With the `otel` feature and an endpoint set through `TESTLOG_OTLP_ENDPOINT`
+//! or [`ConfigBuilder::otlp_endpoint`](crate::ConfigBuilder::otlp_endpoint),
+//! the spans that [`chrome`](crate::chrome) describes are sent to an
+//! OpenTelemetry collector when the process exits, so in-test events line up
+//! with service traces in Jaeger or similar. Each test thread becomes one
+//! trace, its spans carry the test's name as `test.name`, and section banners
+//! become span events.
+//!
+//! The export is a single JSON-encoded OTLP request made with `std` alone,
+//! so only plain `http://` endpoints are supported.
+
+use crate::chrome::escape;
+use crate::spans::{Kind, Record};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io::{Read as _, Write as _};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends `records` to `endpoint`; runs from an exit hook and reports
+/// failures on stderr.
+pub(crate) fn export(endpoint: &str, records: &[Record]) {
+    if !records.iter().any(|r| r.kind != Kind::Thread) {
+        return;
+    }
+    let start = crate::spans::epoch().1;
+    let seed = std::collections::hash_map::RandomState::new().hash_one((std::process::id(), start));
+    let body = render(records, start, seed, &crate::spans::binary_name());
+    if let Err(err) = post(endpoint, &body) {
+        let _ = writeln!(
+            std::io::stderr(),
+            "testlog: OTLP export to {endpoint} failed: {err}"
+        );
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos())
+}
+
+fn string_attr(key: &str, value: &str) -> String {
+    format!(
+        "{{\"key\":\"{key}\",\"value\":{{\"stringValue\":\"{}\"}}}}",
+        escape(value)
+    )
+}
+
+/// Renders an `ExportTraceServiceRequest` in OTLP's JSON encoding.
+fn render(records: &[Record], start: SystemTime, seed: u64, service: &str) -> String {
+    let at = |offset: Duration| unix_nanos(start + offset);
+    let tests: HashMap<u64, &str> = records
+        .iter()
+        .filter(|r| r.kind == Kind::Thread)
+        .map(|r| (r.tid, r.name.as_str()))
+        .collect();
+    let mut events: HashMap<u64, Vec<String>> = HashMap::new();
+    for record in records
+        .iter()
+        .filter(|r| r.kind == Kind::Instant && r.parent != 0)
+    {
+        events.entry(record.parent).or_default().push(format!(
+            "{{\"timeUnixNano\":\"{}\",\"name\":\"{}\"}}",
+            at(record.offset),
+            escape(&record.name)
+        ));
+    }
+    let spans: Vec<String> = records
+        .iter()
+        // Instants outside any span become zero-length spans.
+        .filter(|r| r.kind == Kind::Span || r.kind == Kind::Instant && r.parent == 0)
+        .map(|record| {
+            let test = tests.get(&record.tid).copied().unwrap_or("<unnamed>");
+            let mut span = format!(
+                "{{\"traceId\":\"{seed:016x}{:016x}\",\"spanId\":\"{:016x}\",",
+                record.tid,
+                seed ^ record.id
+            );
+            if record.parent != 0 {
+                span.push_str(&format!("\"parentSpanId\":\"{:016x}\",", seed ^ record.parent));
+            }
+            span.push_str(&format!(
+                "\"name\":\"{}\",\"kind\":1,\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\
+                 \"attributes\":[{},{}]",
+                escape(&record.name),
+                at(record.offset),
+                at(record.offset + record.dur),
+                string_attr("test.name", test),
+                string_attr("testlog.category", record.cat),
+            ));
+            if let Some(events) = events.get(&record.id) {
+                span.push_str(&format!(",\"events\":[{}]", events.join(",")));
+            }
+            span.push('}');
+            span
+        })
+        .collect();
+    format!(
+        "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[{}]}},\
+         \"scopeSpans\":[{{\"scope\":{{\"name\":\"testlog\",\"version\":\"{}\"}},\
+         \"spans\":[{}]}}]}}]}}",
+        string_attr("service.name", service),
+        env!("CARGO_PKG_VERSION"),
+        spans.join(",")
+    )
+}
+
+/// Where an endpoint points: the `host:port` to connect to, the authority
+/// as written and the path. The port defaults to OTLP/HTTP's 4318 and the
+/// path to `/v1/traces`.
+fn parse_endpoint(endpoint: &str) -> Result<(String, &str, &str), String> {
+    let rest = endpoint
+        .strip_prefix("http://")
+        .ok_or("only http:// endpoints are supported")?;
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.len() <= 1 { "/v1/traces" } else { path };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:4318")
+    };
+    Ok((addr, authority, path))
+}
+
+/// POSTs `body` to the traces path of `endpoint`, `/v1/traces` unless the
+/// endpoint names a path itself.
+fn post(endpoint: &str, body: &str) -> Result<(), String> {
+    let (addr, authority, path) = parse_endpoint(endpoint)?;
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("endpoint does not resolve")?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| e.to_string())?;
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .map_err(|e| e.to_string())?;
+    let mut head = [0u8; 64];
+    let n = stream.read(&mut head).map_err(|e| e.to_string())?;
+    let status = String::from_utf8_lossy(&head[..n]);
+    let status = status.lines().next().unwrap_or_default();
+    match status.split(' ').nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("collector answered `{status}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn record(kind: Kind, name: &str, id: u64, parent: u64, at_us: u64) -> Record {
+        Record {
+            kind,
+            cat: "group",
+            name: name.into(),
+            tid: 2,
+            id,
+            parent,
+            offset: Duration::from_micros(at_us),
+            dur: Duration::from_micros(5),
+        }
+    }
+
+    #[test]
+    fn renders_spans_with_parents_and_events() {
+        let records = [
+            record(Kind::Thread, "tests::login", 0, 0, 0),
+            record(Kind::Span, "tests::login", 1, 0, 0),
+            record(Kind::Instant, "assert", 3, 1, 3),
+            record(Kind::Span, "load", 2, 1, 1),
+        ];
+        let json = render(&records, UNIX_EPOCH + Duration::from_secs(1), 0x10, "it");
+        assert!(json.contains(
+            "{\"traceId\":\"00000000000000100000000000000002\",\"spanId\":\"0000000000000012\",\
+             \"parentSpanId\":\"0000000000000011\",\"name\":\"load\",\"kind\":1,\
+             \"startTimeUnixNano\":\"1000001000\",\"endTimeUnixNano\":\"1000006000\""
+        ), "{json}");
+        assert!(json.contains("\"events\":[{\"timeUnixNano\":\"1000003000\",\"name\":\"assert\"}]"));
+        assert!(
+            json.contains("{\"key\":\"test.name\",\"value\":{\"stringValue\":\"tests::login\"}}")
+        );
+        assert!(json.contains("{\"key\":\"service.name\",\"value\":{\"stringValue\":\"it\"}}"));
+    }
+
+    #[test]
+    fn endpoints_default_to_the_otlp_port() {
+        assert_eq!(
+            parse_endpoint("http://host/v1/traces"),
+            Ok(("host:4318".to_string(), "host", "/v1/traces"))
+        );
+        assert_eq!(
+            parse_endpoint("http://host:8080/"),
+            Ok(("host:8080".to_string(), "host:8080", "/v1/traces"))
+        );
+    }
+
+    #[test]
+    fn posts_to_the_traces_path() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"{}") {
+                let n = conn.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            conn.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        post(&endpoint, "{}").unwrap();
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("POST /v1/traces HTTP/1.1\r\n"),
+            "{request}"
+        );
+        assert!(request.contains("Content-Type: application/json\r\nContent-Length: 2\r\n"));
+        assert!(post("https://collector:4318", "{}").is_err());
+    }
+}
@@ -0,0 +1,504 @@
+//! Output streams.
+
+use crate::level::Level;
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Standard stream that records are written to.
+///
+/// Both streams go through `print!`/`eprint!`, so libtest's output capture
+/// applies either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stream {
+    #[default]
+    Stderr,
+    Stdout,
+}
+
+impl FromStr for Stream {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stderr" => Ok(Stream::Stderr),
+            "stdout" => Ok(Stream::Stdout),
+            other => Err(format!("unknown stream `{other}`")),
+        }
+    }
+}
+
+/// Destination for all output, overriding the per-record [`Stream`].
+///
+/// Set with `TESTLOG_OUTPUT=stderr`, `stdout` or `file:/path/to/log`, or
+/// with [`ConfigBuilder::output`](crate::ConfigBuilder::output).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Stderr,
+    Stdout,
+    /// Appends to a file, bypassing libtest's capture.
+    File(PathBuf),
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("file:") {
+            Some("") => Err("`file:` needs a path".to_string()),
+            Some(path) => Ok(Output::File(PathBuf::from(path))),
+            None => match s.parse()? {
+                Stream::Stderr => Ok(Output::Stderr),
+                Stream::Stdout => Ok(Output::Stdout),
+            },
+        }
+    }
+}
+
+/// One destination among those in `TESTLOG_OUTPUT`, receiving records up
+/// to its own level, such as `stderr@warn` or `file:target/test.log@trace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sink {
+    pub(crate) output: Output,
+    /// Most verbose level written here; `None` takes all that pass the
+    /// global filters.
+    pub(crate) level: Option<Level>,
+}
+
+impl Sink {
+    /// Whether records at `level` go to this sink; output that is not a
+    /// record, such as section banners, always does.
+    fn accepts(&self, level: Option<Level>) -> bool {
+        match (level, self.level) {
+            (Some(level), Some(max)) => level <= max,
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for Sink {
+    type Err = String;
+
+    /// Parses an [`Output`] optionally followed by `@level`. An `@` that is
+    /// not followed by a level is taken as part of a file name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((output, level)) = s.rsplit_once('@') {
+            if let Ok(level) = level.parse() {
+                return Ok(Sink {
+                    output: output.parse()?,
+                    level: Some(level),
+                });
+            }
+        }
+        Ok(Sink {
+            output: s.parse()?,
+            level: None,
+        })
+    }
+}
+
+/// Parses a comma-separated list of [`Sink`]s.
+pub(crate) fn parse_sinks(s: &str) -> Result<Vec<Sink>, String> {
+    s.split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+impl Output {
+    /// The stream records end up on, or `None` for a file.
+    pub(crate) fn stream(&self) -> Option<Stream> {
+        match self {
+            Output::Stderr => Some(Stream::Stderr),
+            Output::Stdout => Some(Stream::Stdout),
+            Output::File(_) => None,
+        }
+    }
+}
+
+/// The log file currently written to, kept open between lines.
+static FILE: Mutex<Option<(PathBuf, BufWriter<File>)>> = Mutex::new(None);
+
+/// Appends `text` to the file at `path`; returns false if it cannot be
+/// opened or written.
+///
+/// The line is written through at once unless a flush interval is
+/// configured, in which case it may wait in the buffer for up to that long.
+fn write_file(path: &PathBuf, text: &str) -> bool {
+    let buffered = crate::config::current()
+        .flush_interval
+        .is_some_and(flusher_running);
+    append(path, text, buffered)
+}
+
+fn append(path: &PathBuf, text: &str, buffered: bool) -> bool {
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if file.as_ref().is_none_or(|(open, _)| open != path) {
+        let opened = File::options().create(true).append(true).open(path);
+        *file = opened.ok().map(|f| (path.clone(), BufWriter::new(f)));
+    }
+    match file.as_mut() {
+        Some((_, f)) => writeln!(f, "{text}").is_ok() && (buffered || f.flush().is_ok()),
+        None => false,
+    }
+}
+
+/// Writes out whatever the log file's buffer holds.
+pub(crate) fn flush() {
+    if let Some((_, f)) = FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        let _ = f.flush();
+    }
+}
+
+/// Starts the thread that flushes the log file every `interval`, and the
+/// exit hook that flushes it one last time, since statics are never
+/// dropped. A process that is killed loses at most one interval of output.
+///
+/// Returns whether the thread is running; lines are written through at
+/// once if it could not be started.
+fn flusher_running(interval: Duration) -> bool {
+    static RUNNING: OnceLock<bool> = OnceLock::new();
+    *RUNNING.get_or_init(|| {
+        let spawned = std::thread::Builder::new()
+            .name("testlog-flush".into())
+            .spawn(move || loop {
+                let every = crate::config::current().flush_interval;
+                std::thread::sleep(every.unwrap_or(interval));
+                flush();
+            });
+        match spawned {
+            Ok(_) => {
+                extern "C" fn at_exit() {
+                    flush();
+                }
+                crate::exit::at_exit(at_exit);
+                true
+            }
+            Err(err) => {
+                eprintln!("testlog: cannot start the flush thread: {err}");
+                false
+            }
+        }
+    })
+}
+
+/// What happens to output for a standard stream that can no longer be
+/// written, such as stdout piped into `head` under `--nocapture`.
+///
+/// Set with `TESTLOG_BROKEN_PIPE=drop` (the default) or an [`Output`]
+/// such as `file:/tmp/rest.log`, or with
+/// [`ConfigBuilder::on_broken_pipe`](crate::ConfigBuilder::on_broken_pipe).
+/// Either way the test carries on; the logging macros never panic over a
+/// failed write.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BrokenPipe {
+    /// Output for the stream is discarded.
+    #[default]
+    Drop,
+    /// Output for the stream goes to another output instead.
+    Fallback(Output),
+}
+
+impl FromStr for BrokenPipe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "drop" | "ignore" | "" => Ok(BrokenPipe::Drop),
+            _ => s.parse().map(BrokenPipe::Fallback),
+        }
+    }
+}
+
+/// Standard streams found to be unwritable, which are not tried again.
+#[derive(Default)]
+struct Broken {
+    stderr: AtomicBool,
+    stdout: AtomicBool,
+}
+
+impl Broken {
+    fn flag(&self, stream: Stream) -> &AtomicBool {
+        match stream {
+            Stream::Stderr => &self.stderr,
+            Stream::Stdout => &self.stdout,
+        }
+    }
+}
+
+static BROKEN: Broken = Broken {
+    stderr: AtomicBool::new(false),
+    stdout: AtomicBool::new(false),
+};
+
+/// Whether libtest is capturing output, which it only does through
+/// `print!` and `eprint!`. Captured output goes to memory, where writes
+/// cannot fail.
+fn libtest_captures() -> bool {
+    static CAPTURES: OnceLock<bool> = OnceLock::new();
+    *CAPTURES.get_or_init(|| {
+        let nocapture = std::env::var("RUST_TEST_NOCAPTURE").is_ok_and(|v| v != "0")
+            || std::env::args().any(|arg| arg == "--nocapture" || arg == "--no-capture");
+        !nocapture
+    })
+}
+
+fn write_fd(stream: Stream, text: &str) -> std::io::Result<()> {
+    match stream {
+        Stream::Stderr => writeln!(std::io::stderr().lock(), "{text}"),
+        Stream::Stdout => {
+            let mut out = std::io::stdout().lock();
+            writeln!(out, "{text}").and_then(|()| out.flush())
+        }
+    }
+}
+
+/// Writes to `stream` through `write` unless it is already known to be
+/// broken, and applies `policy` if it is.
+fn write_or_fallback(
+    broken: &Broken,
+    policy: &BrokenPipe,
+    stream: Stream,
+    text: &str,
+    write: &mut dyn FnMut(Stream, &str) -> std::io::Result<()>,
+) {
+    if !broken.flag(stream).load(Ordering::Relaxed) {
+        if write(stream, text).is_ok() {
+            return;
+        }
+        broken.flag(stream).store(true, Ordering::Relaxed);
+    }
+    match policy {
+        BrokenPipe::Drop => {}
+        BrokenPipe::Fallback(Output::File(path)) => {
+            let _ = write_file(path, text);
+        }
+        BrokenPipe::Fallback(output) => {
+            let other = output.stream().unwrap_or(stream);
+            if other != stream
+                && !broken.flag(other).load(Ordering::Relaxed)
+                && write(other, text).is_err()
+            {
+                broken.flag(other).store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Writes through `eprintln!`/`println!`, which libtest captures. The
+/// macros panic if the thread turns out not to be captured and the stream
+/// is closed, as after a test has returned or in an exit hook; the panic is
+/// caught and handed back as an error.
+///
+/// From the panic hook, where a second panic would abort the process, the
+/// stream is written directly instead.
+fn write_captured(stream: Stream, text: &str) -> std::io::Result<()> {
+    if std::thread::panicking() {
+        return write_fd(stream, text);
+    }
+    let print = || match stream {
+        Stream::Stderr => eprintln!("{text}"),
+        Stream::Stdout => println!("{text}"),
+    };
+    crate::panic::quietly(|| std::panic::catch_unwind(print))
+        .map_err(|_| std::io::Error::other("failed printing"))
+}
+
+fn write_stream(stream: Stream, text: &str) {
+    let policy = &crate::config::current().broken_pipe;
+    if libtest_captures() {
+        write_or_fallback(&BROKEN, policy, stream, text, &mut write_captured);
+    } else {
+        write_or_fallback(&BROKEN, policy, stream, text, &mut write_fd);
+    }
+}
+
+/// Writes `text` and a newline to `stream`, or to every configured
+/// [`Output`] if there are any.
+pub(crate) fn write_line(stream: Stream, text: &str) {
+    write_record(stream, None, text);
+}
+
+/// Like [`write_line`], for a record at `level`: outputs configured with
+/// a less verbose level of their own skip it.
+pub(crate) fn write_record(stream: Stream, level: Option<Level>, text: &str) {
+    let config = crate::config::current();
+    if config.outputs.is_empty() {
+        return write_stream(stream, text);
+    }
+    for sink in config.outputs.iter().filter(|sink| sink.accepts(level)) {
+        match &sink.output {
+            Output::File(path) => {
+                if !write_file(path, text) {
+                    write_stream(Stream::Stderr, text);
+                }
+            }
+            output => write_stream(output.stream().unwrap_or(stream), text),
+        }
+    }
+}
+
+/// Writes `text` and a newline to the configured files, or else straight
+/// to the process's stderr, past libtest's capture. For reports that must
+/// be seen even if the test never finishes.
+pub(crate) fn write_uncaptured(text: &str) {
+    let mut written = false;
+    for sink in &crate::config::current().outputs {
+        if let Output::File(path) = &sink.output {
+            written |= write_file(path, text);
+        }
+    }
+    if written {
+        flush();
+    } else {
+        let _ = writeln!(std::io::stderr().lock(), "{text}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_streams() {
+        assert_eq!("STDOUT".parse(), Ok(Stream::Stdout));
+        assert_eq!("stderr".parse(), Ok(Stream::Stderr));
+        assert!("file".parse::<Stream>().is_err());
+    }
+
+    #[test]
+    fn parses_outputs() {
+        assert_eq!("stdout".parse(), Ok(Output::Stdout));
+        assert_eq!(
+            "file:/tmp/test.log".parse(),
+            Ok(Output::File(PathBuf::from("/tmp/test.log")))
+        );
+        assert!("file:".parse::<Output>().is_err());
+        assert!("syslog".parse::<Output>().is_err());
+    }
+
+    #[test]
+    fn broken_streams_are_dropped_or_redirected() {
+        let mut written = Vec::new();
+        let mut write = |stream: Stream, text: &str| {
+            if stream == Stream::Stdout {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            written.push(text.to_string());
+            Ok(())
+        };
+        let broken = Broken::default();
+        write_or_fallback(
+            &broken,
+            &BrokenPipe::Drop,
+            Stream::Stdout,
+            "lost",
+            &mut write,
+        );
+        let policy = "stderr".parse().unwrap();
+        write_or_fallback(&broken, &policy, Stream::Stdout, "kept", &mut write);
+        write_or_fallback(&broken, &policy, Stream::Stderr, "direct", &mut write);
+        assert!(broken.stdout.load(Ordering::Relaxed));
+        assert_eq!(written, ["kept", "direct"]);
+        assert_eq!("drop".parse(), Ok(BrokenPipe::Drop));
+        assert!("syslog".parse::<BrokenPipe>().is_err());
+    }
+
+    #[test]
+    fn parses_sinks_with_levels() {
+        let sinks = parse_sinks("stderr@warn, file:logs/a@b.log,file:t.log@TRACE").unwrap();
+        assert_eq!(
+            sinks,
+            [
+                Sink {
+                    output: Output::Stderr,
+                    level: Some(Level::Warn)
+                },
+                Sink {
+                    output: Output::File(PathBuf::from("logs/a@b.log")),
+                    level: None
+                },
+                Sink {
+                    output: Output::File(PathBuf::from("t.log")),
+                    level: Some(Level::Trace)
+                },
+            ]
+        );
+        assert!(!sinks[0].accepts(Some(Level::Info)));
+        assert!(sinks[0].accepts(Some(Level::Error)) && sinks[0].accepts(None));
+        assert!(parse_sinks("stderr@warn,syslog").is_err());
+    }
+
+    #[test]
+    fn file_output_appends_lines() {
+        let path = std::env::temp_dir().join(format!("testlog-output-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(append(&path, "one", false));
+        assert!(append(&path, "two", false));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        assert!(append(&path, "three", true));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        flush();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    #[ignore = "run in a child process by `closed_streams_never_panic`"]
+    fn write_after_the_test_returns() {
+        struct LateWrite;
+        impl Drop for LateWrite {
+            fn drop(&mut self) {
+                for _ in 0..3 {
+                    write_line(Stream::Stderr, "written after the test returned");
+                }
+            }
+        }
+        thread_local! {
+            static LATE: LateWrite = const { LateWrite };
+        }
+        LATE.with(|_| {});
+    }
+
+    #[test]
+    #[ignore = "run in a child process by `closed_streams_never_panic`"]
+    fn panic_after_the_test_returns() {
+        struct LatePanic;
+        impl Drop for LatePanic {
+            fn drop(&mut self) {
+                let _ = std::panic::catch_unwind(|| {
+                    let _capture = crate::capture();
+                    crate::test_log!("replayed by the panic hook");
+                    panic!("intentional failure");
+                });
+            }
+        }
+        thread_local! {
+            static LATE: LatePanic = const { LatePanic };
+        }
+        LATE.with(|_| {});
+    }
+
+    #[test]
+    fn closed_streams_never_panic() {
+        for test in [
+            "write_after_the_test_returns",
+            "panic_after_the_test_returns",
+        ] {
+            let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--ignored", "--exact", &format!("output::tests::{test}")])
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            drop(child.stderr.take());
+            assert!(child.wait().unwrap().success(), "{test}");
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Panic hook used to learn whether the current test failed.
+//!
+//! The hook is installed on first use, records that the panicking thread
+//! failed, prints the thread's captured output and its
+//! [`test_context!`](crate::test_context) stack and then defers to whatever
+//! hook was installed before it.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+static ANY_PANICKED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static PANICKED: Cell<bool> = const { Cell::new(false) };
+    /// Set while [`quietly`] runs, for panics the hook should pass over.
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs the testlog panic hook once per process.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if QUIET.try_with(Cell::get).unwrap_or(false) {
+                return;
+            }
+            let _ = PANICKED.try_with(|p| p.set(true));
+            ANY_PANICKED.store(true, Ordering::Relaxed);
+            crate::capture::on_panic();
+            if let Some(context) = crate::context::render() {
+                crate::output::write_line(crate::output::Stream::Stderr, &context);
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Runs `f` with the hook ignoring the calling thread's panics: they are
+/// neither printed nor count as the test failing. For panics `f` catches
+/// itself.
+pub(crate) fn quietly<T>(f: impl FnOnce() -> T) -> T {
+    install();
+    let outer = QUIET.try_with(|quiet| quiet.replace(true)).unwrap_or(false);
+    let result = f();
+    let _ = QUIET.try_with(|quiet| quiet.set(outer));
+    result
+}
+
+/// Whether the calling thread has panicked since the hook was installed.
+///
+/// Unlike [`std::thread::panicking`] this stays true after unwinding has
+/// finished, so it can be consulted from thread-local destructors that run
+/// when a test thread exits.
+pub(crate) fn thread_panicked() -> bool {
+    PANICKED.try_with(Cell::get).unwrap_or(false)
+}
+
+/// Whether any thread has panicked since the hook was installed.
+pub(crate) fn any_panicked() -> bool {
+    ANY_PANICKED.load(Ordering::Relaxed)
+}
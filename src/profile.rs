@@ -0,0 +1,233 @@
+//! Sampling of [`test_timed!`](crate::test_timed) expressions, behind the
+//! `perf` feature.
+//!
+//! With `TESTLOG_PROFILE` set to an interval, a timed expression records
+//! where its thread is at most once per interval, and the record of its
+//! time ends with a summary of the places the samples were taken.
+//!
+//! The standard library cannot interrupt a thread to read its stack, so a
+//! sample is only taken when the thread reaches testlog: when it logs
+//! something, or at [`test_heartbeat!`](crate::test_heartbeat). Work that
+//! does neither shows up at the next place that does; a heartbeat inside a
+//! hot loop sharpens the picture.
+
+use crate::units::HumanDuration;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Places listed in a summary before the rest are counted together.
+const TOP: usize = 5;
+
+/// Frames that belong to testlog or the standard library, which are
+/// skipped to find the place a sample was taken.
+const INTERNAL: [&str; 9] = [
+    "std::",
+    "core::",
+    "alloc::",
+    "testlog::",
+    "<std::",
+    "<core::",
+    "<alloc::",
+    "__rust",
+    "rust_begin_unwind",
+];
+
+struct Profile {
+    interval: Duration,
+    next: Instant,
+    samples: Vec<String>,
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// Samples the calling thread while a timed expression runs; started by
+/// `test_timed!` and handed back to its backend.
+#[doc(hidden)]
+pub struct Sampler {
+    started: bool,
+    /// The profile of an enclosing timed expression, put back at the end.
+    outer: Option<Profile>,
+    // Samples are taken on the thread that started them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Sampler {
+    /// Starts sampling if `TESTLOG_PROFILE` is set.
+    pub fn start() -> Self {
+        Sampler::every(crate::config::current().profile)
+    }
+
+    fn every(interval: Option<Duration>) -> Self {
+        let mut sampler = Sampler {
+            started: false,
+            outer: None,
+            _not_send: PhantomData,
+        };
+        let Some(interval) = interval else {
+            return sampler;
+        };
+        let profile = Profile {
+            interval,
+            next: Instant::now() + interval,
+            samples: Vec::new(),
+        };
+        if let Ok(outer) = ACTIVE.try_with(|active| active.replace(Some(profile))) {
+            sampler.started = true;
+            sampler.outer = outer;
+        }
+        sampler
+    }
+
+    /// Stops sampling and summarizes the samples, if any were taken.
+    pub(crate) fn finish(mut self) -> Option<String> {
+        let profile = self.stop()?;
+        summary(&profile.samples, profile.interval)
+    }
+
+    fn stop(&mut self) -> Option<Profile> {
+        if !std::mem::take(&mut self.started) {
+            return None;
+        }
+        let outer = self.outer.take();
+        ACTIVE
+            .try_with(|active| active.replace(outer))
+            .ok()
+            .flatten()
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Takes a sample if the calling thread is being profiled and one is due.
+pub(crate) fn sample_point() {
+    let _ = ACTIVE.try_with(|active| {
+        let Ok(mut active) = active.try_borrow_mut() else {
+            return;
+        };
+        let Some(profile) = active.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        if now < profile.next {
+            return;
+        }
+        profile.next = now + profile.interval;
+        let backtrace = Backtrace::force_capture().to_string();
+        profile.samples.push(place(&backtrace));
+    });
+}
+
+/// The innermost frame of a printed backtrace outside of testlog and the
+/// standard library, such as `app::parse at src/parse.rs:10:5`.
+fn place(backtrace: &str) -> String {
+    let mut lines = backtrace.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        let Some((index, symbol)) = line.split_once(": ") else {
+            continue;
+        };
+        if index.parse::<usize>().is_err() || INTERNAL.iter().any(|p| symbol.starts_with(p)) {
+            continue;
+        }
+        return match lines.peek().and_then(|l| l.strip_prefix("at ")) {
+            Some(path) => format!("{symbol} at {}", path.trim_start_matches("./")),
+            None => symbol.to_string(),
+        };
+    }
+    "(unknown)".to_string()
+}
+
+fn summary(samples: &[String], interval: Duration) -> Option<String> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for sample in samples {
+        match counts.iter_mut().find(|(place, _)| place == sample) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((sample, 1)),
+        }
+    }
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    let total = samples.len();
+    let percent = |count: usize| count * 100 / total;
+    let s = if total == 1 { "" } else { "s" };
+    let mut out = format!(
+        "where time went ({total} sample{s}, one per {} at most):",
+        HumanDuration(interval)
+    );
+    for &(place, count) in counts.iter().take(TOP) {
+        out.push_str(&format!("\n{:>4}%  {place}", percent(count)));
+    }
+    if counts.len() > TOP {
+        let rest = &counts[TOP..];
+        let count = rest.iter().map(|(_, count)| count).sum();
+        let s = if rest.len() == 1 { "" } else { "s" };
+        out.push_str(&format!(
+            "\n{:>4}%  ({} other place{s})",
+            percent(count),
+            rest.len()
+        ));
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_frame_outside_testlog() {
+        let backtrace = "   0: std::backtrace::Backtrace::force_capture\n             \
+                         at /rustc/abc/library/std/src/backtrace.rs:312:13\n   \
+                         1: testlog::profile::sample_point\n             \
+                         at ./src/profile.rs:120:25\n   \
+                         2: app::parse::tokens\n             \
+                         at ./src/parse.rs:10:5\n   \
+                         3: app::parse\n";
+        assert_eq!(place(backtrace), "app::parse::tokens at src/parse.rs:10:5");
+        assert_eq!(place("   0: std::rt::lang_start\n"), "(unknown)");
+    }
+
+    #[test]
+    fn summarizes_the_most_sampled_places() {
+        let mut samples = vec!["parse".to_string(); 6];
+        samples.extend(["load", "load", "a", "b", "c", "d"].map(String::from));
+        assert_eq!(
+            summary(&samples, Duration::from_millis(10)).unwrap(),
+            "where time went (12 samples, one per 10.0ms at most):\n  \
+             50%  parse\n  \
+             16%  load\n   \
+             8%  a\n   \
+             8%  b\n   \
+             8%  c\n   \
+             8%  (1 other place)"
+        );
+        assert_eq!(summary(&[], Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn samples_are_taken_where_the_thread_logs() {
+        let _capture = crate::capture();
+        let sampler = Sampler::every(Some(Duration::ZERO));
+        for i in 0..3 {
+            let inner = Sampler::every(None);
+            crate::test_trace!("step {i}");
+            assert_eq!(inner.finish(), None);
+        }
+        let summary = sampler.finish().unwrap();
+        assert!(
+            summary.starts_with("where time went (3 samples"),
+            "{summary}"
+        );
+        crate::test_trace!("after");
+        assert!(ACTIVE.with(|active| active.borrow().is_none()));
+    }
+}
@@ -0,0 +1,60 @@
+//! Call-site metadata captured by the logging macros.
+
+use crate::level::Level;
+use crate::output::Stream;
+
+/// Static information about a logging call site.
+///
+/// The macros build one of these in a `static` per call site, so passing it
+/// around costs a pointer.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    pub level: Level,
+    pub target: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// Stream forced by the call site, overriding the configured one.
+    pub stream: Option<Stream>,
+    /// Free-form tags attached with `tags: [...]`.
+    pub tags: &'static [&'static str],
+    /// Cargo package of the call site, when it was built by Cargo.
+    pub krate: Option<&'static str>,
+}
+
+impl Metadata {
+    pub const fn new(level: Level, target: &'static str, file: &'static str, line: u32) -> Self {
+        Metadata {
+            level,
+            target,
+            file,
+            line,
+            stream: None,
+            tags: &[],
+            krate: None,
+        }
+    }
+
+    pub const fn stream(mut self, stream: Stream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    pub const fn tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Takes `option_env!("CARGO_PKG_NAME")` as expanded in the calling crate.
+    pub const fn krate(mut self, name: Option<&'static str>) -> Self {
+        self.krate = name;
+        self
+    }
+
+    /// Name of the emitting crate: its Cargo package, or else the first
+    /// segment of the module path.
+    pub fn crate_name(&self) -> &'static str {
+        self.krate
+            .unwrap_or_else(|| self.target.split("::").next().unwrap_or(self.target))
+    }
+}
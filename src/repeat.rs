@@ -0,0 +1,129 @@
+//! Collapsing of consecutive identical records, enabled with
+//! [`ConfigBuilder::collapse_repeats`](crate::ConfigBuilder::collapse_repeats).
+//!
+//! A record identical to the previous one on the thread, from the same call
+//! site, is counted instead of printed. The count is printed as a single
+//! note once a different record arrives, or when a
+//! [`#[testlog::test]`](macro@crate::test) test ends. It is not printed when
+//! the thread exits, since libtest has stopped capturing by then.
+
+use crate::record::Metadata;
+use std::cell::RefCell;
+
+struct Last {
+    meta: Metadata,
+    msg: String,
+    repeats: usize,
+}
+
+impl Last {
+    fn same(&self, meta: &Metadata, msg: &str) -> bool {
+        self.msg == msg
+            && self.meta.level == meta.level
+            && self.meta.file == meta.file
+            && self.meta.line == meta.line
+    }
+
+    fn report(self) {
+        if self.repeats > 0 {
+            crate::emit::note(
+                &self.meta,
+                &format!("(previous message repeated {} more times)", self.repeats),
+            );
+        }
+    }
+}
+
+thread_local! {
+    static LAST: RefCell<Option<Last>> = const { RefCell::new(None) };
+}
+
+/// Whether `msg` repeats the previous record, in which case it is counted
+/// and should not be printed. Otherwise it becomes the record that later
+/// ones are compared against.
+pub(crate) fn is_repeat(meta: &Metadata, msg: &str) -> bool {
+    let previous = LAST.try_with(|last| {
+        let mut last = last.borrow_mut();
+        match last.as_mut() {
+            Some(last) if last.same(meta, msg) => {
+                last.repeats += 1;
+                None
+            }
+            _ => Some(last.replace(Last {
+                meta: *meta,
+                msg: msg.to_string(),
+                repeats: 0,
+            })),
+        }
+    });
+    match previous {
+        Ok(None) => true,
+        Ok(Some(previous)) => {
+            if let Some(previous) = previous {
+                previous.report();
+            }
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Prints the pending repeat count, if any, and forgets the last record.
+pub(crate) fn flush() {
+    let last = LAST
+        .try_with(|last| last.borrow_mut().take())
+        .ok()
+        .flatten();
+    if let Some(last) = last {
+        last.report();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::record::Metadata;
+    use crate::Level;
+
+    static RETRY: Metadata = Metadata::new(Level::Warn, "app", "src/app.rs", 3);
+
+    #[test]
+    fn consecutive_repeats_collapse_into_a_note() {
+        let config = Config::builder().collapse_repeats(true).build();
+        let _capture = crate::capture();
+        for _ in 0..4 {
+            crate::emit::record(&config, &RETRY, format_args!("connection refused"));
+        }
+        crate::emit::record(&config, &RETRY, format_args!("connected"));
+        super::flush();
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("connection refused"));
+        assert!(lines[1].ends_with("(previous message repeated 3 more times)"));
+        assert!(lines[2].ends_with("connected"));
+    }
+
+    #[test]
+    #[ignore = "run in a child process by `plain_tests_print_nothing_after_returning`"]
+    fn repeated_record_in_a_plain_test() {
+        for _ in 0..3 {
+            crate::test_warn!("connection refused");
+        }
+    }
+
+    #[test]
+    fn plain_tests_print_nothing_after_returning() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--ignored",
+                "--exact",
+                "repeat::tests::repeated_record_in_a_plain_test",
+            ])
+            .env("TESTLOG_COLLAPSE", "1")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{stderr}");
+        assert!(!stderr.contains("repeated"), "{stderr}");
+    }
+}
@@ -0,0 +1,107 @@
+//! Retry loop behind [`test_retry!`](crate::test_retry).
+
+use crate::level::Level;
+use crate::record::Metadata;
+use crate::units::HumanDuration;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Runs `attempt` until it succeeds or `attempts` tries have failed,
+/// sleeping `backoff` after the first failure and twice as long after each
+/// one that follows. Failures are logged through `meta` when it is given.
+#[doc(hidden)]
+pub fn retry<T, E: Debug>(
+    meta: Option<&Metadata>,
+    what: &str,
+    attempts: u32,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+    for n in 1.. {
+        let err = match attempt() {
+            Ok(value) => {
+                if let (Some(meta), true) = (meta, n > 1) {
+                    let meta = Metadata {
+                        level: Level::Info,
+                        ..*meta
+                    };
+                    crate::emit::log(
+                        &meta,
+                        format_args!("`{what}` succeeded on attempt {n}/{attempts}"),
+                    );
+                }
+                return Ok(value);
+            }
+            Err(err) => err,
+        };
+        if n == attempts {
+            if let Some(meta) = meta {
+                let meta = Metadata {
+                    level: Level::Error,
+                    ..*meta
+                };
+                crate::emit::log(
+                    &meta,
+                    format_args!("`{what}` failed {attempts} times, giving up: {err:?}"),
+                );
+            }
+            return Err(err);
+        }
+        if let Some(meta) = meta {
+            if delay.is_zero() {
+                crate::emit::log(
+                    meta,
+                    format_args!("`{what}` attempt {n}/{attempts} failed: {err:?}"),
+                );
+            } else {
+                crate::emit::log(
+                    meta,
+                    format_args!(
+                        "`{what}` attempt {n}/{attempts} failed: {err:?}; retrying in {}",
+                        HumanDuration(delay)
+                    ),
+                );
+            }
+        }
+        std::thread::sleep(delay);
+        delay = delay.saturating_mul(2);
+    }
+    unreachable!("the last attempt returns")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    #[test]
+    fn logs_each_failed_attempt() {
+        let _capture = crate::capture();
+        let mut calls = 0;
+        let result: Result<u32, String> = crate::test_retry!(3, {
+            calls += 1;
+            if calls < 3 {
+                Err(format!("refused #{calls}"))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        assert!(lines[0].contains("attempt 1/3 failed: \"refused #1\""));
+        assert!(lines[2].contains("succeeded on attempt 3/3"));
+    }
+
+    #[test]
+    fn returns_the_last_error_after_backing_off() {
+        let _capture = crate::capture();
+        let result: Result<(), u8> =
+            crate::test_retry!(2, backoff: Duration::from_millis(1), Err(7));
+        assert_eq!(result, Err(7));
+        let lines = crate::captured_lines();
+        assert!(lines[0].ends_with("attempt 1/2 failed: 7; retrying in 1.0ms"));
+        assert!(lines[1].contains("`Err(7)` failed 2 times, giving up: 7"));
+    }
+}
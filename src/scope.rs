@@ -0,0 +1,94 @@
+//! Per-thread name scopes pushed by [`push_scope`].
+//!
+//! While a scope is open every line the thread logs carries its name right
+//! before the message, so output from shared fixture code can be told
+//! apart from the tests that use it. Nested scopes are joined with `›`.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+thread_local! {
+    static STACK: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Guard returned by [`push_scope`]; closes the scope when dropped.
+#[must_use = "the scope is closed when the guard is dropped"]
+#[derive(Debug)]
+pub struct ScopeGuard {
+    /// Entries below this one, which are all that remain after the drop.
+    depth: usize,
+    // Scopes belong to the thread that pushed them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        crate::emit::flush_pending();
+        let _ = STACK.try_with(|stack| stack.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Prefixes every line the calling thread logs with `name` until the
+/// returned guard is dropped, for setup and teardown code shared by many
+/// tests.
+///
+/// ```rust
+/// fn start_postgres() {
+///     let _scope = testlog::push_scope("fixture: postgres");
+///     testlog::test_log!("starting container");
+///     // INFO  [fixture: postgres] starting container
+/// }
+/// start_postgres();
+/// testlog::test_log!("test body");
+/// // INFO  test body
+/// ```
+pub fn push_scope(name: impl Into<String>) -> ScopeGuard {
+    crate::emit::flush_pending();
+    let depth = STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        stack.push(name.into());
+        stack.len() - 1
+    });
+    ScopeGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// The open scopes as written in front of messages, such as
+/// `[fixture: postgres › migrations]`, if there are any.
+pub(crate) fn current() -> Option<String> {
+    STACK
+        .try_with(|stack| {
+            let stack = stack.try_borrow().ok()?;
+            (!stack.is_empty()).then(|| format!("[{}]", stack.join(" › ")))
+        })
+        .ok()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_prefix_messages_until_dropped() {
+        let _capture = crate::capture();
+        let outer = push_scope("fixture: postgres");
+        crate::test_log!("starting");
+        {
+            let _inner = push_scope("migrations");
+            crate::test_warn!("slow");
+        }
+        drop(outer);
+        crate::test_log!("test body");
+        assert_eq!(
+            crate::captured_lines(),
+            [
+                "INFO  [fixture: postgres] starting",
+                "WARN  [fixture: postgres › migrations] slow",
+                "INFO  test body",
+            ]
+        );
+    }
+}
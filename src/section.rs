@@ -0,0 +1,65 @@
+//! Phase banners printed by [`test_section!`](crate::test_section).
+
+use crate::color;
+use crate::config::Config;
+
+const RULE: char = '─';
+const LEAD: usize = 4;
+
+/// Renders `──── title ──────…` padded with rules to `width` columns.
+///
+/// The trailing rule is never shorter than the leading one, so very long
+/// titles overflow `width` rather than losing their frame.
+pub(crate) fn banner(title: &str, width: usize) -> String {
+    let lead: String = std::iter::repeat_n(RULE, LEAD).collect();
+    if title.is_empty() {
+        return std::iter::repeat_n(RULE, width.max(LEAD)).collect();
+    }
+    let used = LEAD + crate::width::str_width(title) + 2;
+    let tail: String = std::iter::repeat_n(RULE, width.saturating_sub(used).max(LEAD)).collect();
+    format!("{lead} {title} {tail}")
+}
+
+/// Formats a banner for the given config at the given group depth.
+pub(crate) fn render(config: &Config, title: &str, depth: usize) -> String {
+    let width = config
+        .width
+        .saturating_sub(depth * crate::group::INDENT.len());
+    color::paint(
+        &banner(title, width),
+        &config.theme.heading(),
+        config.color_enabled(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorChoice;
+
+    #[test]
+    fn pads_to_width() {
+        let line = banner("setup", 20);
+        assert_eq!(line, "──── setup ─────────");
+        assert_eq!(line.chars().count(), 20);
+    }
+
+    #[test]
+    fn long_titles_keep_trailing_rule() {
+        assert_eq!(banner("a long title", 10), "──── a long title ────");
+    }
+
+    #[test]
+    fn untitled_is_a_plain_rule() {
+        assert_eq!(banner("", 6), "──────");
+    }
+
+    #[test]
+    fn nested_banners_shrink() {
+        let config = Config::builder()
+            .width(20)
+            .color(ColorChoice::Never)
+            .build();
+        assert_eq!(render(&config, "act", 1).chars().count(), 18);
+    }
+}
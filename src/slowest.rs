@@ -0,0 +1,112 @@
+//! Table of the slowest tests, printed at exit when `TESTLOG_SLOWEST` is
+//! set.
+//!
+//! [`#[testlog::test]`](macro@crate::test) reports each test's wall time
+//! as it ends, which stable `cargo test` has no way to show.
+
+use crate::units::HumanDuration;
+use std::io::Write as _;
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+struct Timing {
+    name: String,
+    elapsed: Duration,
+    failed: bool,
+}
+
+static TIMINGS: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+/// Adds a finished test to the report, if there is one.
+pub(crate) fn record(name: &str, elapsed: Duration, failed: bool) {
+    if crate::config::current().slowest.is_none() {
+        return;
+    }
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| crate::exit::at_exit(report));
+    TIMINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Timing {
+            name: name.to_string(),
+            elapsed,
+            failed,
+        });
+}
+
+/// The slowest `rows` of `timings`, as printed at exit.
+fn render(timings: &mut [Timing], rows: usize, width: usize) -> String {
+    timings.sort_by(|a, b| b.elapsed.cmp(&a.elapsed).then_with(|| a.name.cmp(&b.name)));
+    let total: Duration = timings.iter().map(|t| t.elapsed).sum();
+    let shown = &timings[..rows.min(timings.len())];
+    let title = if shown.len() == timings.len() {
+        format!(
+            "{} tests by time ({} in total)",
+            timings.len(),
+            HumanDuration(total)
+        )
+    } else {
+        format!(
+            "slowest {} of {} tests ({} in total)",
+            shown.len(),
+            timings.len(),
+            HumanDuration(total)
+        )
+    };
+    let mut out = crate::section::banner(&title, width);
+    let times: Vec<String> = shown
+        .iter()
+        .map(|t| HumanDuration(t.elapsed).to_string())
+        .collect();
+    let pad = times.iter().map(|t| t.chars().count()).max().unwrap_or(0);
+    for (timing, time) in shown.iter().zip(&times) {
+        out.push_str(&format!("\n{time:>pad$}  {}", timing.name));
+        if timing.failed {
+            out.push_str(" (failed)");
+        }
+    }
+    out
+}
+
+/// Exit hook: prints the table straight to stderr, ignoring write errors
+/// because nothing may unwind out of here.
+extern "C" fn report() {
+    let config = crate::config::current();
+    let Some(rows) = config.slowest else {
+        return;
+    };
+    let mut timings = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    if !timings.is_empty() {
+        let table = render(&mut timings, rows, config.width);
+        let _ = writeln!(std::io::stderr(), "{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name: &str, millis: u64, failed: bool) -> Timing {
+        Timing {
+            name: name.to_string(),
+            elapsed: Duration::from_millis(millis),
+            failed,
+        }
+    }
+
+    #[test]
+    fn lists_the_slowest_first() {
+        let mut timings = vec![
+            timing("parse::empty", 2, false),
+            timing("import::large", 2_100, false),
+            timing("net::timeout", 850, true),
+        ];
+        assert_eq!(
+            render(&mut timings, 2, 40),
+            "──── slowest 2 of 3 tests (3.0s in total) ────\n\
+             \u{20}2.1s  import::large\n\
+             850ms  net::timeout (failed)"
+        );
+        assert!(render(&mut timings, usize::MAX, 40).starts_with("──── 3 tests by time"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Recording of groups, traced calls, timers, tests and sections as spans,
+//! for the exporters in [`chrome`](crate::chrome) and, with the `otel`
+//! feature, `otel`.
+//!
+//! Nothing is recorded unless an exporter is configured. Records are kept
+//! in memory and handed to the exporters when the process exits.
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// Something with a duration.
+    Span,
+    /// A point in time, such as a section banner.
+    Instant,
+    /// Names a thread's track after the test running on it.
+    Thread,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Record {
+    pub(crate) kind: Kind,
+    pub(crate) cat: &'static str,
+    pub(crate) name: String,
+    pub(crate) tid: u64,
+    /// Unique within the process; 0 for thread names. Ids and parents are
+    /// only needed by the OpenTelemetry exporter.
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    pub(crate) id: u64,
+    /// The enclosing span on the same thread, or 0.
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    pub(crate) parent: u64,
+    /// Time since the first record.
+    pub(crate) offset: Duration,
+    pub(crate) dur: Duration,
+}
+
+/// A span that has started; see [`start`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpanStart {
+    at: Instant,
+    id: u64,
+    parent: u64,
+}
+
+static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static TID: Cell<u64> = const { Cell::new(0) };
+    static OPEN: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// When the first record was made, both as an `Instant` and as wall-clock
+/// time, which exporters need for absolute timestamps.
+pub(crate) fn epoch() -> (Instant, SystemTime) {
+    static EPOCH: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+    *EPOCH.get_or_init(|| (Instant::now(), SystemTime::now()))
+}
+
+/// Whether any exporter is configured.
+fn recording() -> bool {
+    let config = crate::config::current();
+    config.chrome_trace.is_some() || cfg!(feature = "otel") && config.otlp_endpoint.is_some()
+}
+
+fn top() -> u64 {
+    OPEN.try_with(|o| o.borrow().last().copied().unwrap_or(0))
+        .unwrap_or(0)
+}
+
+/// Opens a span on the calling thread, or returns `None` when nothing is
+/// being recorded.
+pub(crate) fn start() -> Option<SpanStart> {
+    if !recording() {
+        return None;
+    }
+    epoch();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let parent = top();
+    let _ = OPEN.try_with(|o| o.borrow_mut().push(id));
+    Some(SpanStart {
+        at: Instant::now(),
+        id,
+        parent,
+    })
+}
+
+/// Closes a span opened by [`start`].
+pub(crate) fn end(cat: &'static str, name: &str, start: Option<SpanStart>) {
+    if let Some(start) = start {
+        let _ = OPEN.try_with(|o| {
+            let mut o = o.borrow_mut();
+            if let Some(i) = o.iter().rposition(|&id| id == start.id) {
+                o.remove(i);
+            }
+        });
+        push(
+            Kind::Span,
+            cat,
+            name,
+            start.id,
+            start.parent,
+            start.at,
+            start.at.elapsed(),
+        );
+    }
+}
+
+/// Records a span that took `elapsed` and ended just now.
+pub(crate) fn elapsed(cat: &'static str, name: &str, elapsed: Duration) {
+    if recording() {
+        let at = Instant::now()
+            .checked_sub(elapsed)
+            .unwrap_or_else(Instant::now);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        push(Kind::Span, cat, name, id, top(), at, elapsed);
+    }
+}
+
+/// Records a point in time inside the current span.
+pub(crate) fn instant(cat: &'static str, name: &str) {
+    if recording() {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        push(
+            Kind::Instant,
+            cat,
+            name,
+            id,
+            top(),
+            Instant::now(),
+            Duration::ZERO,
+        );
+    }
+}
+
+fn push(
+    kind: Kind,
+    cat: &'static str,
+    name: &str,
+    id: u64,
+    parent: u64,
+    at: Instant,
+    dur: Duration,
+) {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| crate::exit::at_exit(export));
+    let offset = at.saturating_duration_since(epoch().0);
+    let mut records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    let tid = thread_id(&mut records);
+    records.push(Record {
+        kind,
+        cat,
+        name: name.to_string(),
+        tid,
+        id,
+        parent,
+        offset,
+        dur,
+    });
+}
+
+/// The calling thread's track, named when it is first used.
+fn thread_id(records: &mut Vec<Record>) -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    let tid = TID.try_with(Cell::get).unwrap_or(0);
+    if tid != 0 {
+        return tid;
+    }
+    let tid = NEXT.fetch_add(1, Ordering::Relaxed);
+    let _ = TID.try_with(|t| t.set(tid));
+    let thread = std::thread::current();
+    let name = crate::test::current()
+        .or(thread.name())
+        .unwrap_or("<unnamed>");
+    records.push(Record {
+        kind: Kind::Thread,
+        cat: "",
+        name: name.to_string(),
+        tid,
+        id: 0,
+        parent: 0,
+        offset: Duration::ZERO,
+        dur: Duration::ZERO,
+    });
+    tid
+}
+
+/// Exit hook: hands the records to every configured exporter.
+extern "C" fn export() {
+    let config = crate::config::current();
+    let records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(path) = &config.chrome_trace {
+        crate::chrome::write(path, &records);
+    }
+    #[cfg(feature = "otel")]
+    if let Some(endpoint) = &config.otlp_endpoint {
+        crate::otel::export(endpoint, &records);
+    }
+}
+
+/// Name of the test binary, for file names and service names.
+pub(crate) fn binary_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "testlog".to_string())
+}
@@ -0,0 +1,198 @@
+//! Unified text diffs for [`test_log_str_diff!`](crate::test_log_str_diff).
+//!
+//! Lines are aligned as in [`diff`](crate::diff). A removed line followed
+//! by a similar added one is then diffed again character by character, and
+//! the characters that changed are highlighted: bold and underlined when
+//! colors are on, marked with `^` on the line below otherwise.
+
+use crate::color;
+use crate::diff::{self, Op};
+use crate::record::Metadata;
+use crate::theme::{Color, Style};
+
+/// Unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+const REMOVED: Style = Style::new().fg(Color::Red);
+const ADDED: Style = Style::new().fg(Color::Green);
+
+/// A line's characters, flagged `true` where they changed.
+type Parts<'a> = Vec<(bool, &'a str)>;
+
+/// The characters of `line`, in the form [`diff::diff`] takes.
+fn chars(line: &str) -> Vec<&str> {
+    line.char_indices()
+        .map(|(i, c)| &line[i..i + c.len_utf8()])
+        .collect()
+}
+
+/// Writes one changed line, highlighting the parts flagged `true`.
+fn changed(out: &mut String, mark: char, parts: &[(bool, &str)], style: Style, color: bool) {
+    out.push('\n');
+    color::open(out, &style, color);
+    out.push(mark);
+    out.push(' ');
+    for &(hit, text) in parts {
+        if hit && color {
+            color::close(out, &style, color);
+            color::open(out, &style.bold().underline(), color);
+            out.push_str(text);
+            color::close(out, &style.bold().underline(), color);
+            color::open(out, &style, color);
+        } else {
+            out.push_str(text);
+        }
+    }
+    color::close(out, &style, color);
+    if !color && parts.iter().any(|&(hit, _)| hit) {
+        let mut marks = String::from("\n  ");
+        for &(hit, text) in parts {
+            let width = crate::width::str_width(text);
+            marks.extend(std::iter::repeat_n(if hit { '^' } else { ' ' }, width));
+        }
+        out.push_str(marks.trim_end());
+    }
+}
+
+/// Writes a run of removed and added lines, pairing them up in order for
+/// highlighting where they are alike.
+fn run(out: &mut String, removed: &[&str], added: &[&str], color: bool) {
+    let mut pairs: Vec<Option<(Parts, Parts)>> = Vec::new();
+    for (old, new) in removed.iter().zip(added) {
+        let (a, b) = (chars(old), chars(new));
+        let ops = diff::diff(&a, &b);
+        let same = ops.iter().filter(|op| matches!(op, Op::Same(_))).count();
+        if same * 2 < a.len().max(b.len()) {
+            pairs.push(None);
+            continue;
+        }
+        let side = |keep: fn(&Op) -> Option<bool>| {
+            ops.iter()
+                .filter_map(|op| {
+                    let (Op::Same(c) | Op::Removed(c) | Op::Added(c)) = *op;
+                    keep(op).map(|hit| (hit, c))
+                })
+                .collect()
+        };
+        pairs.push(Some((
+            side(|op| match op {
+                Op::Same(_) => Some(false),
+                Op::Removed(_) => Some(true),
+                Op::Added(_) => None,
+            }),
+            side(|op| match op {
+                Op::Same(_) => Some(false),
+                Op::Added(_) => Some(true),
+                Op::Removed(_) => None,
+            }),
+        )));
+    }
+    for (i, line) in removed.iter().enumerate() {
+        match pairs.get(i) {
+            Some(Some((parts, _))) => changed(out, '-', parts, REMOVED, color),
+            _ => changed(out, '-', &[(false, line)], REMOVED, color),
+        }
+    }
+    for (i, line) in added.iter().enumerate() {
+        match pairs.get(i) {
+            Some(Some((_, parts))) => changed(out, '+', parts, ADDED, color),
+            _ => changed(out, '+', &[(false, line)], ADDED, color),
+        }
+    }
+}
+
+fn render(names: (&str, &str), expected: &str, actual: &str, color: bool) -> String {
+    let (a, b) = names;
+    if expected == actual {
+        let lines = expected.split('\n').count();
+        let s = if lines == 1 { "" } else { "s" };
+        return format!("`{a}` and `{b}` are equal ({lines} line{s})");
+    }
+    let old: Vec<&str> = expected.split('\n').collect();
+    let new: Vec<&str> = actual.split('\n').collect();
+    let ops = diff::diff(&old, &new);
+    let shown = diff::visible(&ops, CONTEXT);
+
+    let mut out = format!("`{a}` and `{b}` differ:");
+    let mut k = 0;
+    while k < shown.len() {
+        match shown[k].map(|i| ops[i]) {
+            None => out.push_str("\n  …"),
+            Some(Op::Same(line)) => {
+                out.push_str("\n  ");
+                out.push_str(line);
+            }
+            Some(_) => {
+                let (mut removed, mut added) = (Vec::new(), Vec::new());
+                while let Some(op) = shown.get(k).copied().flatten().map(|i| ops[i]) {
+                    match op {
+                        Op::Removed(line) => removed.push(line),
+                        Op::Added(line) => added.push(line),
+                        Op::Same(_) => break,
+                    }
+                    k += 1;
+                }
+                run(&mut out, &removed, &added, color);
+                continue;
+            }
+        }
+        k += 1;
+    }
+    out
+}
+
+/// Backend of `test_log_str_diff!`.
+#[doc(hidden)]
+pub fn log_str_diff(meta: &Metadata, names: (&str, &str), expected: &str, actual: &str) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    let color = crate::config::current().color_enabled();
+    crate::emit::log(
+        meta,
+        format_args!("{}", render(names, expected, actual, color)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_changed_characters() {
+        let expected = "[server]\nhost = \"localhost\"\nport = 8080\n\n[log]\nlevel = \"info\"";
+        let actual = "[server]\nhost = \"127.0.0.1\"\nport = 8080\n\n[log]\nlevel = \"debug\"\nfile = \"a.log\"";
+        assert_eq!(
+            render(("expected", "actual"), expected, actual, false),
+            "`expected` and `actual` differ:\n  \
+             [server]\n\
+             - host = \"localhost\"\n  \
+             \u{20}       ^^^^^^^^^\n\
+             + host = \"127.0.0.1\"\n  \
+             \u{20}       ^^^^^^^^^\n  \
+             port = 8080\n  \
+             \n  \
+             [log]\n\
+             - level = \"info\"\n  \
+             \u{20}        ^^^^\n\
+             + level = \"debug\"\n  \
+             \u{20}        ^^^^^\n\
+             + file = \"a.log\""
+        );
+    }
+
+    #[test]
+    fn unlike_lines_are_not_highlighted() {
+        let text = render(("a", "b"), "one\ntwo", "one\nsomething else", true);
+        assert_eq!(
+            text,
+            "`a` and `b` differ:\n  one\n\
+             \x1b[31m- two\x1b[0m\n\
+             \x1b[32m+ something else\x1b[0m"
+        );
+        assert_eq!(
+            render(("a", "b"), "x\ny", "x\ny", false),
+            "`a` and `b` are equal (2 lines)"
+        );
+    }
+}
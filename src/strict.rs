@@ -0,0 +1,170 @@
+//! Strict mode: warnings and errors logged by a test make it fail.
+//!
+//! A [`StrictGuard`] counts the `WARN` and `ERROR` records emitted on its
+//! thread, whether or not the level filter or a
+//! [`suppress`](crate::suppress) guard hides them, and panics when it
+//! is dropped if there were any. [`#[testlog::test]`](macro@crate::test)
+//! sets one up with its `strict` option or when `TESTLOG_STRICT=1`.
+
+use crate::level::Level;
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Messages quoted in the failure message.
+const QUOTED: usize = 5;
+
+#[derive(Debug, Default)]
+struct Hits {
+    count: usize,
+    first: Vec<String>,
+}
+
+thread_local! {
+    static HITS: RefCell<Option<Hits>> = const { RefCell::new(None) };
+}
+
+/// Guard returned by [`strict`]; fails the test when dropped if warnings or
+/// errors were logged meanwhile.
+#[must_use = "strict mode only covers the guard's lifetime"]
+#[derive(Debug)]
+pub struct StrictGuard {
+    previous: Option<Option<Hits>>,
+    // The counts belong to the thread that started them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl StrictGuard {
+    /// Ends strict mode, returning the failure message if there is one.
+    pub(crate) fn finish(mut self) -> Option<String> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Option<String> {
+        let previous = self.previous.take()?;
+        let hits = HITS.try_with(|h| h.replace(previous)).ok().flatten()?;
+        if hits.count == 0 {
+            return None;
+        }
+        let mut msg = format!(
+            "strict mode: {} warning or error record{} logged",
+            hits.count,
+            if hits.count == 1 { " was" } else { "s were" }
+        );
+        for text in &hits.first {
+            msg.push_str("\n  ");
+            msg.push_str(&text.replace('\n', "\n    "));
+        }
+        if hits.count > hits.first.len() {
+            msg.push_str(&format!("\n  … and {} more", hits.count - hits.first.len()));
+        }
+        Some(msg)
+    }
+}
+
+impl Drop for StrictGuard {
+    fn drop(&mut self) {
+        if let Some(msg) = self.end() {
+            if !std::thread::panicking() {
+                panic!("{msg}");
+            }
+        }
+    }
+}
+
+/// Fails the calling test if it logs anything at [`Level::Warn`] or
+/// [`Level::Error`] before the returned guard is dropped.
+///
+/// For plain `#[test]` functions; [`#[testlog::test(strict)]`](macro@crate::test)
+/// does the same for the whole test.
+///
+/// ```rust
+/// let _strict = testlog::strict();
+/// testlog::test_info!("info records are fine");
+/// // testlog::test_warn!("...") here would fail the test.
+/// ```
+pub fn strict() -> StrictGuard {
+    let previous = HITS.with(|h| h.replace(Some(Hits::default())));
+    StrictGuard {
+        previous: Some(previous),
+        _not_send: PhantomData,
+    }
+}
+
+/// Whether strict mode on the calling thread counts records at `level`,
+/// which it does whether or not they are shown.
+pub(crate) fn wants(level: Level) -> bool {
+    level <= Level::Warn
+        && HITS
+            .try_with(|h| h.try_borrow().is_ok_and(|hits| hits.is_some()))
+            .unwrap_or(false)
+}
+
+/// Counts a record at `level` if strict mode is on for the calling thread.
+pub(crate) fn record(level: Level, args: fmt::Arguments) {
+    if level > Level::Warn {
+        return;
+    }
+    let _ = HITS.try_with(|h| {
+        if let Ok(mut hits) = h.try_borrow_mut() {
+            if let Some(hits) = hits.as_mut() {
+                hits.count += 1;
+                if hits.first.len() < QUOTED {
+                    hits.first.push(format!("{level}: {args}"));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_warnings_and_errors_only() {
+        let _capture = crate::capture();
+        let guard = strict();
+        crate::test_info!("fine");
+        crate::test_warn!("disk {}% full", 91);
+        crate::test_error!("write failed");
+        let msg = guard.finish().unwrap();
+        assert_eq!(
+            msg,
+            "strict mode: 2 warning or error records were logged\n  \
+             WARN: disk 91% full\n  ERROR: write failed"
+        );
+        assert_eq!(strict().finish(), None);
+    }
+
+    #[test]
+    fn guard_panics_when_dropped() {
+        let _capture = crate::capture();
+        let result = std::panic::catch_unwind(|| {
+            let _strict = strict();
+            crate::test_warn!("retrying");
+        });
+        let payload = result.unwrap_err();
+        assert!(payload
+            .downcast_ref::<String>()
+            .unwrap()
+            .contains("WARN: retrying"));
+    }
+
+    #[test]
+    fn counts_records_that_are_not_shown() {
+        let _capture = crate::capture();
+        let guard = strict();
+        {
+            let _quiet = crate::set_level_for(module_path!(), Level::Error);
+            crate::test_log_lazy!(Level::Warn, || "filtered");
+        }
+        {
+            let _suppress = crate::suppress();
+            crate::test_warn!("suppressed");
+        }
+        assert!(crate::captured_lines().is_empty());
+        let msg = guard.finish().unwrap();
+        assert!(msg.contains("WARN: filtered\n  WARN: suppressed"), "{msg}");
+    }
+}
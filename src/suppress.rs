@@ -0,0 +1,61 @@
+//! Scoped suppression used by [`test_log_off!`](crate::test_log_off).
+
+use std::cell::Cell;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Guard returned by [`suppress`]; output resumes when it is dropped.
+#[must_use = "output is only suppressed while the guard is alive"]
+#[derive(Debug)]
+pub struct SuppressGuard {
+    _private: (),
+}
+
+impl Drop for SuppressGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+    }
+}
+
+/// Discards all testlog output on the calling thread until the returned
+/// guard is dropped, including output from helpers called meanwhile.
+///
+/// Suppressed output is not captured either. See also
+/// [`test_log_off!`](crate::test_log_off).
+///
+/// ```rust
+/// let quiet = testlog::suppress();
+/// testlog::test_log!("never printed");
+/// drop(quiet);
+/// testlog::test_log!("printed again");
+/// ```
+pub fn suppress() -> SuppressGuard {
+    crate::emit::flush_pending();
+    DEPTH.with(|d| d.set(d.get() + 1));
+    SuppressGuard { _private: () }
+}
+
+/// Whether output on the calling thread is currently suppressed.
+pub(crate) fn active() -> bool {
+    DEPTH.try_with(|d| d.get() > 0).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppression_nests_and_discards_output() {
+        let _capture = crate::capture();
+        crate::test_log_off! {
+            crate::test_log!("fixture noise");
+            let _inner = suppress();
+            crate::test_warn!("more noise");
+        }
+        assert!(!active());
+        crate::test_log!("interesting");
+        assert_eq!(crate::captured_lines().len(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+//! Per-test setup behind the test attributes such as
+//! [`tokio_test`](crate::tokio_test).
+//!
+//! For the duration of the test the attribute installs the panic hook,
+//! [captures](crate::capture) the test's output so it is only shown if the
+//! test fails, and tags records with the test's name. With `check_env` it
+//! also reports environment variables and the working directory the test
+//! left changed.
+
+use crate::baseline::{BaselineGuard, BaselineMode};
+use crate::capture::{capture, CaptureGuard};
+use crate::level::Level;
+use crate::record::Metadata;
+use crate::spans::SpanStart;
+use crate::strict::{strict, StrictGuard};
+use crate::watchdog::{watchdog, WatchdogGuard};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::panic::Location;
+use std::path::PathBuf;
+use std::time::Instant;
+
+thread_local! {
+    static CURRENT: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Name of the test running on this thread, if it was set up by one of the
+/// test attributes.
+pub(crate) fn current() -> Option<&'static str> {
+    CURRENT.try_with(Cell::get).ok().flatten()
+}
+
+/// `path` as libtest names the test: without the leading crate name.
+fn test_name(path: &'static str) -> &'static str {
+    path.split_once("::").map_or(path, |(_, rest)| rest)
+}
+
+/// Process state a test can leak into the tests that run after it.
+#[derive(Debug, PartialEq)]
+struct EnvSnapshot {
+    vars: BTreeMap<OsString, OsString>,
+    cwd: Option<PathBuf>,
+}
+
+impl EnvSnapshot {
+    fn take() -> Self {
+        EnvSnapshot {
+            vars: std::env::vars_os().collect(),
+            cwd: std::env::current_dir().ok(),
+        }
+    }
+
+    /// One line per change from `self` to `after`.
+    fn diff(&self, after: &EnvSnapshot) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (name, value) in &after.vars {
+            match self.vars.get(name) {
+                None => changes.push(format!("+{}={}", name.display(), value.display())),
+                Some(old) if old != value => changes.push(format!(
+                    "~{}: {} → {}",
+                    name.display(),
+                    old.display(),
+                    value.display()
+                )),
+                Some(_) => {}
+            }
+        }
+        for name in self.vars.keys().filter(|n| !after.vars.contains_key(*n)) {
+            changes.push(format!("-{}", name.display()));
+        }
+        if self.cwd != after.cwd {
+            let show = |cwd: &Option<PathBuf>| {
+                cwd.as_ref()
+                    .map_or("<unknown>".to_string(), |p| p.display().to_string())
+            };
+            changes.push(format!("cwd: {} → {}", show(&self.cwd), show(&after.cwd)));
+        }
+        changes
+    }
+}
+
+/// Guard created at the start of an attributed test.
+#[doc(hidden)]
+pub struct TestGuard {
+    meta: Metadata,
+    started: Instant,
+    previous: Option<&'static str>,
+    env: Option<EnvSnapshot>,
+    capture: Option<CaptureGuard>,
+    trace: Option<SpanStart>,
+    strict: Option<StrictGuard>,
+    baseline: Option<BaselineGuard>,
+    _watchdog: Option<WatchdogGuard>,
+}
+
+impl TestGuard {
+    /// `path` is the test function's full path, `module_path!()` included.
+    #[track_caller]
+    pub fn begin(path: &'static str) -> Self {
+        crate::panic::install();
+        let location = Location::caller();
+        let previous = CURRENT.with(|c| c.replace(Some(test_name(path))));
+        let config = crate::config::current();
+        TestGuard {
+            meta: Metadata::new(Level::Warn, path, location.file(), location.line()),
+            started: Instant::now(),
+            previous,
+            env: None,
+            capture: Some(capture()),
+            trace: crate::spans::start(),
+            strict: config.strict.then(strict),
+            baseline: (config.baseline != BaselineMode::Off)
+                .then(|| crate::baseline::for_test(test_name(path))),
+            _watchdog: config.watchdog.map(watchdog),
+        }
+    }
+
+    /// Fails the test if it logs warnings or errors; see [`strict`].
+    pub fn strict(mut self) -> Self {
+        self.strict.get_or_insert_with(strict);
+        self
+    }
+
+    /// Fails the test if its log differs from the recorded one; see
+    /// [`baseline`](crate::baseline).
+    pub fn baseline(mut self) -> Self {
+        if self.baseline.is_none() {
+            let name = current().unwrap_or(self.meta.target);
+            self.baseline = Some(crate::baseline::for_test(name));
+        }
+        self
+    }
+
+    /// Reports environment and working directory changes when the test
+    /// ends. Both are process-wide, so changes made by tests running in
+    /// parallel show up too; run with `--test-threads=1` to attribute them.
+    pub fn check_env(mut self) -> Self {
+        self.env = Some(EnvSnapshot::take());
+        self
+    }
+}
+
+impl Drop for TestGuard {
+    fn drop(&mut self) {
+        crate::emit::flush_pending();
+        crate::once::report();
+        let differs = self.baseline.take().and_then(BaselineGuard::finish);
+        let strict = self.strict.take().and_then(StrictGuard::finish);
+        let failure = match (differs, strict) {
+            (Some(differs), Some(strict)) => Some(format!("{differs}\n{strict}")),
+            (differs, strict) => differs.or(strict),
+        };
+        // The report must not be discarded along with the captured output,
+        // which is kept for the panic below when strict mode or the baseline
+        // failed.
+        if failure.is_none() {
+            drop(self.capture.take());
+        }
+        if let Some(before) = &self.env {
+            let changes = before.diff(&EnvSnapshot::take());
+            if !changes.is_empty() {
+                crate::emit::log(
+                    &self.meta,
+                    format_args!("test left the environment changed:\n{}", changes.join("\n")),
+                );
+            }
+        }
+        let name = current().unwrap_or(self.meta.target);
+        crate::spans::end("test", name, self.trace);
+        let failed = failure.is_some() || std::thread::panicking();
+        crate::slowest::record(name, self.started.elapsed(), failed);
+        let _ = CURRENT.try_with(|c| c.set(self.previous));
+        if let Some(failure) = failure.filter(|_| !std::thread::panicking()) {
+            panic!("{failure}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_tags_and_captures_the_test() {
+        {
+            let _test = TestGuard::begin(concat!(module_path!(), "::", "tagged"));
+            assert_eq!(current(), Some("test::tests::tagged"));
+            crate::test_log!("only shown on failure");
+            assert_eq!(crate::captured_lines().len(), 1);
+        }
+        assert_eq!(current(), None);
+        assert!(crate::captured_lines().is_empty());
+    }
+
+    #[test]
+    fn strict_tests_fail_on_warnings() {
+        let result = std::panic::catch_unwind(|| {
+            let _test = TestGuard::begin(concat!(module_path!(), "::", "strict")).strict();
+            crate::test_warn!("deprecated option");
+        });
+        let payload = result.unwrap_err();
+        let msg = payload.downcast_ref::<String>().unwrap();
+        assert!(
+            msg.ends_with("was logged\n  WARN: deprecated option"),
+            "{msg}"
+        );
+        assert_eq!(current(), None);
+    }
+
+    // What happens to tasks of a `multi_thread` tokio test, which run on
+    // worker threads of their own.
+    #[test]
+    fn other_threads_are_not_covered() {
+        let _test = TestGuard::begin(concat!(module_path!(), "::", "workers")).strict();
+        std::thread::spawn(|| {
+            assert_eq!(current(), None);
+            crate::test_warn!("logged from a worker thread");
+        })
+        .join()
+        .unwrap();
+        assert_eq!(current(), Some("test::tests::workers"));
+        assert!(crate::captured_lines().is_empty());
+    }
+
+    #[test]
+    fn env_diff_lists_every_change() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (OsString::from(k), OsString::from(v)))
+                .collect()
+        };
+        let before = EnvSnapshot {
+            vars: vars(&[("KEPT", "1"), ("CHANGED", "a"), ("REMOVED", "x")]),
+            cwd: Some(PathBuf::from("/src")),
+        };
+        let after = EnvSnapshot {
+            vars: vars(&[("ADDED", "y"), ("CHANGED", "b"), ("KEPT", "1")]),
+            cwd: Some(PathBuf::from("/tmp")),
+        };
+        assert_eq!(
+            before.diff(&after),
+            [
+                "+ADDED=y",
+                "~CHANGED: a → b",
+                "-REMOVED",
+                "cwd: /src → /tmp"
+            ]
+        );
+        assert!(before.diff(&before).is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+//! Color themes.
+//!
+//! A [`Theme`] decides how level markers, prefix elements (time, thread,
+//! module path, location) and headings (section banners, group markers)
+//! are styled when colors are enabled. Pick one with `TESTLOG_THEME` or
+//! [`ConfigBuilder::theme`](crate::ConfigBuilder::theme).
+
+use crate::level::Level;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// One of the 256 colors of the extended palette.
+    Ansi256(u8),
+}
+
+impl Color {
+    fn write_fg(self, out: &mut String) {
+        let basic = |n: u8| if n < 8 { 30 + n } else { 90 + n - 8 };
+        let index = match self {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::White => 7,
+            Color::BrightBlack => 8,
+            Color::BrightRed => 9,
+            Color::BrightGreen => 10,
+            Color::BrightYellow => 11,
+            Color::BrightBlue => 12,
+            Color::BrightMagenta => 13,
+            Color::BrightCyan => 14,
+            Color::BrightWhite => 15,
+            Color::Ansi256(n) => {
+                let _ = write!(out, "38;5;{n}");
+                return;
+            }
+        };
+        let _ = write!(out, "{}", basic(index));
+    }
+}
+
+/// Text attributes applied to one element of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    fg: Option<Color>,
+    bold: bool,
+    dim: bool,
+    underline: bool,
+}
+
+impl Style {
+    /// No attributes at all.
+    pub const fn new() -> Self {
+        Style {
+            fg: None,
+            bold: false,
+            dim: false,
+            underline: false,
+        }
+    }
+
+    /// Sets the foreground color.
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    pub const fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub const fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    pub const fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    /// Whether the style changes nothing.
+    pub(crate) fn is_plain(&self) -> bool {
+        *self == Style::new()
+    }
+
+    /// Appends the SGR parameters of the style, e.g. `1;31`.
+    pub(crate) fn write_sgr(&self, out: &mut String) {
+        let flags = [(self.bold, "1"), (self.dim, "2"), (self.underline, "4")];
+        let mut first = true;
+        for (_, code) in flags.iter().filter(|(on, _)| *on) {
+            if !first {
+                out.push(';');
+            }
+            out.push_str(code);
+            first = false;
+        }
+        if let Some(fg) = self.fg {
+            if !first {
+                out.push(';');
+            }
+            fg.write_fg(out);
+        }
+    }
+}
+
+/// Styles for every element testlog colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    error: Style,
+    warn: Style,
+    info: Style,
+    debug: Style,
+    trace: Style,
+    prefix: Style,
+    heading: Style,
+}
+
+impl Theme {
+    /// The default, for dark backgrounds.
+    pub const fn dark() -> Theme {
+        Theme {
+            error: Style::new().fg(Color::Red),
+            warn: Style::new().fg(Color::Yellow),
+            info: Style::new().fg(Color::Green),
+            debug: Style::new().fg(Color::Blue),
+            trace: Style::new().fg(Color::Magenta),
+            prefix: Style::new().dim(),
+            heading: Style::new().bold(),
+        }
+    }
+
+    /// Darker colors that stay readable on light backgrounds, where yellow
+    /// and dimmed text wash out.
+    pub const fn light() -> Theme {
+        Theme {
+            error: Style::new().fg(Color::Red).bold(),
+            warn: Style::new().fg(Color::Ansi256(130)),
+            info: Style::new().fg(Color::Ansi256(28)),
+            debug: Style::new().fg(Color::Blue),
+            trace: Style::new().fg(Color::Ansi256(90)),
+            prefix: Style::new().fg(Color::Ansi256(242)),
+            heading: Style::new().bold(),
+        }
+    }
+
+    /// Bold, bright colors and no dimming.
+    pub const fn high_contrast() -> Theme {
+        Theme {
+            error: Style::new().fg(Color::BrightRed).bold(),
+            warn: Style::new().fg(Color::BrightYellow).bold(),
+            info: Style::new().fg(Color::BrightGreen).bold(),
+            debug: Style::new().fg(Color::BrightCyan).bold(),
+            trace: Style::new().fg(Color::BrightWhite).bold(),
+            prefix: Style::new(),
+            heading: Style::new().bold().underline(),
+        }
+    }
+
+    /// Style of the level marker for `level`.
+    pub fn level(&self, level: Level) -> Style {
+        match level {
+            Level::Error => self.error,
+            Level::Warn => self.warn,
+            Level::Info => self.info,
+            Level::Debug => self.debug,
+            Level::Trace => self.trace,
+        }
+    }
+
+    /// Replaces the style of the level marker for `level`.
+    pub fn with_level(mut self, level: Level, style: Style) -> Theme {
+        let slot = match level {
+            Level::Error => &mut self.error,
+            Level::Warn => &mut self.warn,
+            Level::Info => &mut self.info,
+            Level::Debug => &mut self.debug,
+            Level::Trace => &mut self.trace,
+        };
+        *slot = style;
+        self
+    }
+
+    /// Style of the time, thread, module path and location prefixes.
+    pub fn prefix(&self) -> Style {
+        self.prefix
+    }
+
+    /// Replaces the prefix style.
+    pub fn with_prefix(mut self, style: Style) -> Theme {
+        self.prefix = style;
+        self
+    }
+
+    /// Style of section banners and group markers.
+    pub fn heading(&self) -> Style {
+        self.heading
+    }
+
+    /// Replaces the heading style.
+    pub fn with_heading(mut self, style: Style) -> Theme {
+        self.heading = style;
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dark" => Ok(Theme::dark()),
+            "light" => Ok(Theme::light()),
+            "high-contrast" | "high_contrast" => Ok(Theme::high_contrast()),
+            other => Err(format!("unknown theme `{other}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgr(style: Style) -> String {
+        let mut out = String::new();
+        style.write_sgr(&mut out);
+        out
+    }
+
+    #[test]
+    fn styles_render_sgr_parameters() {
+        assert_eq!(sgr(Style::new().fg(Color::Red)), "31");
+        assert_eq!(sgr(Style::new().bold().fg(Color::BrightCyan)), "1;96");
+        assert_eq!(
+            sgr(Style::new().dim().fg(Color::Ansi256(130))),
+            "2;38;5;130"
+        );
+        assert!(Style::new().is_plain());
+    }
+
+    #[test]
+    fn themes_parse_and_customize() {
+        assert_eq!("light".parse(), Ok(Theme::light()));
+        assert_eq!("High-Contrast".parse(), Ok(Theme::high_contrast()));
+        assert!("neon".parse::<Theme>().is_err());
+        let theme = Theme::dark().with_level(Level::Warn, Style::new().underline());
+        assert_eq!(theme.level(Level::Warn), Style::new().underline());
+        assert_eq!(theme.level(Level::Error), Theme::dark().level(Level::Error));
+    }
+}
@@ -0,0 +1,52 @@
+//! Timing records for [`test_timed!`](crate::test_timed).
+
+use crate::record::Metadata;
+use crate::units::HumanDuration;
+use std::time::Duration;
+
+#[cfg(feature = "perf")]
+pub use crate::profile::Sampler;
+
+/// Stand-in for the sampler of the `perf` feature, which does nothing.
+#[cfg(not(feature = "perf"))]
+#[doc(hidden)]
+pub struct Sampler;
+
+#[cfg(not(feature = "perf"))]
+impl Sampler {
+    pub fn start() -> Self {
+        Sampler
+    }
+}
+
+/// Backend of `test_timed!`.
+#[doc(hidden)]
+pub fn log_timed(meta: &Metadata, expr: &str, elapsed: Duration, sampler: Sampler) {
+    crate::spans::elapsed("timed", expr, elapsed);
+    #[cfg(feature = "perf")]
+    if let Some(summary) = sampler.finish() {
+        crate::emit::log(
+            meta,
+            format_args!("`{expr}` took {}\n{summary}", HumanDuration(elapsed)),
+        );
+        return;
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = sampler;
+    crate::emit::log(
+        meta,
+        format_args!("`{expr}` took {}", HumanDuration(elapsed)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn timed_returns_the_value_and_logs_the_expression() {
+        let _capture = crate::capture();
+        let v = crate::test_timed!([3, 1, 2].iter().max().copied());
+        assert_eq!(v, Some(3));
+        let lines = crate::captured_lines();
+        assert!(lines[0].contains("`[3, 1, 2].iter().max().copied()` took "));
+    }
+}
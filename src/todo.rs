@@ -0,0 +1,46 @@
+//! Markers logged by [`test_todo!`](crate::test_todo) and
+//! [`test_unreachable!`](crate::test_unreachable).
+
+use crate::record::Metadata;
+use std::fmt;
+
+/// Backend of `test_todo!` and `test_unreachable!`.
+///
+/// `what` is `not yet implemented` or `entered unreachable code`, as in the
+/// std macros. Always logs, whatever the level filter, and panics with the
+/// same message when `panic` is set.
+#[doc(hidden)]
+#[track_caller]
+pub fn reached(meta: &Metadata, what: &str, message: Option<fmt::Arguments>, panic: bool) {
+    let text = match message {
+        Some(message) => format!("{what}: {message}"),
+        None => what.to_string(),
+    };
+    crate::emit::log_unfiltered(meta, format_args!("▲ {text} ({}:{})", meta.file, meta.line));
+    if panic {
+        panic!("{text}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn markers_log_and_optionally_panic() {
+        let _capture = crate::capture();
+        crate::test_todo!();
+        crate::test_unreachable!("state {}", 3);
+        let lines = crate::captured_lines();
+        assert!(
+            lines[0].contains("▲ not yet implemented (src/todo.rs:"),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].contains("▲ entered unreachable code: state 3 ("));
+        let panicked = std::panic::catch_unwind(|| crate::test_todo!(panic, "retry"));
+        let payload = panicked.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().map(String::as_str),
+            Some("not yet implemented: retry")
+        );
+    }
+}
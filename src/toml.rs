@@ -0,0 +1,171 @@
+//! The subset of TOML used by `testlog.toml`: top-level `key = value`
+//! pairs whose values are strings, integers, booleans or arrays of those.
+//!
+//! Values are handed back as the text their `FromStr` impl expects, so the
+//! file accepts exactly what the matching environment variable does.
+
+/// One `key = value` pair with the line it was found on.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) line: usize,
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+/// Parses `src`, or returns the first error with its line number.
+pub(crate) fn parse(src: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut entries = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err((line_no, "tables are not supported".to_string()));
+        }
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| (line_no, "expected `key = value`".to_string()))?;
+        let key = key.trim().trim_matches('"');
+        if key.is_empty() {
+            return Err((line_no, "missing key".to_string()));
+        }
+        let mut value = Value { rest: rest.trim() };
+        let text = value.parse().map_err(|msg| (line_no, msg))?;
+        let rest = value.rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err((line_no, format!("unexpected `{rest}` after value")));
+        }
+        entries.push(Entry {
+            line: line_no,
+            key: key.to_string(),
+            value: text,
+        });
+    }
+    Ok(entries)
+}
+
+/// The unparsed remainder of a line.
+struct Value<'a> {
+    rest: &'a str,
+}
+
+impl Value<'_> {
+    /// Parses one value; arrays become their items joined by commas.
+    fn parse(&mut self) -> Result<String, String> {
+        self.rest = self.rest.trim_start();
+        match self.rest.chars().next() {
+            Some('"') => self.basic_string(),
+            Some('\'') => {
+                let end = self.rest[1..].find('\'').ok_or("unterminated string")?;
+                let text = self.rest[1..=end].to_string();
+                self.rest = &self.rest[end + 2..];
+                Ok(text)
+            }
+            Some('[') => {
+                self.rest = &self.rest[1..];
+                let mut items = Vec::new();
+                loop {
+                    self.rest = self.rest.trim_start();
+                    if let Some(rest) = self.rest.strip_prefix(']') {
+                        self.rest = rest;
+                        return Ok(items.join(","));
+                    }
+                    items.push(self.parse()?);
+                    self.rest = self.rest.trim_start();
+                    if let Some(rest) = self.rest.strip_prefix(',') {
+                        self.rest = rest;
+                    } else if !self.rest.starts_with(']') {
+                        return Err("expected `,` or `]` in array".to_string());
+                    }
+                }
+            }
+            Some(_) => {
+                let end = self
+                    .rest
+                    .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+                    .unwrap_or(self.rest.len());
+                let word = &self.rest[..end];
+                let valid = matches!(word, "true" | "false")
+                    || word.bytes().all(|b| b.is_ascii_digit() || b == b'_');
+                if !valid {
+                    return Err(format!("`{word}` is not a string, integer or boolean"));
+                }
+                self.rest = &self.rest[end..];
+                Ok(word.replace('_', ""))
+            }
+            None => Err("missing value".to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        let mut chars = self.rest[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 2..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or("invalid `\\u` escape")?;
+                        out.push(c);
+                    }
+                    _ => return Err("invalid escape".to_string()),
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(src: &str) -> Vec<(String, String)> {
+        parse(src)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.key, e.value))
+            .collect()
+    }
+
+    #[test]
+    fn parses_scalars_and_arrays() {
+        let src = "# shared settings\nlevel = \"debug\"  # comment\nwidth = 1_00\n\
+                   collapse = true\ntags = ['io', \"-slow\"]\noutput = \"file:a \\\"b\\\".log\"\n";
+        assert_eq!(
+            pairs(src),
+            [
+                ("level".into(), "debug".into()),
+                ("width".into(), "100".into()),
+                ("collapse".into(), "true".into()),
+                ("tags".into(), "io,-slow".into()),
+                ("output".into(), "file:a \"b\".log".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        assert_eq!(parse("\n[testlog]").unwrap_err().0, 2);
+        assert_eq!(
+            parse("level = debug").unwrap_err(),
+            (1, "`debug` is not a string, integer or boolean".to_string())
+        );
+        assert!(parse("level = \"debug").is_err());
+        assert!(parse("width = 3 4").is_err());
+    }
+}
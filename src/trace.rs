@@ -0,0 +1,166 @@
+//! Runtime support for [`trace_impl`](crate::trace_impl).
+//!
+//! Every traced call logs a `→` line with its arguments when it starts and
+//! a `←` line with its return value when it finishes. Output between the
+//! two is indented like a [`test_group!`](crate::test_group), so nested
+//! calls read as a call tree.
+
+use crate::record::Metadata;
+use crate::spans::SpanStart;
+use std::fmt;
+
+/// Wraps a value so that [`TraceDebug`] or [`TraceOpaque`] can describe
+/// it, whichever applies. Method resolution on `(&TraceValue(&v))` picks
+/// the `Debug` impl when there is one and the placeholder otherwise.
+#[doc(hidden)]
+pub struct TraceValue<'a, T: ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait TraceDebug {
+    fn describe(&self) -> String;
+}
+
+impl<T: fmt::Debug + ?Sized> TraceDebug for TraceValue<'_, T> {
+    fn describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait TraceOpaque {
+    fn describe(&self) -> String;
+}
+
+impl<T: ?Sized> TraceOpaque for &TraceValue<'_, T> {
+    fn describe(&self) -> String {
+        "…".to_string()
+    }
+}
+
+/// One traced call; logs the exit line, or that the call panicked.
+#[doc(hidden)]
+pub struct TraceSpan {
+    meta: &'static Metadata,
+    name: &'static str,
+    open: bool,
+    /// Whether the entry line was shown, and the lines after it indented.
+    indented: bool,
+    trace: Option<SpanStart>,
+}
+
+impl TraceSpan {
+    pub fn enter(
+        meta: &'static Metadata,
+        name: &'static str,
+        args: impl FnOnce() -> Vec<(&'static str, String)>,
+    ) -> Self {
+        let open = crate::emit::enabled(meta);
+        let indented = open && crate::emit::shown(meta);
+        if open {
+            let args = args()
+                .into_iter()
+                .map(|(name, value)| format!("{name} = {value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            crate::emit::log(meta, format_args!("→ {name}({args})"));
+        }
+        if indented {
+            crate::group::indent();
+        }
+        TraceSpan {
+            meta,
+            name,
+            open,
+            indented,
+            trace: crate::spans::start(),
+        }
+    }
+
+    /// Ends a call that returned a value.
+    pub fn returned(mut self, value: impl FnOnce() -> String) {
+        if self.close() {
+            let value = value();
+            crate::emit::log(self.meta, format_args!("← {} = {value}", self.name));
+        }
+    }
+
+    /// Ends a call without a return value.
+    pub fn finished(mut self) {
+        if self.close() {
+            crate::emit::log(self.meta, format_args!("← {}", self.name));
+        }
+    }
+
+    fn close(&mut self) -> bool {
+        crate::spans::end("trace", self.name, self.trace.take());
+        if std::mem::take(&mut self.indented) {
+            crate::group::outdent();
+        }
+        std::mem::take(&mut self.open)
+    }
+}
+
+impl Drop for TraceSpan {
+    fn drop(&mut self) {
+        if self.close() {
+            crate::emit::log(self.meta, format_args!("← {} panicked", self.name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::__private::{TraceDebug as _, TraceOpaque as _, TraceValue};
+
+    struct Machine {
+        state: u8,
+    }
+
+    #[crate::trace_impl]
+    impl Machine {
+        fn step(&mut self, input: char) -> Result<u8, String> {
+            if input == '!' {
+                return Err("halt".into());
+            }
+            self.state = self.state.checked_add(1).ok_or("overflow")?;
+            Ok(self.state)
+        }
+
+        fn name(&self) -> &str {
+            "machine"
+        }
+
+        fn reset(&mut self) {
+            self.state = 0;
+        }
+
+        #[trace_impl(skip)]
+        fn hot(&self) -> u8 {
+            self.state
+        }
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn describes_debug_values_and_falls_back_otherwise() {
+        struct Opaque;
+        assert_eq!((&TraceValue(&"a")).describe(), "\"a\"");
+        assert_eq!((&TraceValue(&Opaque)).describe(), "…");
+    }
+
+    #[test]
+    fn traced_methods_behave_and_log_calls() {
+        let _capture = crate::capture();
+        let mut machine = Machine { state: 0 };
+        assert_eq!(machine.step('a'), Ok(1));
+        assert_eq!(machine.step('!'), Err("halt".to_string()));
+        assert_eq!(machine.name(), "machine");
+        machine.reset();
+        assert_eq!(machine.hot(), 0);
+        let lines = crate::captured_lines();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].ends_with("→ Machine::step(input = 'a')"));
+        assert!(lines[1].ends_with("← Machine::step = Ok(1)"));
+        assert!(lines[7].ends_with("← Machine::reset"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Human-readable quantities for log messages.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Displays a [`Duration`] the way a person would write it: `850ns`,
+/// `12.3µs`, `1.2ms`, `3.4s`, `2m 05s` or `1h 02m 03s`.
+///
+/// All timing output from testlog uses this format.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use testlog::HumanDuration;
+///
+/// assert_eq!(HumanDuration(Duration::from_micros(1_234)).to_string(), "1.2ms");
+/// assert_eq!(HumanDuration(Duration::from_secs(125)).to_string(), "2m 05s");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.0;
+        let secs = d.as_secs();
+        if secs >= 3600 {
+            write!(
+                f,
+                "{}h {:02}m {:02}s",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )
+        } else if secs >= 60 {
+            write!(f, "{}m {:02}s", secs / 60, secs % 60)
+        } else if secs > 0 {
+            scaled(f, d.as_secs_f64(), "s")
+        } else if d.as_millis() > 0 {
+            scaled(f, d.as_secs_f64() * 1e3, "ms")
+        } else if d.as_micros() > 0 {
+            scaled(f, d.as_secs_f64() * 1e6, "µs")
+        } else {
+            write!(f, "{}ns", d.as_nanos())
+        }
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = String;
+
+    /// Parses what [`Display`](fmt::Display) prints, one or more numbers
+    /// each followed by `ns`, `µs` (or `us`), `ms`, `s`, `m` or `h`, such as
+    /// `500ms`, `1.5s` or `2m 05s`. A bare number is in seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid duration `{s}`");
+        let s = s.trim();
+        if let Ok(secs) = s.parse::<f64>() {
+            return Duration::try_from_secs_f64(secs)
+                .map(HumanDuration)
+                .map_err(|_| invalid());
+        }
+        let mut total = Duration::ZERO;
+        for part in s.split_whitespace() {
+            let split = part
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .ok_or_else(invalid)?;
+            let (value, unit) = part.split_at(split);
+            let value: f64 = value.parse().map_err(|_| invalid())?;
+            let scale = match unit {
+                "ns" => 1e-9,
+                "µs" | "us" => 1e-6,
+                "ms" => 1e-3,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                _ => return Err(invalid()),
+            };
+            total += Duration::try_from_secs_f64(value * scale).map_err(|_| invalid())?;
+        }
+        Ok(HumanDuration(total))
+    }
+}
+
+/// One decimal for small values, none once the decimal stops mattering.
+fn scaled(f: &mut fmt::Formatter<'_>, value: f64, unit: &str) -> fmt::Result {
+    if value < 100.0 {
+        write!(f, "{value:.1}{unit}")
+    } else {
+        write!(f, "{value:.0}{unit}")
+    }
+}
+
+/// `n` bytes in the largest binary unit that keeps the number above one:
+/// `512 B`, `1.5 KiB`, `3.0 MiB`.
+#[cfg(any(feature = "alloc-stats", feature = "mem"))]
+pub(crate) fn bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations_at_every_scale() {
+        let cases = [
+            (Duration::from_nanos(850), "850ns"),
+            (Duration::from_nanos(12_340), "12.3µs"),
+            (Duration::from_micros(1_234), "1.2ms"),
+            (Duration::from_millis(345), "345ms"),
+            (Duration::from_millis(3_400), "3.4s"),
+            (Duration::from_secs(125), "2m 05s"),
+            (Duration::from_secs(3723), "1h 02m 03s"),
+        ];
+        for (duration, expected) in cases {
+            assert_eq!(HumanDuration(duration).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        let parse = |s: &str| s.parse::<HumanDuration>().map(|d| d.0);
+        assert_eq!(parse("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse("2m 05s"), Ok(Duration::from_secs(125)));
+        assert_eq!(parse("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse("5 parsecs").is_err());
+    }
+
+    #[cfg(any(feature = "alloc-stats", feature = "mem"))]
+    #[test]
+    fn picks_the_largest_fitting_unit() {
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(1023), "1023 B");
+        assert_eq!(bytes(1536), "1.5 KiB");
+        assert_eq!(bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
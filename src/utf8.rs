@@ -0,0 +1,106 @@
+//! Safe rendering of untrusted bytes for
+//! [`test_log_utf8_lossy!`](crate::test_log_utf8_lossy).
+//!
+//! Bytes are decoded as UTF-8 and written between quotes. Control
+//! characters, including the escape that starts terminal sequences, and the
+//! bidirectional overrides that reorder text are escaped the way `Debug`
+//! escapes them. Bytes that are not valid UTF-8 are written as `\xNN`
+//! rather than replaced, so the exact input can still be read back.
+
+use crate::record::Metadata;
+use std::fmt::Write;
+
+/// Bytes shown before the rest are only counted.
+const LIMIT: usize = 1024;
+
+/// Whether `c` is written escaped: it could move the cursor, change the
+/// terminal's state or the direction of the text, or be confused with
+/// the quoting.
+fn escaped(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\\' | '"' | '\u{200e}' | '\u{200f}' | '\u{2028}' | '\u{2029}'
+        )
+        || ('\u{202a}'..='\u{202e}').contains(&c)
+        || ('\u{2066}'..='\u{2069}').contains(&c)
+}
+
+fn render(expr: &str, bytes: &[u8]) -> String {
+    let shown = &bytes[..bytes.len().min(LIMIT)];
+    let mut text = String::from("\"");
+    for chunk in shown.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if escaped(c) {
+                text.extend(c.escape_debug());
+            } else {
+                text.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(text, "\\x{byte:02x}");
+        }
+    }
+    text.push('"');
+    if bytes.len() > shown.len() {
+        let _ = write!(text, "… ({} more bytes)", bytes.len() - shown.len());
+    }
+
+    let (mut bad, mut first, mut offset) = (0, None, 0);
+    for chunk in bytes.utf8_chunks() {
+        offset += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            first.get_or_insert(offset);
+            bad += chunk.invalid().len();
+        }
+        offset += chunk.invalid().len();
+    }
+    let len = bytes.len();
+    let s = if len == 1 { "" } else { "s" };
+    let note = match first {
+        None => "valid UTF-8".to_string(),
+        Some(at) => {
+            let b = if bad == 1 { "" } else { "s" };
+            format!("invalid UTF-8: {bad} bad byte{b}, first at offset {at}")
+        }
+    };
+    format!("`{expr}` = {text} ({len} byte{s}, {note})")
+}
+
+/// Backend of `test_log_utf8_lossy!`.
+#[doc(hidden)]
+pub fn log_utf8_lossy(meta: &Metadata, expr: &str, bytes: &[u8]) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    crate::emit::log(meta, format_args!("{}", render(expr, bytes)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_what_would_reach_the_terminal() {
+        assert_eq!(
+            render("input", b"GET /\r\n"),
+            r#"`input` = "GET /\r\n" (7 bytes, valid UTF-8)"#
+        );
+        assert_eq!(
+            render(
+                "case",
+                b"\xffab\x1b[2J\"\\\xe2\x80\xaegr\xc3\xbc\xc3\x9fe\xc3"
+            ),
+            r#"`case` = "\xffab\u{1b}[2J\"\\\u{202e}grüße\xc3" (20 bytes, invalid UTF-8: 2 bad bytes, first at offset 0)"#
+        );
+    }
+
+    #[test]
+    fn long_inputs_are_cut_short() {
+        let text = render("blob", &[b'a'; LIMIT + 5]);
+        assert!(
+            text.ends_with("a\"… (5 more bytes) (1029 bytes, valid UTF-8)"),
+            "{text}"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Watchdog that reports tests which stop making progress.
+//!
+//! A [`WatchdogGuard`] registers its thread with a single monitor thread.
+//! Records the thread shows, and [`test_heartbeat!`](crate::test_heartbeat)
+//! calls, count as activity. When there has been none for the guard's
+//! timeout, the monitor prints what the thread logged so far, which would
+//! otherwise be lost with the test's captured output when CI kills the
+//! hung process, along with the stack of the last heartbeat. That is the
+//! thread's [capture](crate::capture) if it has one, as every
+//! [`#[testlog::test]`](macro@crate::test) does, and its last records
+//! otherwise. The report goes straight to stderr, past libtest's capture,
+//! once per stall.
+//!
+//! The standard library cannot capture another thread's stack, so the
+//! backtrace is the one taken at the last heartbeat rather than where the
+//! thread is stuck; heartbeats placed around blocking calls narrow it down.
+
+use crate::capture::RingBuffer;
+use crate::record::Metadata;
+use crate::units::HumanDuration;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+/// Records kept for the report of a thread that is not capturing.
+const RECENT: usize = 20;
+
+/// How often the monitor looks at the watched threads.
+const POLL: Duration = Duration::from_millis(100);
+
+/// The last [`test_heartbeat!`](crate::test_heartbeat) on a thread.
+struct Beat {
+    location: &'static Location<'static>,
+    backtrace: Backtrace,
+}
+
+#[derive(Default)]
+struct History {
+    recent: VecDeque<String>,
+    beat: Option<Beat>,
+}
+
+/// A watched thread, shared with the monitor.
+struct Watch {
+    thread: String,
+    timeout: Duration,
+    start: Instant,
+    /// Time of the last activity, in nanoseconds since `start`.
+    last: AtomicU64,
+    reported: AtomicBool,
+    history: Mutex<History>,
+    /// The thread's capture buffer, once it has one.
+    capture: Mutex<Weak<Mutex<RingBuffer>>>,
+}
+
+impl Watch {
+    fn touch(&self) {
+        let now = self.start.elapsed().as_nanos() as u64;
+        self.last.store(now, Ordering::Relaxed);
+        self.reported.store(false, Ordering::Relaxed);
+    }
+
+    /// Picks up the calling thread's capture buffer if the one known is
+    /// gone; called on the watched thread.
+    fn attach_capture(&self) {
+        let mut capture = self.capture.lock().unwrap_or_else(|e| e.into_inner());
+        if capture.strong_count() == 0 {
+            if let Some(shared) = crate::capture::shared() {
+                *capture = shared;
+            }
+        }
+    }
+
+    /// The thread's captured output so far, with a note about lines the
+    /// capture limit evicted, or `None` if it is not capturing.
+    fn captured(&self) -> Option<Vec<String>> {
+        let buffer = self
+            .capture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .upgrade()?;
+        let buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut lines = Vec::new();
+        if buffer.dropped() > 0 {
+            lines.push(format!(
+                "… {} earlier lines dropped by the capture limit …",
+                buffer.dropped()
+            ));
+        }
+        lines.extend(buffer.lines().map(str::to_string));
+        Some(lines)
+    }
+
+    /// How long the thread has been quiet, if that is past the timeout and
+    /// not reported yet.
+    fn stalled(&self, now: Instant) -> Option<Duration> {
+        let last = self.start + Duration::from_nanos(self.last.load(Ordering::Relaxed));
+        let quiet = now.saturating_duration_since(last);
+        (quiet >= self.timeout && !self.reported.swap(true, Ordering::Relaxed)).then_some(quiet)
+    }
+
+    fn report(&self, quiet: Duration) -> String {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = format!(
+            "testlog watchdog: no log activity on `{}` for {}",
+            self.thread,
+            HumanDuration(quiet)
+        );
+        if let Some(captured) = self.captured() {
+            if captured.is_empty() {
+                out.push_str("\nnothing was logged so far");
+            } else {
+                out.push_str("\nlogged so far:");
+            }
+            for line in &captured {
+                out.push_str("\n  ");
+                out.push_str(&line.replace('\n', "\n  "));
+            }
+        } else if history.recent.is_empty() {
+            out.push_str("\nnothing was logged while watched");
+        } else {
+            out.push_str(&format!(
+                "\nlast {} record{}:",
+                history.recent.len(),
+                if history.recent.len() == 1 { "" } else { "s" }
+            ));
+            for line in &history.recent {
+                out.push_str("\n  ");
+                out.push_str(&line.replace('\n', "\n  "));
+            }
+        }
+        match &history.beat {
+            Some(beat) => out.push_str(&format!(
+                "\nlast heartbeat at {}:{}, backtrace:\n{}",
+                beat.location.file(),
+                beat.location.line(),
+                beat.backtrace
+            )),
+            None => out.push_str("\nno test_heartbeat! was reached"),
+        }
+        out
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Watch>>> = const { RefCell::new(None) };
+}
+
+static WATCHES: Mutex<Vec<Weak<Watch>>> = Mutex::new(Vec::new());
+
+/// Starts the monitor thread the first time a thread is watched.
+fn spawn_monitor() {
+    static STARTED: OnceLock<()> = OnceLock::new();
+    STARTED.get_or_init(|| {
+        let spawned = std::thread::Builder::new()
+            .name("testlog-watchdog".into())
+            .spawn(|| loop {
+                std::thread::sleep(POLL);
+                let now = Instant::now();
+                let watches: Vec<Arc<Watch>> = {
+                    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+                    watches.retain(|w| w.strong_count() > 0);
+                    watches.iter().filter_map(Weak::upgrade).collect()
+                };
+                for watch in watches {
+                    if let Some(quiet) = watch.stalled(now) {
+                        crate::output::write_uncaptured(&watch.report(quiet));
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            eprintln!("testlog: cannot start the watchdog thread: {err}");
+        }
+    });
+}
+
+/// Guard returned by [`watchdog`]; stops watching the thread when dropped.
+#[must_use = "the thread is only watched during the guard's lifetime"]
+pub struct WatchdogGuard {
+    previous: Option<Option<Arc<Watch>>>,
+    // The watch belongs to the thread that started it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl std::fmt::Debug for WatchdogGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchdogGuard").finish_non_exhaustive()
+    }
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            let _ = CURRENT.try_with(|c| *c.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Reports the calling thread if it logs nothing for `timeout`, until the
+/// returned guard is dropped.
+///
+/// The report lists what the thread logged so far, all of its
+/// [capture](crate::capture) if it has one, and the backtrace of its last
+/// [`test_heartbeat!`](crate::test_heartbeat), and is printed
+/// directly to stderr so it survives a CI timeout that kills the test
+/// binary. [`#[testlog::test]`](macro@crate::test) watches every test when
+/// `TESTLOG_WATCHDOG` is set to a duration such as `30s`.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// let _watchdog = testlog::watchdog(Duration::from_secs(30));
+/// testlog::test_heartbeat!("waiting for the server");
+/// ```
+pub fn watchdog(timeout: Duration) -> WatchdogGuard {
+    let thread = crate::test::current()
+        .map(str::to_string)
+        .or_else(|| std::thread::current().name().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", std::thread::current().id()));
+    let watch = Arc::new(Watch {
+        thread,
+        timeout,
+        start: Instant::now(),
+        last: AtomicU64::new(0),
+        reported: AtomicBool::new(false),
+        history: Mutex::new(History::default()),
+        capture: Mutex::new(Weak::new()),
+    });
+    watch.attach_capture();
+    WATCHES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::downgrade(&watch));
+    spawn_monitor();
+    let previous = CURRENT.with(|c| c.replace(Some(watch)));
+    WatchdogGuard {
+        previous: Some(previous),
+        _not_send: PhantomData,
+    }
+}
+
+/// Runs `f` with the calling thread's watch, if it is watched.
+fn with_watch(f: impl FnOnce(&Watch)) {
+    let _ = CURRENT.try_with(|c| {
+        if let Ok(current) = c.try_borrow() {
+            if let Some(watch) = current.as_ref() {
+                f(watch);
+            }
+        }
+    });
+}
+
+/// Counts a shown record as activity and keeps it for the report.
+pub(crate) fn record(meta: &Metadata, msg: &str) {
+    with_watch(|watch| {
+        watch.touch();
+        watch.attach_capture();
+        let mut history = watch.history.lock().unwrap_or_else(|e| e.into_inner());
+        if history.recent.len() == RECENT {
+            history.recent.pop_front();
+        }
+        history
+            .recent
+            .push_back(format!("{:<5} {msg}", meta.level.as_str()));
+    });
+}
+
+/// Backend of `test_heartbeat!`.
+#[doc(hidden)]
+#[track_caller]
+pub fn heartbeat() {
+    #[cfg(feature = "perf")]
+    crate::profile::sample_point();
+    let location = Location::caller();
+    with_watch(|watch| {
+        watch.touch();
+        watch.history.lock().unwrap_or_else(|e| e.into_inner()).beat = Some(Beat {
+            location,
+            backtrace: Backtrace::force_capture(),
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> Arc<Watch> {
+        CURRENT.with(|c| c.borrow().clone()).unwrap()
+    }
+
+    #[test]
+    fn reports_a_stall_once_until_the_next_activity() {
+        let _capture = crate::capture();
+        let _watchdog = watchdog(Duration::from_secs(3600));
+        crate::test_info!("connecting");
+        crate::test_heartbeat!();
+        let watch = current();
+        let later = Instant::now() + Duration::from_secs(3601);
+        let quiet = watch.stalled(later).unwrap();
+        assert!(watch.stalled(later).is_none());
+
+        let report = watch.report(quiet);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with(
+            "testlog watchdog: no log activity on `watchdog::tests::reports_a_stall_once_until_the_next_activity` for 1h 00m 0"
+        ));
+        assert_eq!(lines[1..3], ["logged so far:", "  INFO  connecting"]);
+        assert!(lines[3].starts_with("last heartbeat at src/watchdog.rs:"));
+
+        crate::test_info!("connected");
+        assert!(watch.stalled(later).is_some());
+    }
+
+    #[test]
+    fn reports_all_of_the_capture() {
+        let _watchdog = watchdog(Duration::from_secs(3600));
+        let _capture = crate::capture();
+        crate::test_section!("setup");
+        for i in 0..RECENT + 5 {
+            crate::test_info!("step {i}");
+        }
+        let report = current().report(Duration::from_secs(3601));
+        let captured = crate::captured_lines();
+        assert_eq!(captured.len(), RECENT + 6);
+        for line in &captured {
+            assert!(report.contains(line.as_str()), "{report}");
+        }
+    }
+
+    #[test]
+    fn threads_without_a_capture_report_their_last_records() {
+        std::thread::spawn(|| {
+            let _watchdog = watchdog(Duration::from_secs(3600));
+            for i in 0..RECENT + 5 {
+                crate::test_info!("step {i}");
+            }
+            let report = current().report(Duration::from_secs(3601));
+            assert!(
+                report.contains("\nlast 20 records:\n  INFO  step 5\n"),
+                "{report}"
+            );
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn unwatched_threads_are_untouched() {
+        crate::test_heartbeat!();
+        assert!(CURRENT.with(|c| c.borrow().is_none()));
+        {
+            let _watchdog = watchdog(Duration::from_secs(1));
+        }
+        assert!(CURRENT.with(|c| c.borrow().is_none()));
+    }
+}
@@ -0,0 +1,294 @@
+//! Display width of text and detection of the terminal width.
+//!
+//! The width tables are an approximation of Unicode's East Asian Width and
+//! emoji presentation data: enough to keep CJK text, emoji markers and
+//! combining marks aligned without pulling in a dependency.
+
+use crate::output::Stream;
+
+/// Zero-width code points: combining marks, joiners and format controls.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x202A, 0x202E),
+    (0x2060, 0x2064),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0E),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0100, 0xE01EF),
+];
+
+/// Code points drawn two columns wide.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x3FFFD),
+];
+
+fn in_table(table: &[(u32, u32)], c: u32) -> bool {
+    table
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                std::cmp::Ordering::Less
+            } else if lo > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Number of terminal columns `c` occupies.
+///
+/// `U+FE0F` (emoji presentation selector) counts as one column, since it
+/// widens the preceding symbol to emoji width in most terminals.
+pub(crate) fn char_width(c: char) -> usize {
+    let c = c as u32;
+    if c < 0x20 || (0x7F..0xA0).contains(&c) || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Number of terminal columns `s` occupies, ignoring ANSI escape sequences.
+pub(crate) fn str_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            width += char_width(c);
+        }
+    }
+    width
+}
+
+/// Consumes the rest of an ANSI escape sequence whose `ESC` was just read.
+fn skip_escape(chars: &mut std::str::Chars<'_>) -> String {
+    let mut seq = String::from('\x1b');
+    for c in chars.by_ref() {
+        seq.push(c);
+        if c.is_ascii_alphabetic() {
+            break;
+        }
+    }
+    seq
+}
+
+/// Splits `line` into pieces no wider than `width` columns.
+///
+/// Breaks at the last space that fits when there is one and mid-word
+/// otherwise, so unbroken blobs such as URLs are still wrapped. Escape
+/// sequences are kept intact and take no room.
+pub(crate) fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    // Byte offset and column just after the last space in `current`.
+    let mut last_space: Option<(usize, usize)> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            current.push_str(&skip_escape(&mut chars));
+            continue;
+        }
+        let w = char_width(c);
+        if current_width + w > width && current_width > 0 {
+            match last_space {
+                Some((at, col)) if c != ' ' => {
+                    let rest = current.split_off(at);
+                    current.pop();
+                    out.push(std::mem::replace(&mut current, rest));
+                    current_width -= col;
+                }
+                _ => {
+                    out.push(std::mem::take(&mut current));
+                    current_width = 0;
+                }
+            }
+            last_space = None;
+            if c == ' ' {
+                continue;
+            }
+        }
+        current.push(c);
+        current_width += w;
+        if c == ' ' {
+            last_space = Some((current.len(), current_width));
+        }
+    }
+    out.push(current);
+    out
+}
+
+/// Width of the terminal attached to `stream`, if any.
+///
+/// `COLUMNS` takes precedence over asking the terminal.
+pub(crate) fn terminal_width(stream: Stream) -> Option<usize> {
+    if let Some(cols) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.trim().parse().ok())
+        .filter(|&c: &usize| c > 0)
+    {
+        return Some(cols);
+    }
+    sys::terminal_width(match stream {
+        Stream::Stdout => 1,
+        Stream::Stderr => 2,
+    })
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+mod sys {
+    use std::os::raw::{c_int, c_ulong};
+
+    #[repr(C)]
+    struct WinSize {
+        ws_row: u16,
+        ws_col: u16,
+        ws_xpixel: u16,
+        ws_ypixel: u16,
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    pub(super) fn terminal_width(fd: c_int) -> Option<usize> {
+        let mut size = WinSize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ writes a `struct winsize` through the pointer,
+        // which matches `WinSize`'s layout.
+        let rc = unsafe { ioctl(fd, TIOCGWINSZ, &mut size as *mut WinSize) };
+        (rc == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+mod sys {
+    pub(super) fn terminal_width(_fd: i32) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_wide_and_zero_width_chars() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("日本"), 4);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("❌"), 2);
+        assert_eq!(str_width("\x1b[31mred\x1b[0m"), 3);
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn hard_wraps_long_words() {
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn wraps_by_display_width() {
+        assert_eq!(wrap("日本語テキスト", 6), ["日本語", "テキス", "ト"]);
+    }
+
+    #[test]
+    fn short_lines_are_untouched() {
+        assert_eq!(wrap("fits", 10), ["fits"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+}
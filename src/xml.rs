@@ -0,0 +1,223 @@
+//! Re-indentation for [`test_log_xml!`](crate::test_log_xml).
+//!
+//! A lenient tokenizer rather than a parser: markup is never rejected, only
+//! laid out one element per line. Elements holding nothing but text stay on
+//! one line, tags and text are otherwise kept as written, and HTML void
+//! elements such as `<br>` do not open a level.
+
+use crate::record::Metadata;
+
+const INDENT: &str = "  ";
+
+/// HTML elements that never have an end tag.
+const VOID: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements whose content is not markup.
+const RAW: [&str; 2] = ["script", "style"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    /// Self-closing and void elements, comments, declarations and
+    /// processing instructions.
+    Empty(&'a str),
+    Text(&'a str),
+}
+
+/// Index just past the `>` that ends the tag starting at `start`, skipping
+/// quoted attribute values.
+fn tag_end(src: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, b) in src.bytes().enumerate().skip(start) {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (Some(q), _) if b == q => quote = None,
+            (None, b'>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The element name of a start or end tag, from after its `<` or `</`.
+fn tag_name(tag: &str) -> &str {
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+/// Whether `rest` starts with markup rather than a lone `<`, as in `a < b`.
+fn is_markup(rest: &str) -> bool {
+    rest.strip_prefix('<')
+        .is_some_and(|r| r.starts_with(|c: char| c.is_alphabetic() || "_/!?".contains(c)))
+}
+
+fn tokenize(src: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < src.len() {
+        let rest = &src[pos..];
+        if !is_markup(rest) {
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(i, _)| is_markup(&rest[i..]))
+                .map_or(src.len(), |(i, _)| pos + i);
+            tokens.push(Token::Text(&src[pos..end]));
+            pos = end;
+            continue;
+        }
+        let delimited = |open: &str, close: &str| {
+            rest.starts_with(open)
+                .then(|| rest[open.len()..].find(close))
+                .map(|found| pos + found.map_or(rest.len(), |i| open.len() + i + close.len()))
+        };
+        let end = if let Some(end) = delimited("<!--", "-->") {
+            tokens.push(Token::Empty(&src[pos..end]));
+            end
+        } else if let Some(end) = delimited("<![CDATA[", "]]>") {
+            tokens.push(Token::Text(&src[pos..end]));
+            end
+        } else if let Some(end) = delimited("<?", "?>") {
+            tokens.push(Token::Empty(&src[pos..end]));
+            end
+        } else if rest.starts_with("</") || rest.starts_with("<!") {
+            let end = tag_end(src, pos).unwrap_or(src.len());
+            let tag = &src[pos..end];
+            tokens.push(if rest.starts_with("</") {
+                Token::Close(tag)
+            } else {
+                Token::Empty(tag)
+            });
+            end
+        } else {
+            let end = tag_end(src, pos).unwrap_or(src.len());
+            let tag = &src[pos..end];
+            let name = tag_name(&tag[1..]);
+            if tag.ends_with("/>") || VOID.iter().any(|v| v.eq_ignore_ascii_case(name)) {
+                tokens.push(Token::Empty(tag));
+                end
+            } else if RAW.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+                tokens.push(Token::Open(tag));
+                let close = format!("</{}", name.to_ascii_lowercase());
+                let body = src[end..]
+                    .to_ascii_lowercase()
+                    .find(&close)
+                    .map_or(src.len(), |i| end + i);
+                if body > end {
+                    tokens.push(Token::Text(&src[end..body]));
+                }
+                body
+            } else {
+                tokens.push(Token::Open(tag));
+                end
+            }
+        };
+        pos = end;
+    }
+    tokens
+}
+
+/// Lays `src` out one element per line, indented by nesting depth.
+pub(crate) fn pretty(src: &str) -> String {
+    let tokens = tokenize(src);
+    let mut out = String::new();
+    let mut line = |depth: usize, text: &str| {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.extend(std::iter::repeat_n(INDENT, depth));
+        out.push_str(text);
+    };
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i..] {
+            [Token::Open(open), Token::Text(text), Token::Close(close), ..]
+                if !text.trim().contains('\n') =>
+            {
+                line(depth, &format!("{open}{}{close}", text.trim()));
+                i += 3;
+            }
+            [Token::Open(open), Token::Close(close), ..] => {
+                line(depth, &format!("{open}{close}"));
+                i += 2;
+            }
+            [Token::Open(open), ..] => {
+                line(depth, open);
+                depth += 1;
+                i += 1;
+            }
+            [Token::Close(close), ..] => {
+                depth = depth.saturating_sub(1);
+                line(depth, close);
+                i += 1;
+            }
+            [Token::Empty(tag), ..] => {
+                line(depth, tag);
+                i += 1;
+            }
+            [Token::Text(text), ..] => {
+                for text in text.lines().map(str::trim).filter(|t| !t.is_empty()) {
+                    line(depth, text);
+                }
+                i += 1;
+            }
+            [] => unreachable!("loop ends before the last token"),
+        }
+    }
+    out
+}
+
+/// Backend of `test_log_xml!`.
+#[doc(hidden)]
+pub fn log_xml(meta: &Metadata, src: &str) {
+    if !crate::emit::enabled(meta) {
+        return;
+    }
+    crate::emit::log(meta, format_args!("{}", pretty(src)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indents_nested_elements() {
+        let src = r#"<?xml version="1.0"?><soap:Envelope xmlns:soap="urn:x"><soap:Body><m:Get filter="a>b"><m:Id> 42 </m:Id><m:Empty/><!-- note --></m:Get></soap:Body></soap:Envelope>"#;
+        assert_eq!(
+            pretty(src),
+            "<?xml version=\"1.0\"?>\n\
+             <soap:Envelope xmlns:soap=\"urn:x\">\n  \
+               <soap:Body>\n    \
+                 <m:Get filter=\"a>b\">\n      \
+                   <m:Id>42</m:Id>\n      \
+                   <m:Empty/>\n      \
+                   <!-- note -->\n    \
+                 </m:Get>\n  \
+               </soap:Body>\n\
+             </soap:Envelope>"
+        );
+    }
+
+    #[test]
+    fn understands_html_void_and_raw_elements() {
+        let src = "<ul><li>a<br>b</li><li><IMG src=x></li></ul><script>if (a<b) {}</script>";
+        assert_eq!(
+            pretty(src),
+            "<ul>\n  <li>\n    a\n    <br>\n    b\n  </li>\n  <li>\n    <IMG src=x>\n  </li>\n</ul>\n\
+             <script>if (a<b) {}</script>"
+        );
+    }
+
+    #[test]
+    fn copes_with_broken_markup() {
+        assert_eq!(pretty("</a></b>1 < 2 <c"), "</a>\n</b>\n1 < 2\n<c");
+        assert_eq!(pretty("plain text"), "plain text");
+    }
+}
@@ -0,0 +1,184 @@
+//! `test_assert!`: an assertion that logs the values of the variables its
+//! condition mentions when it fails.
+//!
+//! Which identifiers are variables is guessed from the tokens alone: an
+//! identifier counts unless it is a keyword, starts with an uppercase
+//! letter (types, variants, constants), is a field or method name, a path
+//! segment, a function or macro name, or a closure parameter.
+
+use crate::{brace, code, describe, is_ident, is_punct, paren, skip_angles};
+use proc_macro::{Delimiter, Ident, Literal, Spacing, TokenStream, TokenTree};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while",
+];
+
+pub(crate) fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let split = top_level_comma(&tokens);
+    let (cond, message) = match split {
+        Some(i) => (&tokens[..i], &tokens[i + 1..]),
+        None => (&tokens[..], &[][..]),
+    };
+    if cond.is_empty() {
+        return Err("test_assert! needs a condition".to_string());
+    }
+    let cond: TokenStream = cond.iter().cloned().collect();
+
+    let mut names = Vec::new();
+    let mut params = Vec::new();
+    collect_variables(cond.clone(), &mut names, &mut params);
+    names.retain(|name: &Ident| !params.iter().any(|p: &String| *p == name.to_string()));
+
+    let mut values = TokenStream::new();
+    for name in names {
+        let mut pair: TokenStream = TokenTree::Literal(Literal::string(&name.to_string())).into();
+        pair.extend(code(","));
+        pair.extend(describe(TokenTree::Ident(name)));
+        values.extend([paren(pair)]);
+        values.extend(code(","));
+    }
+
+    let mut stringify = code("::core::stringify!");
+    stringify.extend([paren(cond.clone())]);
+
+    let message = if message.iter().all(|t| is_punct(t, ',')) {
+        code("::core::option::Option::None")
+    } else {
+        let mut args = code("::core::format_args!");
+        args.extend([paren(message.iter().cloned().collect())]);
+        let mut some = code("::core::option::Option::Some");
+        some.extend([paren(args)]);
+        some
+    };
+
+    let mut call_args = code("&__TESTLOG_METADATA,");
+    call_args.extend(stringify);
+    call_args.extend(code(", &"));
+    call_args.extend([TokenTree::Group(proc_macro::Group::new(
+        Delimiter::Bracket,
+        values,
+    ))]);
+    call_args.extend(code(","));
+    call_args.extend(message);
+
+    let mut failed = code(
+        "#[allow(unused_imports)] \
+         use ::testlog::__private::{TraceDebug as _, TraceOpaque as _}; \
+         static __TESTLOG_METADATA: ::testlog::__private::Metadata = \
+             ::testlog::__private::Metadata::new(\
+                 ::testlog::Level::Error, ::core::module_path!(), ::core::file!(), ::core::line!()) \
+             .krate(::core::option_env!(\"CARGO_PKG_NAME\")); \
+         ::testlog::__private::assert_failed",
+    );
+    failed.extend([paren(call_args)]);
+    failed.extend(code(";"));
+
+    let mut check = code("let __testlog_ok: bool =");
+    check.extend(cond);
+    check.extend(code("; if !__testlog_ok"));
+    check.extend([brace(failed)]);
+
+    let mut out = code("if ::core::cfg!(test)");
+    out.extend([brace(check)]);
+    Ok(brace(out).into())
+}
+
+/// Index of the comma ending the condition. Commas inside a turbofish such
+/// as `HashMap::<K, V>::new()` do not count; other `<` are comparisons.
+pub(crate) fn top_level_comma(tokens: &[TokenTree]) -> Option<usize> {
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_punct(&tokens[i], '<') && i >= 1 && is_path_sep(tokens, i - 1) {
+            i = skip_angles(tokens, i);
+            continue;
+        }
+        if is_punct(&tokens[i], ',') {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether `tokens[i]` is the second colon of a `::`.
+fn is_path_sep(tokens: &[TokenTree], i: usize) -> bool {
+    i >= 1
+        && is_punct(&tokens[i], ':')
+        && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == ':' && p.spacing() == Spacing::Joint)
+}
+
+/// Appends the variables mentioned in `stream` to `names`, once each, and
+/// the names of closure parameters to `params`.
+fn collect_variables(stream: TokenStream, names: &mut Vec<Ident>, params: &mut Vec<String>) {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            TokenTree::Group(group) => {
+                let macro_args = i >= 1 && is_punct(&tokens[i - 1], '!');
+                if !macro_args {
+                    collect_variables(group.stream(), names, params);
+                }
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' && i >= 1 && is_path_sep(&tokens, i - 1) => {
+                i = skip_angles(&tokens, i);
+                continue;
+            }
+            TokenTree::Punct(p) if p.as_char() == '|' && p.spacing() == Spacing::Alone => {
+                // A `|` after an operand is a bitwise or, and the second
+                // half of `||` is not a parameter list either.
+                let opens_closure = i == 0
+                    || matches!(&tokens[i - 1], TokenTree::Punct(q)
+                        if !(q.as_char() == '|' && q.spacing() == Spacing::Joint))
+                    || is_ident(&tokens[i - 1], "move");
+                if opens_closure {
+                    i += 1;
+                    while i < tokens.len() && !is_punct(&tokens[i], '|') {
+                        if let TokenTree::Ident(param) = &tokens[i] {
+                            params.push(param.to_string());
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            TokenTree::Ident(ident) => {
+                if is_variable(&tokens, i)
+                    && !names.iter().any(|n| n.to_string() == ident.to_string())
+                {
+                    names.push(ident.clone());
+                }
+                if is_ident(&tokens[i], "as") {
+                    // Skip the target type of a cast.
+                    i += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+fn is_variable(tokens: &[TokenTree], i: usize) -> bool {
+    let name = tokens[i].to_string();
+    if KEYWORDS.contains(&name.as_str()) || name.starts_with(|c: char| c.is_uppercase()) {
+        return false;
+    }
+    if i >= 1
+        && (is_punct(&tokens[i - 1], '.')
+            || is_punct(&tokens[i - 1], '\'')
+            || is_path_sep(tokens, i - 1))
+    {
+        return false;
+    }
+    match tokens.get(i + 1) {
+        Some(TokenTree::Punct(p)) => !matches!(p.as_char(), '!' | ':'),
+        Some(TokenTree::Group(g)) => {
+            g.delimiter() != Delimiter::Parenthesis && g.delimiter() != Delimiter::Brace
+        }
+        _ => true,
+    }
+}
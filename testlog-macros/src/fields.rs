@@ -0,0 +1,92 @@
+//! `test_log_fields_of!`: logs selected fields of a value instead of its
+//! whole `Debug` output.
+//!
+//! Each argument after the value is either a field path such as `name`,
+//! `limits.max` or `items.len()`, read from the value, or `label = expr`,
+//! an expression of the caller's own.
+
+use crate::assert::top_level_comma;
+use crate::{brace, code, describe, is_punct, paren};
+use proc_macro::{Delimiter, Group, Literal, Spacing, TokenStream, TokenTree};
+
+pub(crate) fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut parts = Vec::new();
+    let mut rest = &tokens[..];
+    while !rest.is_empty() {
+        match top_level_comma(rest) {
+            Some(i) => {
+                parts.push(&rest[..i]);
+                rest = &rest[i + 1..];
+            }
+            None => {
+                parts.push(rest);
+                rest = &[];
+            }
+        }
+    }
+    let (value, fields) = match parts.split_first() {
+        Some((value, fields)) if !value.is_empty() && !fields.is_empty() => (*value, fields),
+        _ => return Err("test_log_fields_of! needs a value and at least one field".to_string()),
+    };
+    let value: TokenStream = value.iter().cloned().collect();
+
+    let mut pairs = TokenStream::new();
+    for field in fields.iter().filter(|f| !f.is_empty()) {
+        let (label, expr) = match field {
+            [TokenTree::Ident(label), TokenTree::Punct(eq), expr @ ..]
+                if eq.as_char() == '=' && eq.spacing() == Spacing::Alone && !expr.is_empty() =>
+            {
+                let label: TokenStream =
+                    TokenTree::Literal(Literal::string(&label.to_string())).into();
+                (label, expr.iter().cloned().collect::<TokenStream>())
+            }
+            path => {
+                if is_punct(&path[0], '.') {
+                    return Err(
+                        "fields of test_log_fields_of! are written without a leading `.`"
+                            .to_string(),
+                    );
+                }
+                let mut label = code("::core::stringify!");
+                label.extend([paren(path.iter().cloned().collect())]);
+                let mut expr = code("__testlog_value.");
+                expr.extend(path.iter().cloned());
+                (label, expr)
+            }
+        };
+        let mut pair = label;
+        pair.extend(code(","));
+        pair.extend(describe(paren(expr)));
+        pairs.extend([paren(pair)]);
+        pairs.extend(code(","));
+    }
+
+    let mut stringify = code("::core::stringify!");
+    stringify.extend([paren(value.clone())]);
+    let mut call_args = code("&__TESTLOG_METADATA,");
+    call_args.extend(stringify);
+    call_args.extend(code(", &"));
+    call_args.extend([TokenTree::Group(Group::new(Delimiter::Bracket, pairs))]);
+
+    let mut log = code("let __testlog_value = &");
+    log.extend([paren(value)]);
+    log.extend(code("; ::testlog::__private::log_fields"));
+    log.extend([paren(call_args)]);
+    log.extend(code(";"));
+
+    let mut body = code(
+        "#[allow(unused_imports)] \
+         use ::testlog::__private::{TraceDebug as _, TraceOpaque as _}; \
+         static __TESTLOG_METADATA: ::testlog::__private::Metadata = \
+             ::testlog::__private::Metadata::new(\
+                 ::testlog::Level::Info, ::core::module_path!(), ::core::file!(), ::core::line!()) \
+             .krate(::core::option_env!(\"CARGO_PKG_NAME\")); \
+         if ::testlog::__private::enabled(&__TESTLOG_METADATA)",
+    );
+    body.extend([brace(log)]);
+
+    let mut out = code("if ::core::cfg!(test)");
+    out.extend([brace(body)]);
+    Ok(brace(out).into())
+}
@@ -0,0 +1,133 @@
+//! Attribute macros for [testlog](https://docs.rs/testlog).
+//!
+//! Use them through their re-exports in `testlog`; the generated code
+//! refers to `::testlog` paths. The crate has no dependencies, so the
+//! parsing here only understands as much Rust syntax as it needs to.
+
+use proc_macro::{Delimiter, Group, Literal, Punct, Spacing, TokenStream, TokenTree};
+
+mod assert;
+mod fields;
+mod test;
+mod trace;
+
+/// See `testlog::test_assert`.
+#[proc_macro]
+pub fn test_assert(input: TokenStream) -> TokenStream {
+    assert::expand(input).unwrap_or_else(|msg| compile_error(&msg))
+}
+
+/// See `testlog::test_log_fields_of`.
+#[proc_macro]
+pub fn test_log_fields_of(input: TokenStream) -> TokenStream {
+    fields::expand(input).unwrap_or_else(|msg| compile_error(&msg))
+}
+
+/// See `testlog::trace_impl`.
+#[proc_macro_attribute]
+pub fn trace_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    trace::expand(attr, item).unwrap_or_else(|msg| compile_error(&msg))
+}
+
+/// See `testlog::test`.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    test::expand("::core::prelude::v1::test", attr, item, false)
+        .unwrap_or_else(|msg| compile_error(&msg))
+}
+
+/// See `testlog::tokio_test`.
+#[proc_macro_attribute]
+pub fn tokio_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    test::expand("::tokio::test", attr, item, true).unwrap_or_else(|msg| compile_error(&msg))
+}
+
+/// Parses a fixed snippet of generated code.
+fn code(src: &str) -> TokenStream {
+    src.parse().expect("generated code is valid")
+}
+
+fn compile_error(msg: &str) -> TokenStream {
+    let mut out = code("::core::compile_error!");
+    out.extend([TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        TokenStream::from(TokenTree::Literal(Literal::string(msg))),
+    ))]);
+    out.extend([TokenTree::Punct(Punct::new(';', Spacing::Alone))]);
+    out
+}
+
+fn paren(inner: TokenStream) -> TokenTree {
+    TokenTree::Group(Group::new(Delimiter::Parenthesis, inner))
+}
+
+fn brace(inner: TokenStream) -> TokenTree {
+    TokenTree::Group(Group::new(Delimiter::Brace, inner))
+}
+
+/// `(&::testlog::__private::TraceValue(&value)).describe()`, which uses
+/// `Debug` when the value implements it.
+fn describe(value: TokenTree) -> TokenStream {
+    let mut inner = code("&::testlog::__private::TraceValue");
+    let mut arg = code("&");
+    arg.extend([value]);
+    inner.extend([paren(arg)]);
+    let mut out: TokenStream = paren(inner).into();
+    out.extend(code(".describe()"));
+    out
+}
+
+fn is_ident(tt: &TokenTree, name: &str) -> bool {
+    matches!(tt, TokenTree::Ident(i) if i.to_string() == name)
+}
+
+fn is_punct(tt: &TokenTree, ch: char) -> bool {
+    matches!(tt, TokenTree::Punct(p) if p.as_char() == ch)
+}
+
+/// Index just past the `<...>` starting at `start`, which must be a `<`.
+/// `->` inside the brackets, as in `F: Fn() -> u8`, is not a closing `>`.
+fn skip_angles(tokens: &[TokenTree], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut arrow = false;
+    for (i, tt) in tokens.iter().enumerate().skip(start) {
+        if let TokenTree::Punct(p) = tt {
+            match p.as_char() {
+                '<' => depth += 1,
+                '>' if !arrow => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+            arrow = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+        } else {
+            arrow = false;
+        }
+    }
+    tokens.len()
+}
+
+/// Splits `tokens` on commas that are not nested inside `<...>`.
+fn split_commas(tokens: &[TokenTree]) -> Vec<&[TokenTree]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_punct(&tokens[i], '<') {
+            i = skip_angles(tokens, i);
+            continue;
+        }
+        if is_punct(&tokens[i], ',') {
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+        i += 1;
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
@@ -0,0 +1,81 @@
+//! Test attributes that add testlog's per-test setup to a test harness
+//! attribute such as `#[tokio::test]`.
+//!
+//! testlog's own options, `check_env`, `strict` and `baseline`, are taken
+//! out of the attribute's arguments; the rest are passed on to the harness.
+
+use crate::{code, is_ident, split_commas};
+use proc_macro::{Delimiter, Group, Literal, TokenStream, TokenTree};
+
+/// Emits `#[<harness>(<attr>)]` followed by `item`, with the body wrapped
+/// so that a `TestGuard` lives for the whole test.
+pub(crate) fn expand(
+    harness: &str,
+    attr: TokenStream,
+    item: TokenStream,
+    require_async: bool,
+) -> Result<TokenStream, String> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g,
+        _ => return Err("expected a test function".into()),
+    };
+    let name = tokens
+        .iter()
+        .position(|t| is_ident(t, "fn"))
+        .and_then(|i| tokens.get(i + 1))
+        .map(|t| t.to_string())
+        .ok_or("expected a test function")?;
+    if require_async && !tokens.iter().any(|t| is_ident(t, "async")) {
+        return Err(format!("`{name}` must be an `async fn`"));
+    }
+
+    let attr: Vec<TokenTree> = attr.into_iter().collect();
+    let mut check_env = false;
+    let mut strict = false;
+    let mut baseline = false;
+    let mut passed = TokenStream::new();
+    for arg in split_commas(&attr) {
+        match arg {
+            [option] if is_ident(option, "check_env") => check_env = true,
+            [option] if is_ident(option, "strict") => strict = true,
+            [option] if is_ident(option, "baseline") => baseline = true,
+            _ => {
+                if !passed.is_empty() {
+                    passed.extend(code(","));
+                }
+                passed.extend(arg.iter().cloned());
+            }
+        }
+    }
+
+    let mut out = code("#");
+    let mut harness = code(harness);
+    if !passed.is_empty() {
+        harness.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, passed))]);
+    }
+    out.extend([TokenTree::Group(Group::new(Delimiter::Bracket, harness))]);
+    out.extend(tokens);
+
+    let mut path = code("::core::module_path!(),");
+    path.extend([TokenTree::Literal(Literal::string(&format!("::{name}")))]);
+    let mut concat = code("::core::concat!");
+    concat.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, path))]);
+    let mut wrapped = code("let _testlog_test = ::testlog::__private::TestGuard::begin");
+    wrapped.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, concat))]);
+    if check_env {
+        wrapped.extend(code(".check_env()"));
+    }
+    if strict {
+        wrapped.extend(code(".strict()"));
+    }
+    if baseline {
+        wrapped.extend(code(".baseline()"));
+    }
+    wrapped.extend(code(";"));
+    wrapped.extend(body.stream());
+    let mut wrapped = Group::new(Delimiter::Brace, wrapped);
+    wrapped.set_span(body.span());
+    out.extend([TokenTree::Group(wrapped)]);
+    Ok(out)
+}
@@ -0,0 +1,339 @@
+//! `#[trace_impl]`: logs entry, arguments and return value of every method
+//! in an impl block.
+//!
+//! Each method is emitted twice: unchanged under `#[cfg(not(test))]`, and
+//! instrumented under `#[cfg(test)]`. The instrumented body runs the
+//! original one in an immediately called closure so that `return` and `?`
+//! keep working while the return value is still observed on the way out.
+
+use crate::{code, describe, is_ident, is_punct, paren, skip_angles, split_commas};
+use proc_macro::{Delimiter, Group, Ident, Literal, TokenStream, TokenTree};
+
+const NOT_IMPL: &str = "`trace_impl` can only be applied to an impl block";
+
+pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> Result<TokenStream, String> {
+    let level = level(attr)?;
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g,
+        _ => return Err(NOT_IMPL.into()),
+    };
+    let ty = self_type_name(&tokens).ok_or(NOT_IMPL)?;
+    let mut group = Group::new(Delimiter::Brace, items(body.stream(), &ty, &level));
+    group.set_span(body.span());
+    let mut out: TokenStream = tokens.into_iter().collect();
+    out.extend([TokenTree::Group(group)]);
+    Ok(out)
+}
+
+/// The level path for the attribute's optional argument, e.g. `(debug)`.
+fn level(attr: TokenStream) -> Result<String, String> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    let name = match tokens.as_slice() {
+        [] => "trace".to_string(),
+        [TokenTree::Ident(level)] => level.to_string(),
+        _ => return Err("expected `#[trace_impl]` or `#[trace_impl(<level>)]`".into()),
+    };
+    let variant = match name.as_str() {
+        "error" => "Error",
+        "warn" => "Warn",
+        "info" => "Info",
+        "debug" => "Debug",
+        "trace" => "Trace",
+        _ => {
+            return Err(format!(
+                "unknown level `{name}`; expected error, warn, info, debug or trace"
+            ))
+        }
+    };
+    Ok(format!("::testlog::Level::{variant}"))
+}
+
+/// Name of the type being implemented, without path or generics:
+/// `Machine` for `impl<T> fmt::Debug for state::Machine<T>`.
+fn self_type_name(header: &[TokenTree]) -> Option<String> {
+    let mut start = header.iter().position(|t| is_ident(t, "impl"))? + 1;
+    if header.get(start).is_some_and(|t| is_punct(t, '<')) {
+        start = skip_angles(header, start);
+    }
+    let mut ty = &header[start..];
+    let mut i = 0;
+    while i < ty.len() {
+        if is_punct(&ty[i], '<') {
+            i = skip_angles(ty, i);
+            continue;
+        }
+        if is_ident(&ty[i], "for") {
+            ty = &ty[i + 1..];
+            break;
+        }
+        if is_ident(&ty[i], "where") {
+            ty = &ty[..i];
+            break;
+        }
+        i += 1;
+    }
+    let end = ty.iter().position(|t| is_punct(t, '<')).unwrap_or(ty.len());
+    ty[..end].iter().rev().find_map(|t| match t {
+        TokenTree::Ident(name) => Some(name.to_string()),
+        _ => None,
+    })
+}
+
+/// Splits the impl body into items and instruments the methods.
+fn items(stream: TokenStream, ty: &str, level: &str) -> TokenStream {
+    let mut out = TokenStream::new();
+    let mut item: Vec<TokenTree> = Vec::new();
+    for tt in stream {
+        let ends = match &tt {
+            TokenTree::Punct(p) => p.as_char() == ';',
+            TokenTree::Group(g) => match g.delimiter() {
+                // A method body, or the body of a macro invocation.
+                Delimiter::Brace => {
+                    Method::parse(&item).is_some() || item.last().is_some_and(|t| is_punct(t, '!'))
+                }
+                // An inner attribute such as `#![allow(...)]`.
+                Delimiter::Bracket => {
+                    item.len() == 2 && is_punct(&item[0], '#') && is_punct(&item[1], '!')
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        item.push(tt);
+        if ends {
+            let item = std::mem::take(&mut item);
+            let (tokens, body) = item.split_at(item.len() - 1);
+            match (Method::parse(tokens), &body[0]) {
+                (Some(method), TokenTree::Group(body)) if method.traced() => {
+                    out.extend(method.instrument(body, ty, level));
+                }
+                (Some(method), _) => {
+                    out.extend(method.attrs);
+                    out.extend(method.sig);
+                    out.extend(body.iter().cloned());
+                }
+                (None, _) => out.extend(item),
+            }
+        }
+    }
+    out.extend(item);
+    out
+}
+
+/// A method signature, split into the parts the instrumentation needs.
+struct Method {
+    /// Outer attributes, minus `#[trace_impl(skip)]`.
+    attrs: Vec<TokenTree>,
+    /// Everything from the visibility up to the body.
+    sig: Vec<TokenTree>,
+    name: String,
+    params: Vec<TokenTree>,
+    ret: Option<Vec<TokenTree>>,
+    skip: bool,
+    /// `const` and `async` methods are left alone: the former cannot log
+    /// and the latter would need a future wrapper.
+    unsupported: bool,
+}
+
+impl Method {
+    /// Parses the tokens before a brace group, if they form a method
+    /// signature.
+    fn parse(tokens: &[TokenTree]) -> Option<Method> {
+        let mut attrs = Vec::new();
+        let mut skip = false;
+        let mut i = 0;
+        while let (Some(hash), Some(TokenTree::Group(attr))) = (tokens.get(i), tokens.get(i + 1)) {
+            if !is_punct(hash, '#') || attr.delimiter() != Delimiter::Bracket {
+                break;
+            }
+            if is_skip(attr) {
+                skip = true;
+            } else {
+                attrs.extend(tokens[i..i + 2].iter().cloned());
+            }
+            i += 2;
+        }
+        let sig = tokens[i..].to_vec();
+        if tokens.get(i).is_some_and(|t| is_ident(t, "pub")) {
+            i += 1;
+            if let Some(TokenTree::Group(g)) = tokens.get(i) {
+                if g.delimiter() == Delimiter::Parenthesis {
+                    i += 1;
+                }
+            }
+        }
+        let mut unsupported = false;
+        loop {
+            match tokens.get(i)? {
+                TokenTree::Ident(q) if matches!(q.to_string().as_str(), "const" | "async") => {
+                    unsupported = true;
+                }
+                TokenTree::Ident(q)
+                    if matches!(q.to_string().as_str(), "default" | "unsafe" | "extern") => {}
+                TokenTree::Literal(_) => {}
+                _ => break,
+            }
+            i += 1;
+        }
+        if !is_ident(tokens.get(i)?, "fn") {
+            return None;
+        }
+        let name = match tokens.get(i + 1)? {
+            TokenTree::Ident(name) => name.to_string(),
+            _ => return None,
+        };
+        i += 2;
+        if tokens.get(i).is_some_and(|t| is_punct(t, '<')) {
+            i = skip_angles(tokens, i);
+        }
+        let params = match tokens.get(i)? {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis => {
+                g.stream().into_iter().collect()
+            }
+            _ => return None,
+        };
+        i += 1;
+        let mut ret = None;
+        if tokens.get(i).is_some_and(|t| is_punct(t, '-'))
+            && tokens.get(i + 1).is_some_and(|t| is_punct(t, '>'))
+        {
+            let rest = &tokens[i + 2..];
+            let mut end = 0;
+            while end < rest.len() && !is_ident(&rest[end], "where") {
+                end = if is_punct(&rest[end], '<') {
+                    skip_angles(rest, end)
+                } else {
+                    end + 1
+                };
+            }
+            ret = Some(rest[..end].to_vec());
+        }
+        Some(Method {
+            attrs,
+            sig,
+            name,
+            params,
+            ret,
+            skip,
+            unsupported,
+        })
+    }
+
+    /// Whether the method gets instrumented at all.
+    fn traced(&self) -> bool {
+        let diverges = self
+            .ret
+            .as_ref()
+            .is_some_and(|ret| ret.len() == 1 && is_punct(&ret[0], '!'));
+        !self.skip && !self.unsupported && !diverges
+    }
+
+    /// Names of the parameters that are plain bindings, in order.
+    fn arg_names(&self) -> Vec<Ident> {
+        split_commas(&self.params)
+            .into_iter()
+            .filter_map(|param| {
+                let mut param = param;
+                while param.len() >= 2 && is_punct(&param[0], '#') {
+                    param = &param[2..];
+                }
+                let colon = (0..param.len()).find(|&i| {
+                    is_punct(&param[i], ':')
+                        && !param.get(i + 1).is_some_and(|t| is_punct(t, ':'))
+                        && !(i > 0 && is_punct(&param[i - 1], ':'))
+                })?;
+                match &param[..colon] {
+                    [TokenTree::Ident(name)] | [_, TokenTree::Ident(name)]
+                        if name.to_string() != "self"
+                            && name.to_string() != "_"
+                            && (colon == 1 || is_ident(&param[0], "mut")) =>
+                    {
+                        Some(name.clone())
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn instrument(self, body: &Group, ty: &str, level: &str) -> TokenStream {
+        let mut out = code("#[cfg(not(test))]");
+        out.extend(self.attrs.iter().cloned());
+        out.extend(self.sig.iter().cloned());
+        out.extend([TokenTree::Group(body.clone())]);
+
+        let mut args = TokenStream::new();
+        for name in self.arg_names() {
+            let mut arg: TokenStream =
+                TokenTree::Literal(Literal::string(&name.to_string())).into();
+            arg.extend(code(","));
+            arg.extend(describe(TokenTree::Ident(name)));
+            args.extend([paren(arg)]);
+            args.extend(code(","));
+        }
+
+        let mut enter = code(&format!(
+            "&__TESTLOG_METADATA, \"{ty}::{}\", || ::std::vec!",
+            self.name
+        ));
+        enter.extend([TokenTree::Group(Group::new(Delimiter::Bracket, args))]);
+
+        let mut closure = code("move ||");
+        // `impl Trait` is not allowed in a closure's return type; such
+        // bodies rely on inference instead.
+        match &self.ret {
+            Some(ret) if !ret.iter().any(|t| is_ident(t, "impl")) => {
+                closure.extend(code("->"));
+                closure.extend(ret.iter().cloned());
+            }
+            _ => {}
+        }
+        closure.extend([TokenTree::Group(body.clone())]);
+
+        let mut traced = code(&format!(
+            "#[allow(unused_imports)] \
+             use ::testlog::__private::{{TraceDebug as _, TraceOpaque as _}}; \
+             static __TESTLOG_METADATA: ::testlog::__private::Metadata = \
+                 ::testlog::__private::Metadata::new(\
+                     {level}, ::core::module_path!(), ::core::file!(), ::core::line!()) \
+                 .krate(::core::option_env!(\"CARGO_PKG_NAME\")); \
+             let __testlog_span = ::testlog::__private::TraceSpan::enter"
+        ));
+        traced.extend([paren(enter)]);
+        traced.extend(code(
+            "; #[allow(clippy::redundant_closure_call)] let __testlog_ret =",
+        ));
+        traced.extend([paren(closure), paren(TokenStream::new())]);
+        traced.extend(code(";"));
+        if self.ret.is_some() {
+            let mut value = code("||");
+            value.extend(describe(code("__testlog_ret").into_iter().next().unwrap()));
+            traced.extend(code("__testlog_span.returned"));
+            traced.extend([paren(value)]);
+            traced.extend(code(";"));
+        } else {
+            traced.extend(code("__testlog_span.finished();"));
+        }
+        traced.extend(code("__testlog_ret"));
+        let mut traced = Group::new(Delimiter::Brace, traced);
+        traced.set_span(body.span());
+
+        out.extend(code("#[cfg(test)]"));
+        out.extend(self.attrs);
+        out.extend(self.sig);
+        out.extend([TokenTree::Group(traced)]);
+        out
+    }
+}
+
+/// Whether an attribute is `#[trace_impl(skip)]`, possibly path-qualified.
+fn is_skip(attr: &Group) -> bool {
+    let tokens: Vec<TokenTree> = attr.stream().into_iter().collect();
+    match tokens.as_slice() {
+        [.., name, TokenTree::Group(args)] => {
+            is_ident(name, "trace_impl") && args.stream().to_string().trim() == "skip"
+        }
+        _ => false,
+    }
+}